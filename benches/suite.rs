@@ -0,0 +1,418 @@
+// Throughput and allocations of the lexer, the parser and the converter on generated inputs
+// of different shapes, run with
+//
+//     cargo bench --bench suite -- [filter] [--time <ms>] [--save <file>] [--compare <file>]
+//         [--threshold <percent>]
+//
+// Benchmarks whose name contains `filter` run for about `--time` each. `--save` writes the
+// results as JSON and `--compare` prints the change against saved ones and exits with 1 when
+// one is slower by more than `--threshold`, so two builds are compared by saving with one and
+// comparing with the other.
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::fs;
+use std::hint::black_box;
+use std::process::exit;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use toml_parser::json::{convert_json_to_string, parse_json, JSONValue};
+use toml_parser::parser::ast::AST;
+use toml_parser::parser::converter::convert_ast_to_string;
+use toml_parser::parser::lexer::Lexer;
+use toml_parser::parser::parser::Parser;
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATED_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        return System.alloc(layout);
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(new_size, Ordering::Relaxed);
+        return System.realloc(ptr, layout, new_size);
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+const SAMPLES: usize = 20;
+
+// Tables of keys with every kind of value, like a generated service config
+fn generate_service_config() -> String {
+    let mut input: String = String::new();
+
+    for i in 0..5000 {
+        input.push_str(format!("[service{}]\n", i).as_str());
+        input.push_str(format!("name = \"service-{}\"\n", i).as_str());
+        input.push_str(format!("port = {}\n", 8000 + i % 1000).as_str());
+        input.push_str("enabled = true\n");
+        input.push_str("started = 2024-01-01T00:00:00Z\n");
+        input.push_str("hosts = [\"a.example.com\", \"b.example.com\", \"c.example.com\"]\n");
+        input.push_str("limits = [[1, 2], [3, 4]]\n\n");
+    }
+
+    return input;
+}
+
+// Many tables with a few keys each
+fn generate_small_tables() -> String {
+    let mut input: String = String::new();
+
+    for i in 0..20000 {
+        input.push_str(format!("[table{}]\n", i).as_str());
+        input.push_str(format!("id = {}\n", i).as_str());
+        input.push_str("name = \"small\"\n");
+        input.push_str("enabled = true\n\n");
+    }
+
+    return input;
+}
+
+// One array of numbers over many lines
+fn generate_huge_array() -> String {
+    let mut input: String = String::from("values = [\n");
+
+    for i in 0..200000 {
+        input.push_str(format!("{}, ", i).as_str());
+
+        if i % 20 == 19 {
+            input.push('\n');
+        }
+    }
+
+    input.push_str("0\n]\n");
+
+    return input;
+}
+
+// Dotted tables many levels down and arrays nested in arrays
+fn generate_deep_nesting() -> String {
+    let mut input: String = String::new();
+
+    for i in 0..200 {
+        let mut name: String = format!("root{}", i);
+
+        for level in 0..30 {
+            name.push_str(format!(".level{}", level).as_str());
+            input.push_str(format!("[{}]\n", name).as_str());
+            input.push_str(format!("depth = {}\n", level).as_str());
+        }
+
+        let depth: usize = 50;
+        input
+            .push_str(format!("nested = {}1{}\n\n", "[".repeat(depth), "]".repeat(depth)).as_str());
+    }
+
+    return input;
+}
+
+// Few keys with long string values
+fn generate_long_strings() -> String {
+    let mut input: String = String::new();
+
+    for i in 0..100 {
+        let text: String = "lorem ipsum dolor sit amet ".repeat(400);
+
+        input.push_str(format!("text{} = \"{}\"\n", i, text).as_str());
+    }
+
+    return input;
+}
+
+struct Measurement {
+    name: String,
+    // Per iteration, sorted
+    samples: Vec<Duration>,
+    size: usize,
+    allocations: usize,
+    allocated_bytes: usize,
+}
+
+impl Measurement {
+    fn get_median(&self) -> Duration {
+        return self.samples[self.samples.len() / 2];
+    }
+
+    fn get_throughput(&self) -> f64 {
+        return self.size as f64 / self.get_median().as_secs_f64() / 1e6;
+    }
+
+    // Half the distance between the quartiles relative to the median
+    fn get_spread(&self) -> f64 {
+        let low: Duration = self.samples[self.samples.len() / 4];
+        let high: Duration = self.samples[self.samples.len() * 3 / 4];
+
+        return (high - low).as_secs_f64() / 2.0 / self.get_median().as_secs_f64() * 100.0;
+    }
+}
+
+// Warms up, then takes `SAMPLES` samples of as many iterations as fit in their share of `time`
+fn measure<T>(name: &str, size: usize, time: Duration, f: impl Fn() -> T) -> Measurement {
+    let allocations: usize = ALLOCATIONS.load(Ordering::Relaxed);
+    let allocated_bytes: usize = ALLOCATED_BYTES.load(Ordering::Relaxed);
+
+    let start: Instant = Instant::now();
+    black_box(f());
+    let once: Duration = start.elapsed();
+
+    let allocations: usize = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+    let allocated_bytes: usize = ALLOCATED_BYTES.load(Ordering::Relaxed) - allocated_bytes;
+
+    let warm_up: Instant = Instant::now();
+    while warm_up.elapsed() < time / 5 {
+        black_box(f());
+    }
+
+    let per_sample: Duration = time / SAMPLES as u32;
+    let iterations: u32 = (per_sample.as_secs_f64() / once.as_secs_f64().max(1e-9)).max(1.0) as u32;
+
+    let mut samples: Vec<Duration> = vec![];
+
+    for _ in 0..SAMPLES {
+        let start: Instant = Instant::now();
+
+        for _ in 0..iterations {
+            black_box(f());
+        }
+
+        samples.push(start.elapsed() / iterations);
+    }
+
+    samples.sort();
+
+    return Measurement {
+        name: String::from(name),
+        samples,
+        size,
+        allocations,
+        allocated_bytes,
+    };
+}
+
+fn convert_measurements_to_json(measurements: &[Measurement]) -> JSONValue {
+    return JSONValue::Object(vec![(
+        String::from("benchmarks"),
+        JSONValue::Array(
+            measurements
+                .iter()
+                .map(|m| {
+                    JSONValue::Object(vec![
+                        (String::from("name"), JSONValue::String(m.name.clone())),
+                        (
+                            String::from("median_ns"),
+                            JSONValue::Int(m.get_median().as_nanos() as i64),
+                        ),
+                        (String::from("size"), JSONValue::Int(m.size as i64)),
+                        (
+                            String::from("allocations"),
+                            JSONValue::Int(m.allocations as i64),
+                        ),
+                        (
+                            String::from("allocated_bytes"),
+                            JSONValue::Int(m.allocated_bytes as i64),
+                        ),
+                    ])
+                })
+                .collect(),
+        ),
+    )]);
+}
+
+// Median time and allocations by name
+fn read_baseline(filepath: &str) -> Result<Vec<(String, i64, i64)>, String> {
+    let content: String = fs::read_to_string(filepath)
+        .map_err(|e| format!("Failed to read `{}`: {}", filepath, e))?;
+    let value: JSONValue =
+        parse_json(content.as_str()).map_err(|e| format!("`{}`: {}", filepath, e))?;
+
+    let benchmarks: &Vec<JSONValue> = match value.get("benchmarks") {
+        Some(JSONValue::Array(benchmarks)) => benchmarks,
+        _ => return Err(format!("`{}` has no `benchmarks`.", filepath)),
+    };
+
+    return Ok(benchmarks.iter().filter_map(read_benchmark).collect());
+}
+
+fn read_benchmark(benchmark: &JSONValue) -> Option<(String, i64, i64)> {
+    return Some((
+        String::from(benchmark.get("name")?.as_str()?),
+        benchmark.get("median_ns")?.as_i64()?,
+        benchmark.get("allocations")?.as_i64()?,
+    ));
+}
+
+fn format_change(old: f64, new: f64) -> String {
+    return if old == 0.0 {
+        String::from("n/a")
+    } else {
+        format!("{:+.1}%", (new - old) / old * 100.0)
+    };
+}
+
+fn parse_option<T: std::str::FromStr>(args: &[String], i: usize, name: &str) -> T {
+    return match args.get(i).and_then(|v| v.parse::<T>().ok()) {
+        Some(value) => value,
+        None => {
+            eprintln!("[ERROR] `{}` needs a valid value.", name);
+            exit(2);
+        }
+    };
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    let mut filter: Option<String> = None;
+    let mut time: Duration = Duration::from_millis(1000);
+    let mut save: Option<String> = None;
+    let mut compare: Option<String> = None;
+    let mut threshold: f64 = 5.0;
+    let mut i: usize = 0;
+
+    while i < args.len() {
+        match args[i].as_str() {
+            "--time" => {
+                time = Duration::from_millis(parse_option(&args, i + 1, "--time"));
+                i += 1;
+            }
+            "--save" => {
+                save = Some(parse_option(&args, i + 1, "--save"));
+                i += 1;
+            }
+            "--compare" => {
+                compare = Some(parse_option(&args, i + 1, "--compare"));
+                i += 1;
+            }
+            "--threshold" => {
+                threshold = parse_option(&args, i + 1, "--threshold");
+                i += 1;
+            }
+            // Passed by `cargo bench`
+            "--bench" => {}
+            arg => filter = Some(String::from(arg)),
+        }
+
+        i += 1;
+    }
+
+    let inputs: [(&str, String); 5] = [
+        ("service-config", generate_service_config()),
+        ("small-tables", generate_small_tables()),
+        ("huge-array", generate_huge_array()),
+        ("deep-nesting", generate_deep_nesting()),
+        ("long-strings", generate_long_strings()),
+    ];
+
+    let is_selected = |name: &str| filter.as_ref().map_or(true, |f| name.contains(f.as_str()));
+
+    let mut measurements: Vec<Measurement> = vec![];
+
+    for (shape, input) in &inputs {
+        let size: usize = input.len();
+        let ast: AST = Parser::from_string(input.as_str()).parse();
+
+        let name: String = format!("lexer/{}", shape);
+        if is_selected(name.as_str()) {
+            measurements.push(measure(name.as_str(), size, time, || {
+                let mut lexer: Lexer = Lexer::from_string(input.as_str());
+                let mut count: usize = 0;
+
+                while let Some(token) = lexer.get_next_token() {
+                    black_box(&token);
+                    count += 1;
+                }
+
+                return count;
+            }));
+        }
+
+        let name: String = format!("parser/{}", shape);
+        if is_selected(name.as_str()) {
+            measurements.push(measure(name.as_str(), size, time, || {
+                Parser::from_string(input.as_str()).parse()
+            }));
+        }
+
+        let name: String = format!("converter/{}", shape);
+        if is_selected(name.as_str()) {
+            measurements.push(measure(name.as_str(), size, time, || {
+                convert_ast_to_string(&ast, true)
+            }));
+        }
+    }
+
+    let baseline: Option<Vec<(String, i64, i64)>> = compare.as_ref().map(|filepath| {
+        return match read_baseline(filepath) {
+            Ok(baseline) => baseline,
+            Err(e) => {
+                eprintln!("[ERROR] {}", e);
+                exit(2);
+            }
+        };
+    });
+
+    let mut regressions: usize = 0;
+
+    for m in &measurements {
+        print!(
+            "{:<26} {:>10.2?} ±{:>4.1}% {:>9.1} MB/s {:>9} allocations {:>12} bytes",
+            m.name,
+            m.get_median(),
+            m.get_spread(),
+            m.get_throughput(),
+            m.allocations,
+            m.allocated_bytes
+        );
+
+        let old = baseline
+            .as_ref()
+            .and_then(|b| b.iter().find(|(name, _, _)| *name == m.name));
+
+        if let Some((_, median, allocations)) = old {
+            let new: f64 = m.get_median().as_nanos() as f64;
+            let regressed: bool = new > *median as f64 * (1.0 + threshold / 100.0);
+
+            print!(
+                "  time {} allocations {}{}",
+                format_change(*median as f64, new),
+                format_change(*allocations as f64, m.allocations as f64),
+                if regressed { "  REGRESSED" } else { "" }
+            );
+
+            if regressed {
+                regressions += 1;
+            }
+        }
+
+        println!();
+    }
+
+    if let Some(filepath) = save {
+        let json: String =
+            convert_json_to_string(&convert_measurements_to_json(&measurements), true);
+
+        if let Err(e) = fs::write(&filepath, json + "\n") {
+            eprintln!("[ERROR] Failed to write `{}`: {}", filepath, e);
+            exit(2);
+        }
+    }
+
+    if regressions > 0 {
+        eprintln!(
+            "[ERROR] {} benchmarks are slower by more than {}%.",
+            regressions, threshold
+        );
+        exit(1);
+    }
+}
@@ -0,0 +1,143 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::ext::IdentExt;
+use syn::{parse_macro_input, Data, DeriveInput, Field, Fields, LitStr, Type};
+
+enum FieldDefault {
+    None,
+    Default,
+    Function(syn::Path),
+}
+
+struct FieldOptions {
+    rename: Option<String>,
+    default: FieldDefault,
+}
+
+fn parse_field_options(field: &Field) -> syn::Result<FieldOptions> {
+    let mut options: FieldOptions = FieldOptions {
+        rename: None,
+        default: FieldDefault::None,
+    };
+
+    for attr in field.attrs.iter().filter(|a| a.path().is_ident("toml")) {
+        attr.parse_nested_meta(|meta| {
+            // #[toml(rename = "name")]
+            if meta.path.is_ident("rename") {
+                let value: LitStr = meta.value()?.parse()?;
+                options.rename = Some(value.value());
+
+                return Ok(());
+            }
+
+            // #[toml(default)] or #[toml(default = "path::to::function")]
+            if meta.path.is_ident("default") {
+                options.default = if meta.input.peek(syn::Token![=]) {
+                    let value: LitStr = meta.value()?.parse()?;
+                    FieldDefault::Function(value.parse()?)
+                } else {
+                    FieldDefault::Default
+                };
+
+                return Ok(());
+            }
+
+            return Err(meta.error("unknown `toml` attribute, expected `rename` or `default`"));
+        })?;
+    }
+
+    return Ok(options);
+}
+
+fn is_option(ty: &Type) -> bool {
+    return match ty {
+        Type::Path(p) => {
+            p.qself.is_none()
+                && p.path
+                    .segments
+                    .last()
+                    .map_or(false, |s| s.ident == "Option")
+        }
+        _ => false,
+    };
+}
+
+fn expand_from_toml(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let struct_name: String = name.to_string();
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    name,
+                    "`FromToml` can only be derived for structs with named fields",
+                ));
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                name,
+                "`FromToml` can only be derived for structs",
+            ));
+        }
+    };
+
+    let mut initializers: Vec<TokenStream2> = vec![];
+
+    for field in fields {
+        let ident = field.ident.as_ref().unwrap();
+        let options: FieldOptions = parse_field_options(field)?;
+
+        // The key in the document and the field named in error messages
+        let key: String = options.rename.unwrap_or_else(|| ident.unraw().to_string());
+        let field_name: String = format!("{}.{}", struct_name, ident.unraw());
+
+        let value: TokenStream2 = match options.default {
+            FieldDefault::Function(function) => quote! {
+                get_field_or(table, path, #key, #field_name, #function)?
+            },
+            FieldDefault::Default => quote! {
+                get_field_or(table, path, #key, #field_name, ::core::default::Default::default)?
+            },
+            FieldDefault::None if is_option(&field.ty) => quote! {
+                get_field_or(table, path, #key, #field_name, || ::core::option::Option::None)?
+            },
+            FieldDefault::None => quote! {
+                get_field(table, path, #key, #field_name)?
+            },
+        };
+
+        initializers.push(quote! { #ident: #value });
+    }
+
+    return Ok(quote! {
+        impl #impl_generics ::toml_parser::parser::from_toml::FromToml for #name #ty_generics #where_clause {
+            fn from_toml(
+                tree: &::toml_parser::parser::tree::TOMLTree,
+                path: &[::toml_parser::parser::path::PathSegment],
+            ) -> ::core::result::Result<Self, ::toml_parser::parser::from_toml::FromTomlError> {
+                use ::toml_parser::parser::from_toml::{get_field, get_field_or, get_table};
+
+                let table = get_table(tree, path, #struct_name)?;
+
+                return ::core::result::Result::Ok(#name {
+                    #(#initializers),*
+                });
+            }
+        }
+    });
+}
+
+#[proc_macro_derive(FromToml, attributes(toml))]
+pub fn derive_from_toml(input: TokenStream) -> TokenStream {
+    let input: DeriveInput = parse_macro_input!(input as DeriveInput);
+
+    return match expand_from_toml(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    };
+}
@@ -0,0 +1,11 @@
+use std::io;
+use std::process;
+use toml_parser::lsp::run_server;
+
+// toml-lsp, a language server over stdio
+fn main() {
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+
+    process::exit(run_server(&mut stdin.lock(), &mut stdout));
+}
@@ -0,0 +1,289 @@
+use crate::c::converter::{convert_c_str_to_str, convert_str_to_c_string};
+use crate::parser::ast::AST;
+use crate::parser::converter::{
+    convert_ast_value_to_string, get_common_type, get_value_type, try_convert_ast_value_to_string,
+};
+use crate::parser::token::Token;
+use crate::parser::writer::convert_string_map_to_toml;
+use crate::TOMLStringMap;
+use std::fs;
+use std::os::raw::c_char;
+
+pub struct TOMLArray {
+    values: Vec<AST>,
+    // The most specific type of the elements so far
+    element_type: Option<String>,
+}
+
+fn get_ast_type(ast: &AST) -> Option<String> {
+    let value: String = try_convert_ast_value_to_string(ast, true).ok()?;
+
+    return get_value_type(value.as_str()).map(String::from);
+}
+
+unsafe fn toml_array_push(array: *mut TOMLArray, value: AST) -> bool {
+    let array: &mut TOMLArray = &mut *array;
+
+    // Arrays must stay homogeneous, only the new element is checked
+    let found: String = match get_ast_type(&value) {
+        Some(t) => t,
+        None => return false,
+    };
+
+    let element_type: String = match &array.element_type {
+        Some(expected) => match get_common_type(expected.as_str(), found.as_str()) {
+            Some(t) => String::from(t),
+            None => return false,
+        },
+        None => found,
+    };
+
+    array.element_type = Some(element_type);
+    array.values.push(value);
+
+    return true;
+}
+
+unsafe fn toml_document_set_value(
+    map: *mut TOMLStringMap,
+    tag: *const c_char,
+    name: *const c_char,
+    value: AST,
+) -> bool {
+    // Get tag
+    let tag: &str = convert_c_str_to_str(tag);
+
+    // Get name
+    let name: &str = convert_c_str_to_str(name);
+
+    // Names must be readable by the lexer
+    if (tag != "." && !Token::is_valid_id(tag)) || !Token::is_valid_id(name) {
+        return false;
+    }
+
+    // Create the tag if needed
+    (*map).add_tag(tag, false);
+
+    // Set the value
+    (*map).add_value(
+        tag,
+        name,
+        convert_ast_value_to_string(&value, true).as_str(),
+    );
+
+    return true;
+}
+
+// Strings can't contain quotes, the lexer has no escape sequences.
+unsafe fn convert_c_str_to_ast_string(value: *const c_char) -> Option<AST> {
+    let value: &str = convert_c_str_to_str(value);
+
+    return if value.contains('"') {
+        None
+    } else {
+        Some(AST::ASTString(value.to_owned()))
+    };
+}
+
+// Creates an empty document. Free with `toml_parser_free`.
+#[no_mangle]
+pub unsafe extern "C" fn toml_document_new() -> *mut TOMLStringMap {
+    let mut map: TOMLStringMap = TOMLStringMap::new();
+    map.add_tag(".", false);
+
+    return Box::into_raw(Box::new(map));
+}
+
+// Creates `tag` if it doesn't exist. Returns false if the name is not a valid tag name.
+#[no_mangle]
+pub unsafe extern "C" fn toml_document_add_table(
+    map: *mut TOMLStringMap,
+    tag: *const c_char,
+) -> bool {
+    // Get tag
+    let tag: &str = convert_c_str_to_str(tag);
+
+    if !Token::is_valid_id(tag) {
+        return false;
+    }
+
+    (*map).add_tag(tag, false);
+
+    return true;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn toml_document_remove_table(
+    map: *mut TOMLStringMap,
+    tag: *const c_char,
+) -> bool {
+    return (*map).remove_tag(convert_c_str_to_str(tag));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn toml_document_remove_key(
+    map: *mut TOMLStringMap,
+    tag: *const c_char,
+    name: *const c_char,
+) -> bool {
+    return (*map).remove_value(convert_c_str_to_str(tag), convert_c_str_to_str(name));
+}
+
+// The setters create `tag` if needed and replace any existing value.
+// They return false if the tag or key name can't be written.
+#[no_mangle]
+pub unsafe extern "C" fn toml_document_set_int(
+    map: *mut TOMLStringMap,
+    tag: *const c_char,
+    name: *const c_char,
+    value: i32,
+) -> bool {
+    return toml_document_set_value(map, tag, name, AST::ASTInt(value));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn toml_document_set_bool(
+    map: *mut TOMLStringMap,
+    tag: *const c_char,
+    name: *const c_char,
+    value: bool,
+) -> bool {
+    return toml_document_set_value(map, tag, name, AST::ASTBool(value));
+}
+
+// Also returns false if the string contains a `"`.
+#[no_mangle]
+pub unsafe extern "C" fn toml_document_set_string(
+    map: *mut TOMLStringMap,
+    tag: *const c_char,
+    name: *const c_char,
+    value: *const c_char,
+) -> bool {
+    return if let Some(value) = convert_c_str_to_ast_string(value) {
+        toml_document_set_value(map, tag, name, value)
+    } else {
+        false
+    };
+}
+
+// Takes ownership of `array`, it must not be used or freed afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn toml_document_set_array(
+    map: *mut TOMLStringMap,
+    tag: *const c_char,
+    name: *const c_char,
+    array: *mut TOMLArray,
+) -> bool {
+    let array: Box<TOMLArray> = Box::from_raw(array);
+
+    return toml_document_set_value(map, tag, name, AST::ASTArray(array.values));
+}
+
+// Creates an empty array. Free with `toml_parser_free_array` unless it is
+// handed over to `toml_document_set_array` or `toml_array_push_array`.
+#[no_mangle]
+pub unsafe extern "C" fn toml_array_new() -> *mut TOMLArray {
+    return Box::into_raw(Box::new(TOMLArray {
+        values: vec![],
+        element_type: None,
+    }));
+}
+
+// The push functions return false if the value's type differs from the other elements.
+#[no_mangle]
+pub unsafe extern "C" fn toml_array_push_int(array: *mut TOMLArray, value: i32) -> bool {
+    return toml_array_push(array, AST::ASTInt(value));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn toml_array_push_bool(array: *mut TOMLArray, value: bool) -> bool {
+    return toml_array_push(array, AST::ASTBool(value));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn toml_array_push_string(
+    array: *mut TOMLArray,
+    value: *const c_char,
+) -> bool {
+    return if let Some(value) = convert_c_str_to_ast_string(value) {
+        toml_array_push(array, value)
+    } else {
+        false
+    };
+}
+
+// Takes ownership of `value`, even if it is rejected.
+#[no_mangle]
+pub unsafe extern "C" fn toml_array_push_array(
+    array: *mut TOMLArray,
+    value: *mut TOMLArray,
+) -> bool {
+    let value: Box<TOMLArray> = Box::from_raw(value);
+
+    return toml_array_push(array, AST::ASTArray(value.values));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn toml_parser_free_array(array: *mut TOMLArray) {
+    drop(Box::from_raw(array));
+}
+
+// Serialises the document. Free the result with `toml_parser_free_string`.
+#[no_mangle]
+pub unsafe extern "C" fn toml_document_to_string(map: *const TOMLStringMap) -> *mut c_char {
+    return convert_str_to_c_string(convert_string_map_to_toml(&*map).as_str()).into_raw();
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn toml_document_write_file(
+    map: *const TOMLStringMap,
+    filepath: *const c_char,
+) -> bool {
+    // Get filepath
+    let filepath: &str = convert_c_str_to_str(filepath);
+
+    // Write the file
+    return fs::write(filepath, convert_string_map_to_toml(&*map)).is_ok();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    unsafe fn int_array(values: &[i32]) -> *mut TOMLArray {
+        let array: *mut TOMLArray = toml_array_new();
+        for value in values {
+            assert!(toml_array_push_int(array, *value));
+        }
+
+        return array;
+    }
+
+    #[test]
+    fn push_rejects_other_types() {
+        unsafe {
+            let array: *mut TOMLArray = int_array(&[1, 2]);
+            assert!(!toml_array_push_bool(array, true));
+            assert_eq!((*array).values.len(), 2);
+            toml_parser_free_array(array);
+        }
+    }
+
+    #[test]
+    fn push_nested_arrays() {
+        unsafe {
+            let array: *mut TOMLArray = toml_array_new();
+            assert!(toml_array_push_array(array, int_array(&[])));
+            assert!(toml_array_push_array(array, int_array(&[1])));
+            assert!(toml_array_push_array(array, int_array(&[])));
+
+            let bools: *mut TOMLArray = toml_array_new();
+            assert!(toml_array_push_bool(bools, true));
+            assert!(!toml_array_push_array(array, bools));
+
+            assert_eq!((*array).values.len(), 3);
+            assert_eq!((*array).element_type.as_deref(), Some("array_int"));
+            toml_parser_free_array(array);
+        }
+    }
+}
@@ -1,4 +1,5 @@
-use crate::parser::converter::TOMLStringTag;
+use crate::parser::ast::AST;
+use crate::parser::converter::{convert_string_to_ast, TOMLStringTag};
 use crate::TOMLStringMap;
 use std::ffi::{CStr, CString};
 use std::mem::ManuallyDrop;
@@ -20,7 +21,6 @@ unsafe fn convert_string_to_int_array(array: String) -> *mut i32 {
     let mut current: String = if let Some(pos) = array.find(';') {
         array[pos + 1..array.len()].to_owned()
     } else {
-        println!("[WARNING] Array is empty.");
         return values.into_raw_parts().0;
     };
 
@@ -58,7 +58,7 @@ unsafe fn convert_string_to_int_array(array: String) -> *mut i32 {
     return values.into_raw_parts().0;
 }
 
-unsafe fn convert_c_str_to_str(c_string: *const c_char) -> &'static str {
+pub(super) unsafe fn convert_c_str_to_str(c_string: *const c_char) -> &'static str {
     let raw_str: &CStr = CStr::from_ptr(c_string);
     let str: &str = if let Ok(str) = raw_str.to_str() {
         str
@@ -69,6 +69,10 @@ unsafe fn convert_c_str_to_str(c_string: *const c_char) -> &'static str {
     return str;
 }
 
+pub(super) fn convert_str_to_c_string(str: &str) -> CString {
+    return CString::new(str).expect("[ERROR] String contains a null character.");
+}
+
 unsafe fn toml_parser_get_value(
     map: *const TOMLStringMap,
     tag: *const c_char,
@@ -94,6 +98,10 @@ unsafe fn toml_parser_get_value(
 
 #[no_mangle]
 pub unsafe extern "C" fn toml_parser_free_string(str: *mut c_char) {
+    if str.is_null() {
+        return;
+    }
+
     CString::from_raw(str);
 }
 
@@ -104,6 +112,11 @@ pub unsafe extern "C" fn toml_parser_free_int_array(array: *mut i32) {
 
 #[no_mangle]
 pub unsafe extern "C" fn toml_parser_free_int_array_array(array: *mut *mut i32, size: i32) {
+    // Empty arrays are returned as null
+    if array.is_null() {
+        return;
+    }
+
     let vec: Vec<*mut i32> = Vec::from_raw_parts(array, size as usize, size as usize);
 
     for arr in vec {
@@ -111,6 +124,20 @@ pub unsafe extern "C" fn toml_parser_free_int_array_array(array: *mut *mut i32,
     }
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn toml_parser_free_string_array(array: *mut *mut c_char, size: i32) {
+    // Missing tags give null
+    if array.is_null() {
+        return;
+    }
+
+    let vec: Vec<*mut c_char> = Vec::from_raw_parts(array, size as usize, size as usize);
+
+    for str in vec {
+        toml_parser_free_string(str);
+    }
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn toml_parser_get_int(
     map: *const TOMLStringMap,
@@ -167,8 +194,12 @@ pub unsafe extern "C" fn toml_parser_get_string (
         panic!("[ERROR] Type of value is not an int.");
     }
 
-    // Prepare value in Rust
-    let mut rust_value: String = val[type_sign + 1..val.len()].to_owned();
+    // Prepare value in Rust, the converter removes the escapes
+    let mut rust_value: String = if let Some(AST::ASTString(v)) = convert_string_to_ast(&val) {
+        format!("'{}'", v)
+    } else {
+        panic!("[ERROR] Failed to read string from value `{}`.", val);
+    };
 
     // Push null char
     rust_value.push('\0');
@@ -268,8 +299,6 @@ pub unsafe extern "C" fn toml_parser_get_int_array_array(
     let mut current: String = if let Some(pos) = val.find('|') {
         val[pos + 1..val.len()].to_owned()
     } else {
-        println!("[WARNING] Array is empty.");
-
         *size = 0;
         return null_mut();
     };
@@ -295,4 +324,91 @@ pub unsafe extern "C" fn toml_parser_get_int_array_array(
 
     // Return the array
     return array.into_raw_parts().0;
-}
\ No newline at end of file
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn toml_parser_get_int_or(
+    map: *const TOMLStringMap,
+    tag: *const c_char,
+    name: *const c_char,
+    default: i32,
+) -> i32 {
+    // Get tag
+    let tag: &str = convert_c_str_to_str(tag);
+
+    // Get name
+    let name: &str = convert_c_str_to_str(name);
+
+    // Return the int or the default
+    return (*map).get_int_or(tag, name, default);
+}
+
+// Returns `default` if the value is missing, quoted like `toml_parser_get_string`.
+// Free the result with `toml_parser_free_string`.
+#[no_mangle]
+pub unsafe extern "C" fn toml_parser_get_string_or(
+    map: *const TOMLStringMap,
+    tag: *const c_char,
+    name: *const c_char,
+    default: *const c_char,
+) -> *mut c_char {
+    // Get tag
+    let tag: &str = convert_c_str_to_str(tag);
+
+    // Get name
+    let name: &str = convert_c_str_to_str(name);
+
+    // Get default
+    let default: &str = convert_c_str_to_str(default);
+
+    // Value
+    let value: String = format!("'{}'", (*map).get_string_or(tag, name, default));
+
+    // Return the value
+    return convert_str_to_c_string(value.as_str()).into_raw();
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn toml_parser_get_bool_or(
+    map: *const TOMLStringMap,
+    tag: *const c_char,
+    name: *const c_char,
+    default: bool,
+) -> bool {
+    // Get tag
+    let tag: &str = convert_c_str_to_str(tag);
+
+    // Get name
+    let name: &str = convert_c_str_to_str(name);
+
+    // Return the bool or the default
+    return (*map).get_bool_or(tag, name, default);
+}
+
+// Returns null if the value is missing, otherwise the same array as `toml_parser_get_int_array`.
+#[no_mangle]
+pub unsafe extern "C" fn toml_parser_get_int_array_or_null(
+    map: *const TOMLStringMap,
+    tag: *const c_char,
+    name: *const c_char,
+) -> *mut i32 {
+    // Get tag
+    let tag: &str = convert_c_str_to_str(tag);
+
+    // Get name
+    let name: &str = convert_c_str_to_str(name);
+
+    // Value
+    let array: Vec<i32> = if let Some(array) = (*map).get_int_array(tag, name) {
+        array
+    } else {
+        return null_mut();
+    };
+
+    // Put size first
+    let mut values: Vec<i32> = vec![array.len() as i32 + 1];
+    values.extend(array);
+
+    // Return array
+    return Box::into_raw(values.into_boxed_slice()) as *mut i32;
+}
@@ -1 +1,4 @@
+mod builder;
 mod converter;
+mod query;
+mod schema;
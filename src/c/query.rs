@@ -0,0 +1,186 @@
+use crate::c::converter::{convert_c_str_to_str, convert_str_to_c_string};
+use crate::parser::converter::{convert_ast_value_to_string, get_value_type, TOMLStringTag};
+use crate::TOMLStringMap;
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::ptr::{null, null_mut};
+
+pub struct TOMLNameIterator {
+    names: Vec<CString>,
+    index: usize,
+}
+
+impl TOMLNameIterator {
+    fn new<'a>(names: impl Iterator<Item = &'a String>) -> Self {
+        let mut names: Vec<&String> = names.collect();
+        names.sort();
+
+        return Self {
+            names: names
+                .into_iter()
+                .map(|name| convert_str_to_c_string(name))
+                .collect(),
+            index: 0,
+        };
+    }
+}
+
+unsafe fn toml_get_tag<'a>(
+    map: *const TOMLStringMap,
+    tag: *const c_char,
+) -> Option<&'a TOMLStringTag> {
+    // Get tag
+    let tag: &str = convert_c_str_to_str(tag);
+
+    // Tag
+    return (*map).tags.get(tag);
+}
+
+// Returns the number of keys in `tag`, or -1 if the tag doesn't exist.
+// Global keys live in the tag named ".".
+#[no_mangle]
+pub unsafe extern "C" fn toml_table_len(map: *const TOMLStringMap, tag: *const c_char) -> i32 {
+    return if let Some(tag) = toml_get_tag(map, tag) {
+        tag.values.len() as i32
+    } else {
+        -1
+    };
+}
+
+// Returns the sorted key names of `tag` and writes their count to `size`.
+// Free the result with `toml_parser_free_string_array`. Returns null if the tag doesn't exist.
+#[no_mangle]
+pub unsafe extern "C" fn toml_table_keys(
+    map: *const TOMLStringMap,
+    tag: *const c_char,
+    size: *mut i32,
+) -> *mut *mut c_char {
+    // Tag
+    let tag: &TOMLStringTag = if let Some(tag) = toml_get_tag(map, tag) {
+        tag
+    } else {
+        *size = 0;
+        return null_mut();
+    };
+
+    // Collect names
+    let iter: TOMLNameIterator = TOMLNameIterator::new(tag.values.keys());
+
+    let keys: Vec<*mut c_char> = iter.names.into_iter().map(|name| name.into_raw()).collect();
+
+    // Set the size
+    *size = keys.len() as i32;
+
+    // Return the keys
+    return Box::into_raw(keys.into_boxed_slice()) as *mut *mut c_char;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn toml_has_key(
+    map: *const TOMLStringMap,
+    tag: *const c_char,
+    name: *const c_char,
+) -> bool {
+    // Get name
+    let name: &str = convert_c_str_to_str(name);
+
+    return if let Some(tag) = toml_get_tag(map, tag) {
+        tag.values.contains_key(name)
+    } else {
+        false
+    };
+}
+
+// Returns the type tag of a value (e.g. `int`, `string`, `array_int`), as used by the getters.
+// Free the result with `toml_parser_free_string`. Returns null if the value doesn't exist
+// or the map was parsed without typechecking.
+#[no_mangle]
+pub unsafe extern "C" fn toml_value_type(
+    map: *const TOMLStringMap,
+    tag: *const c_char,
+    name: *const c_char,
+) -> *mut c_char {
+    // Get name
+    let name: &str = convert_c_str_to_str(name);
+
+    // Value
+    let val: &String = if let Some(val) = toml_get_tag(map, tag).and_then(|t| t.values.get(name)) {
+        val
+    } else {
+        return null_mut();
+    };
+
+    // Type
+    return if let Some(type_str) = get_value_type(val) {
+        convert_str_to_c_string(type_str).into_raw()
+    } else {
+        null_mut()
+    };
+}
+
+// Iterates the sorted tag names of the map. Free with `toml_iter_free`.
+#[no_mangle]
+pub unsafe extern "C" fn toml_tag_iter_new(map: *const TOMLStringMap) -> *mut TOMLNameIterator {
+    return Box::into_raw(Box::new(TOMLNameIterator::new((*map).tags.keys())));
+}
+
+// Iterates the sorted key names of `tag`. Free with `toml_iter_free`.
+// Returns null if the tag doesn't exist.
+#[no_mangle]
+pub unsafe extern "C" fn toml_value_iter_new(
+    map: *const TOMLStringMap,
+    tag: *const c_char,
+) -> *mut TOMLNameIterator {
+    return if let Some(tag) = toml_get_tag(map, tag) {
+        Box::into_raw(Box::new(TOMLNameIterator::new(tag.values.keys())))
+    } else {
+        null_mut()
+    };
+}
+
+// Returns the next name, or null when exhausted. The name is owned by the
+// iterator and stays valid until `toml_iter_free` is called.
+#[no_mangle]
+pub unsafe extern "C" fn toml_iter_next(iter: *mut TOMLNameIterator) -> *const c_char {
+    let iter: &mut TOMLNameIterator = &mut *iter;
+
+    if iter.index >= iter.names.len() {
+        return null();
+    }
+
+    iter.index += 1;
+
+    return iter.names[iter.index - 1].as_ptr();
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn toml_iter_free(iter: *mut TOMLNameIterator) {
+    drop(Box::from_raw(iter));
+}
+
+// Looks up a value by path, e.g. `servers[0].ports[1]`, and returns it with its type tag
+// (e.g. `int#8080`), the same way values are stored in the map. On failure returns null
+// and, if `error` is not null, stores a message there. Free both with `toml_parser_free_string`.
+#[no_mangle]
+pub unsafe extern "C" fn toml_get_by_path(
+    map: *const TOMLStringMap,
+    path: *const c_char,
+    error: *mut *mut c_char,
+) -> *mut c_char {
+    // Get path
+    let path: &str = convert_c_str_to_str(path);
+
+    // Value
+    return match (*map).get_path(path) {
+        Ok(ast) => {
+            let value: String = convert_ast_value_to_string(&ast, true);
+            convert_str_to_c_string(value.as_str()).into_raw()
+        }
+        Err(e) => {
+            if !error.is_null() {
+                *error = convert_str_to_c_string(e.to_string().as_str()).into_raw();
+            }
+            null_mut()
+        }
+    };
+}
@@ -0,0 +1,38 @@
+use crate::c::converter::{convert_c_str_to_str, convert_str_to_c_string};
+use crate::schema::Schema;
+use crate::TOMLStringMap;
+use std::os::raw::c_char;
+
+// Validates the map against the schema file at `schema_path` and returns every violation
+// as a message, writing their count to `size`. A schema that fails to load is reported as
+// a single message. Free the result with `toml_parser_free_string_array`.
+#[no_mangle]
+pub unsafe extern "C" fn toml_schema_validate(
+    map: *const TOMLStringMap,
+    schema_path: *const c_char,
+    size: *mut i32,
+) -> *mut *mut c_char {
+    // Get schema path
+    let schema_path: &str = convert_c_str_to_str(schema_path);
+
+    // Validate
+    let messages: Vec<String> = match Schema::from_file(schema_path) {
+        Ok(schema) => schema
+            .validate_map(&*map)
+            .iter()
+            .map(|e| e.to_string())
+            .collect(),
+        Err(e) => vec![format!("Invalid schema: {}", e)],
+    };
+
+    let messages: Vec<*mut c_char> = messages
+        .iter()
+        .map(|m| convert_str_to_c_string(m.as_str()).into_raw())
+        .collect();
+
+    // Set the size
+    *size = messages.len() as i32;
+
+    // Return the messages
+    return Box::into_raw(messages.into_boxed_slice()) as *mut *mut c_char;
+}
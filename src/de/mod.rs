@@ -0,0 +1,468 @@
+use crate::parser::ast::AST;
+use crate::parser::error::ParseError;
+use crate::parser::path::{format_path, PathSegment};
+use crate::parser::token::Position;
+use crate::parser::tree::{parse_document, TOMLTable, TOMLTree};
+use serde::de::{
+    self, DeserializeOwned, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, SeqAccess,
+    VariantAccess, Visitor,
+};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::vec;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Error {
+    pub message: String,
+    pub path: Option<String>,
+    pub position: Option<Position>,
+}
+
+impl Error {
+    pub(crate) fn new(message: &str) -> Self {
+        return Error {
+            message: String::from(message),
+            path: None,
+            position: None,
+        };
+    }
+
+    // The innermost key and position are the most precise, keep them
+    pub(crate) fn with_context(mut self, path: &[PathSegment], position: Option<Position>) -> Self {
+        if self.path.is_none() && !path.is_empty() {
+            self.path = Some(format_path(path));
+        }
+
+        if self.position.is_none() {
+            self.position = position;
+        }
+
+        return self;
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+
+        if let Some(path) = &self.path {
+            write!(f, " for key `{}`", path)?;
+        }
+
+        if let Some(position) = &self.position {
+            write!(f, " at line {}, column {}", position.line, position.column)?;
+        }
+
+        return Ok(());
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        return Error::new(msg.to_string().as_str());
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(e: ParseError) -> Self {
+        return Error {
+            message: e.message,
+            path: None,
+            position: Some(e.position),
+        };
+    }
+}
+
+pub fn from_str<T: DeserializeOwned>(s: &str) -> Result<T, Error> {
+    // Parse
+    let table: TOMLTable = parse_document(s)?;
+
+    // Deserialize
+    return T::deserialize(Deserializer::new(TOMLTree::Table(table), vec![]));
+}
+
+pub fn from_path<T: DeserializeOwned, P: AsRef<Path>>(path: P) -> Result<T, Error> {
+    let content: String = match fs::read_to_string(path.as_ref()) {
+        Ok(content) => content,
+        Err(e) => {
+            return Err(Error::new(
+                format!("Failed to read file `{}`: {}", path.as_ref().display(), e).as_str(),
+            ));
+        }
+    };
+
+    return from_str(content.as_str());
+}
+
+pub struct Deserializer {
+    tree: TOMLTree,
+    path: Vec<PathSegment>,
+}
+
+impl Deserializer {
+    fn new(tree: TOMLTree, path: Vec<PathSegment>) -> Self {
+        return Deserializer { tree, path };
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Deserializer {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let position: Option<Position> = self.tree.get_position();
+
+        let result: Result<V::Value, Error> = match self.tree {
+            TOMLTree::Value(AST::ASTBool(v), _) => visitor.visit_bool(v),
+            TOMLTree::Value(AST::ASTInt(v), _) => visitor.visit_i64(v as i64),
+            TOMLTree::Value(AST::ASTString(v), _) => visitor.visit_string(v),
+            TOMLTree::Value(AST::ASTDateTime(v), _) => visitor.visit_string(v),
+            TOMLTree::Value(AST::ASTArray(v), position) => {
+                let items: Vec<TOMLTree> = v
+                    .into_iter()
+                    .map(|ast| TOMLTree::Value(ast, position))
+                    .collect();
+
+                visitor.visit_seq(SeqDeserializer::new(items, self.path.clone()))
+            }
+            TOMLTree::Value(ast, _) => Err(Error::new(
+                format!("Unsupported value `{:?}`", ast).as_str(),
+            )),
+            TOMLTree::Table(table) => {
+                visitor.visit_map(MapDeserializer::new(table, self.path.clone()))
+            }
+            TOMLTree::TableArray(tables) => {
+                let items: Vec<TOMLTree> = tables.into_iter().map(TOMLTree::Table).collect();
+
+                visitor.visit_seq(SeqDeserializer::new(items, self.path.clone()))
+            }
+        };
+
+        return result.map_err(|e| e.with_context(&self.path, position));
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        // Missing keys are handled by serde, anything present is `Some`
+        return visitor.visit_some(self);
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        return visitor.visit_newtype_struct(self);
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let position: Option<Position> = self.tree.get_position();
+
+        let result: Result<V::Value, Error> = match self.tree {
+            // Unit variants are written as strings
+            TOMLTree::Value(AST::ASTString(v), _) => visitor.visit_enum(v.into_deserializer()),
+            // Other variants are tables with a single key
+            TOMLTree::Table(mut table) if table.entries.len() == 1 => {
+                let (variant, tree) = table.entries.remove(0);
+
+                visitor.visit_enum(EnumDeserializer {
+                    variant,
+                    tree,
+                    path: self.path.clone(),
+                })
+            }
+            _ => Err(Error::new(
+                "Expected a string or a table with a single key for an enum",
+            )),
+        };
+
+        return result.map_err(|e| e.with_context(&self.path, position));
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        return visitor.visit_unit();
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        return self.deserialize_any(visitor);
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit_struct seq tuple tuple_struct map struct identifier
+    }
+}
+
+struct SeqDeserializer {
+    items: vec::IntoIter<TOMLTree>,
+    index: usize,
+    path: Vec<PathSegment>,
+}
+
+impl SeqDeserializer {
+    fn new(items: Vec<TOMLTree>, path: Vec<PathSegment>) -> Self {
+        return SeqDeserializer {
+            items: items.into_iter(),
+            index: 0,
+            path,
+        };
+    }
+}
+
+impl<'de> SeqAccess<'de> for SeqDeserializer {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        let tree: TOMLTree = if let Some(tree) = self.items.next() {
+            tree
+        } else {
+            return Ok(None);
+        };
+
+        let mut path: Vec<PathSegment> = self.path.clone();
+        path.push(PathSegment::Index(self.index));
+        self.index += 1;
+
+        let position: Option<Position> = tree.get_position();
+
+        return seed
+            .deserialize(Deserializer::new(tree, path.clone()))
+            .map(Some)
+            .map_err(|e| e.with_context(&path, position));
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        return Some(self.items.len());
+    }
+}
+
+struct MapDeserializer {
+    entries: vec::IntoIter<(String, TOMLTree)>,
+    value: Option<(String, TOMLTree)>,
+    path: Vec<PathSegment>,
+}
+
+impl MapDeserializer {
+    fn new(table: TOMLTable, path: Vec<PathSegment>) -> Self {
+        return MapDeserializer {
+            entries: table.entries.into_iter(),
+            value: None,
+            path,
+        };
+    }
+}
+
+impl<'de> MapAccess<'de> for MapDeserializer {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        let (key, tree) = if let Some(entry) = self.entries.next() {
+            entry
+        } else {
+            return Ok(None);
+        };
+
+        self.value = Some((key.clone(), tree));
+
+        return seed.deserialize(key.into_deserializer()).map(Some);
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let (key, tree) = self
+            .value
+            .take()
+            .expect("[ERROR] Value requested before its key.");
+
+        let mut path: Vec<PathSegment> = self.path.clone();
+        path.push(PathSegment::Key(key));
+
+        let position: Option<Position> = tree.get_position();
+
+        return seed
+            .deserialize(Deserializer::new(tree, path.clone()))
+            .map_err(|e| e.with_context(&path, position));
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        return Some(self.entries.len());
+    }
+}
+
+struct EnumDeserializer {
+    variant: String,
+    tree: TOMLTree,
+    path: Vec<PathSegment>,
+}
+
+impl<'de> EnumAccess<'de> for EnumDeserializer {
+    type Error = Error;
+    type Variant = Deserializer;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Deserializer), Error> {
+        let mut path: Vec<PathSegment> = self.path;
+        path.push(PathSegment::Key(self.variant.clone()));
+
+        let key: de::value::StringDeserializer<Error> = self.variant.into_deserializer();
+        let variant: V::Value = seed.deserialize(key)?;
+
+        return Ok((variant, Deserializer::new(self.tree, path)));
+    }
+}
+
+impl<'de> VariantAccess<'de> for Deserializer {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        return Ok(());
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        return seed.deserialize(self);
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        return de::Deserializer::deserialize_seq(self, visitor);
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        return de::Deserializer::deserialize_map(self, visitor);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Server {
+        host: String,
+        port: i32,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Mode {
+        Fast,
+        Safe,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Config {
+        name: String,
+        debug: bool,
+        tags: Vec<String>,
+        matrix: Vec<Vec<i32>>,
+        mode: Mode,
+        timeout: Option<i32>,
+        server: Server,
+        replicas: Vec<Server>,
+    }
+
+    fn sample_config() -> Config {
+        return Config {
+            name: String::from("app main"),
+            debug: true,
+            tags: vec![String::from("a"), String::from("b")],
+            matrix: vec![vec![1, 2], vec![], vec![3]],
+            mode: Mode::Safe,
+            timeout: None,
+            server: Server {
+                host: String::from("localhost"),
+                port: 8080,
+            },
+            replicas: vec![
+                Server {
+                    host: String::from("a"),
+                    port: 1,
+                },
+                Server {
+                    host: String::from("b"),
+                    port: 2,
+                },
+            ],
+        };
+    }
+
+    #[test]
+    fn round_trip() {
+        let config: Config = sample_config();
+
+        for text in [crate::to_string(&config), crate::to_string_pretty(&config)] {
+            let text: String = text.unwrap();
+            assert_eq!(
+                from_str::<Config>(text.as_str()),
+                Ok(sample_config()),
+                "{}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn round_trip_option() {
+        let mut config: Config = sample_config();
+        config.timeout = Some(30);
+
+        let text: String = crate::to_string(&config).unwrap();
+        assert_eq!(from_str::<Config>(text.as_str()).unwrap().timeout, Some(30));
+    }
+
+    #[test]
+    fn parse_error_position() {
+        let error: Error = from_str::<Server>("host = \"a\"\nport = = 1\n").unwrap_err();
+
+        assert_eq!(error.path, None);
+        assert_eq!(error.position.map(|p| (p.line, p.column)), Some((2, 8)));
+    }
+
+    #[test]
+    fn type_error_position() {
+        let text: &str = "[server]\nhost = \"a\"\nport = \"80\"\n";
+        let error: Error = from_str::<Config>(text).unwrap_err();
+
+        assert_eq!(error.path.as_deref(), Some("server.port"));
+        assert_eq!(error.position.map(|p| (p.line, p.column)), Some((3, 1)));
+    }
+
+    #[test]
+    fn array_error_path() {
+        let text: &str =
+            "[[replicas]]\nhost = \"a\"\nport = 1\n\n[[replicas]]\nhost = 2\nport = 2\n";
+
+        #[derive(Debug, Deserialize)]
+        struct Replicas {
+            #[allow(dead_code)]
+            replicas: Vec<Server>,
+        }
+
+        let error: Error = from_str::<Replicas>(text).unwrap_err();
+        assert_eq!(error.path.as_deref(), Some("replicas[1].host"));
+        assert_eq!(error.position.map(|p| p.line), Some(6));
+    }
+
+    #[test]
+    fn missing_field() {
+        let error: Error = from_str::<Server>("host = \"a\"\n").unwrap_err();
+
+        assert!(error.message.contains("port"), "{}", error);
+    }
+}
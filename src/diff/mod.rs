@@ -0,0 +1,281 @@
+use crate::json::convert::convert_subtree_to_json;
+use crate::json::{convert_json_to_string, JSONValue};
+use crate::parser::path::{format_path, PathSegment};
+use crate::parser::tree::{TOMLTable, TOMLTree};
+use crate::parser::writer::convert_ast_value_to_toml;
+
+// Semantic diff of two documents. Key order, formatting and positions are ignored, tables
+// are compared key by key and arrays of tables element by element. Arrays of values are
+// compared as a whole.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DiffKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffEntry {
+    pub path: Vec<PathSegment>,
+    pub kind: DiffKind,
+    pub old: Option<TOMLTree>,
+    pub new: Option<TOMLTree>,
+}
+
+fn is_same_value(old: &TOMLTree, new: &TOMLTree) -> bool {
+    return match (old, new) {
+        (TOMLTree::Value(old, _), TOMLTree::Value(new, _)) => old == new,
+        _ => false,
+    };
+}
+
+fn diff_tree(
+    old: &TOMLTree,
+    new: &TOMLTree,
+    path: &mut Vec<PathSegment>,
+    diff: &mut Vec<DiffEntry>,
+) {
+    match (old, new) {
+        (TOMLTree::Table(old), TOMLTree::Table(new)) => diff_table(old, new, path, diff),
+        (TOMLTree::TableArray(old), TOMLTree::TableArray(new)) => {
+            for (i, (old, new)) in old.iter().zip(new).enumerate() {
+                path.push(PathSegment::Index(i));
+                diff_table(old, new, path, diff);
+                path.pop();
+            }
+
+            // Removed from the end first, so indices stay valid when applied in order
+            for i in (new.len()..old.len()).rev() {
+                path.push(PathSegment::Index(i));
+
+                diff.push(DiffEntry {
+                    path: path.clone(),
+                    kind: DiffKind::Removed,
+                    old: Some(TOMLTree::Table(old[i].clone())),
+                    new: None,
+                });
+
+                path.pop();
+            }
+
+            for (i, table) in new.iter().enumerate().skip(old.len()) {
+                path.push(PathSegment::Index(i));
+
+                diff.push(DiffEntry {
+                    path: path.clone(),
+                    kind: DiffKind::Added,
+                    old: None,
+                    new: Some(TOMLTree::Table(table.clone())),
+                });
+
+                path.pop();
+            }
+        }
+        _ if is_same_value(old, new) => {}
+        _ => diff.push(DiffEntry {
+            path: path.clone(),
+            kind: DiffKind::Changed,
+            old: Some(old.clone()),
+            new: Some(new.clone()),
+        }),
+    }
+}
+
+fn diff_table(
+    old: &TOMLTable,
+    new: &TOMLTable,
+    path: &mut Vec<PathSegment>,
+    diff: &mut Vec<DiffEntry>,
+) {
+    for (key, tree) in &old.entries {
+        path.push(PathSegment::Key(key.clone()));
+
+        match new.get(key) {
+            Some(other) => diff_tree(tree, other, path, diff),
+            None => diff.push(DiffEntry {
+                path: path.clone(),
+                kind: DiffKind::Removed,
+                old: Some(tree.clone()),
+                new: None,
+            }),
+        }
+
+        path.pop();
+    }
+
+    for (key, tree) in &new.entries {
+        if old.get(key).is_none() {
+            path.push(PathSegment::Key(key.clone()));
+
+            diff.push(DiffEntry {
+                path: path.clone(),
+                kind: DiffKind::Added,
+                old: None,
+                new: Some(tree.clone()),
+            });
+
+            path.pop();
+        }
+    }
+}
+
+pub fn diff_documents(old: &TOMLTable, new: &TOMLTable) -> Vec<DiffEntry> {
+    let mut diff: Vec<DiffEntry> = vec![];
+
+    diff_table(old, new, &mut vec![], &mut diff);
+
+    return diff;
+}
+
+// Tables are written inline, `{ host = "localhost", port = 80 }`
+fn format_table(table: &TOMLTable) -> String {
+    let entries: Vec<String> = table
+        .entries
+        .iter()
+        .map(|(key, tree)| {
+            format!(
+                "{} = {}",
+                format_path(&[PathSegment::Key(key.clone())]),
+                format_tree(tree)
+            )
+        })
+        .collect();
+
+    if entries.is_empty() {
+        return String::from("{}");
+    }
+
+    return format!("{{ {} }}", entries.join(", "));
+}
+
+fn format_tree(tree: &TOMLTree) -> String {
+    return match tree {
+        TOMLTree::Value(value, _) => convert_ast_value_to_toml(value),
+        TOMLTree::Table(table) => format_table(table),
+        TOMLTree::TableArray(tables) => format!(
+            "[{}]",
+            tables
+                .iter()
+                .map(format_table)
+                .collect::<Vec<String>>()
+                .join(", ")
+        ),
+    };
+}
+
+// One line per entry, `+ path = new`, `- path = old` and `~ path = old -> new`
+pub fn convert_diff_to_text(diff: &[DiffEntry]) -> String {
+    let mut text: String = String::new();
+
+    for entry in diff {
+        let path: String = format_path(&entry.path);
+
+        text += match (&entry.old, &entry.new) {
+            (Some(old), Some(new)) => format!(
+                "~ {} = {} -> {}\n",
+                path,
+                format_tree(old),
+                format_tree(new)
+            ),
+            (Some(old), None) => format!("- {} = {}\n", path, format_tree(old)),
+            (None, Some(new)) => format!("+ {} = {}\n", path, format_tree(new)),
+            (None, None) => String::new(),
+        }
+        .as_str();
+    }
+
+    return text;
+}
+
+fn get_kind_name(kind: DiffKind) -> &'static str {
+    return match kind {
+        DiffKind::Added => "added",
+        DiffKind::Removed => "removed",
+        DiffKind::Changed => "changed",
+    };
+}
+
+// `[{"kind": "changed", "path": "server.port", "old": ..., "new": ...}]` with typed values
+pub fn convert_diff_to_json(diff: &[DiffEntry]) -> JSONValue {
+    return JSONValue::Array(
+        diff.iter()
+            .map(|entry| {
+                let mut object: JSONValue = JSONValue::Object(vec![
+                    (
+                        String::from("kind"),
+                        JSONValue::String(String::from(get_kind_name(entry.kind))),
+                    ),
+                    (
+                        String::from("path"),
+                        JSONValue::String(format_path(&entry.path)),
+                    ),
+                ]);
+
+                if let Some(old) = &entry.old {
+                    object.insert("old", convert_subtree_to_json(old, true));
+                }
+
+                if let Some(new) = &entry.new {
+                    object.insert("new", convert_subtree_to_json(new, true));
+                }
+
+                object
+            })
+            .collect(),
+    );
+}
+
+// JSON Pointer, `~` and `/` in keys are escaped as `~0` and `~1`
+pub fn format_pointer(path: &[PathSegment]) -> String {
+    let mut pointer: String = String::new();
+
+    for segment in path {
+        pointer.push('/');
+
+        match segment {
+            PathSegment::Key(key) => pointer += key.replace('~', "~0").replace('/', "~1").as_str(),
+            PathSegment::Index(index) => pointer += index.to_string().as_str(),
+        }
+    }
+
+    return pointer;
+}
+
+// RFC 6902 operations, values are typed so datetimes and integers survive
+pub fn convert_diff_to_patch(diff: &[DiffEntry]) -> JSONValue {
+    return JSONValue::Array(
+        diff.iter()
+            .map(|entry| {
+                let op: &str = match entry.kind {
+                    DiffKind::Added => "add",
+                    DiffKind::Removed => "remove",
+                    DiffKind::Changed => "replace",
+                };
+
+                let mut object: JSONValue = JSONValue::Object(vec![
+                    (String::from("op"), JSONValue::String(String::from(op))),
+                    (
+                        String::from("path"),
+                        JSONValue::String(format_pointer(&entry.path)),
+                    ),
+                ]);
+
+                if let Some(new) = &entry.new {
+                    object.insert("value", convert_subtree_to_json(new, true));
+                }
+
+                object
+            })
+            .collect(),
+    );
+}
+
+pub fn convert_diff_to_string(diff: &[DiffEntry], format: &str) -> Option<String> {
+    return match format {
+        "text" => Some(convert_diff_to_text(diff)),
+        "json" => Some(convert_json_to_string(&convert_diff_to_json(diff), true) + "\n"),
+        "patch" => Some(convert_json_to_string(&convert_diff_to_patch(diff), true) + "\n"),
+        _ => None,
+    };
+}
@@ -0,0 +1,468 @@
+use crate::parser::ast::AST;
+use crate::parser::converter::{get_value_type, try_convert_ast_value_to_string};
+use crate::parser::error::ParseError;
+use crate::parser::parser::{is_datetime, Parser};
+use crate::parser::token::Position;
+use crate::parser::tree::{TOMLTable, TOMLTree};
+use crate::parser::writer::convert_ast_value_to_toml;
+use std::fmt;
+
+// Documents flatten into `PREFIX_TABLE_KEY=value` variables. Keys have every character
+// that isn't a letter or digit replaced by `_`, elements of arrays of tables use their
+// index as a segment (`APP_WORKERS_0_ID`). Values are written as:
+//
+//   strings and datetimes    raw, without quotes
+//   integers and bools       `8080`, `true`
+//   arrays of values         TOML syntax, `[1, 2]` or `["a", "b"]`
+//
+// Overlaying reads variables back onto a document, converting each value to the type of
+// the value it replaces. The `overlay-env` command separates segments with `__` by default,
+// as in `APP__SERVER__PORT=8080`. New keys are read as TOML values when they parse as one, and as
+// strings otherwise.
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EnvCase {
+    Upper,
+    Lower,
+    Preserve,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnvOptions {
+    pub prefix: String,
+    pub separator: String,
+    pub case: EnvCase,
+}
+
+impl EnvOptions {
+    pub fn new() -> Self {
+        return EnvOptions {
+            prefix: String::new(),
+            separator: String::from("_"),
+            case: EnvCase::Upper,
+        };
+    }
+
+    fn get_segment(&self, key: &str) -> String {
+        let segment: String = key
+            .chars()
+            .map(|c| if c.is_alphanumeric() { c } else { '_' })
+            .collect();
+
+        return match self.case {
+            EnvCase::Upper => segment.to_uppercase(),
+            EnvCase::Lower => segment.to_lowercase(),
+            EnvCase::Preserve => segment,
+        };
+    }
+
+    fn get_name(&self, segments: &[String]) -> String {
+        let mut parts: Vec<String> = vec![];
+
+        if !self.prefix.is_empty() {
+            parts.push(self.get_segment(self.prefix.as_str()));
+        }
+
+        parts.extend(segments.iter().map(|s| self.get_segment(s)));
+
+        return parts.join(self.separator.as_str());
+    }
+
+    // Segments compare the way they were written, ignoring case unless it's preserved
+    fn matches_segment(&self, key: &str, segment: &str) -> bool {
+        return match self.case {
+            EnvCase::Preserve => self.get_segment(key) == segment,
+            _ => self.get_segment(key).to_lowercase() == segment.to_lowercase(),
+        };
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnvError {
+    pub name: String,
+    pub message: String,
+}
+
+impl fmt::Display for EnvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return write!(f, "`{}`: {}", self.name, self.message);
+    }
+}
+
+impl std::error::Error for EnvError {}
+
+fn convert_ast_to_env(value: &AST) -> String {
+    return match value {
+        AST::ASTString(v) | AST::ASTDateTime(v) => v.clone(),
+        _ => convert_ast_value_to_toml(value),
+    };
+}
+
+fn flatten_table(
+    table: &TOMLTable,
+    options: &EnvOptions,
+    path: &mut Vec<String>,
+    vars: &mut Vec<(String, String)>,
+) {
+    for (key, tree) in &table.entries {
+        path.push(key.clone());
+
+        match tree {
+            TOMLTree::Value(ast, _) => {
+                vars.push((options.get_name(path), convert_ast_to_env(ast)));
+            }
+            TOMLTree::Table(sub_table) => flatten_table(sub_table, options, path, vars),
+            TOMLTree::TableArray(tables) => {
+                for (i, sub_table) in tables.iter().enumerate() {
+                    path.push(i.to_string());
+                    flatten_table(sub_table, options, path, vars);
+                    path.pop();
+                }
+            }
+        }
+
+        path.pop();
+    }
+}
+
+// Variables in document order
+pub fn convert_tree_to_env(table: &TOMLTable, options: &EnvOptions) -> Vec<(String, String)> {
+    let mut vars: Vec<(String, String)> = vec![];
+
+    flatten_table(table, options, &mut vec![], &mut vars);
+
+    return vars;
+}
+
+fn needs_quotes(value: &str) -> bool {
+    return value.is_empty()
+        || value.contains(|c: char| {
+            c.is_whitespace() || matches!(c, '"' | '\'' | '\\' | '$' | '#' | '`')
+        });
+}
+
+// `KEY=value` lines, values with spaces or shell characters are double quoted
+pub fn convert_env_to_dotenv(vars: &[(String, String)]) -> String {
+    let mut string: String = String::new();
+
+    for (name, value) in vars {
+        string += name.as_str();
+        string += "=";
+
+        if needs_quotes(value) {
+            string.push('"');
+
+            for c in value.chars() {
+                match c {
+                    '"' => string += "\\\"",
+                    '\\' => string += "\\\\",
+                    '$' => string += "\\$",
+                    '`' => string += "\\`",
+                    '\n' => string += "\\n",
+                    c => string.push(c),
+                }
+            }
+
+            string.push('"');
+        } else {
+            string += value.as_str();
+        }
+
+        string += "\n";
+    }
+
+    return string;
+}
+
+fn unquote_dotenv_value(value: &str, position: Position) -> Result<String, ParseError> {
+    let quote: char = match value.chars().next() {
+        Some(c @ ('"' | '\'')) => c,
+        _ => {
+            // Unquoted values end at a comment
+            let value: &str = match value.find(" #") {
+                Some(i) => &value[0..i],
+                None => value,
+            };
+
+            return Ok(String::from(value.trim_end()));
+        }
+    };
+
+    let mut string: String = String::new();
+    let mut chars = value[1..].char_indices();
+
+    // Single quotes keep everything as written, double quotes have escapes
+    let end: usize = loop {
+        match chars.next() {
+            Some((i, c)) if c == quote => break i + 2,
+            Some((_, '\\')) if quote == '"' => match chars.next() {
+                Some((_, 'n')) => string.push('\n'),
+                Some((_, 't')) => string.push('\t'),
+                Some((_, c)) => string.push(c),
+                None => break 0,
+            },
+            Some((_, c)) => string.push(c),
+            None => break 0,
+        }
+    };
+
+    if end == 0 {
+        return Err(ParseError::new("Unterminated quoted value.", position));
+    }
+
+    // Only a comment can follow
+    let rest: &str = value[end..].trim_start();
+
+    if !rest.is_empty() && !rest.starts_with('#') {
+        return Err(ParseError::new(
+            "Unexpected characters after the quoted value.",
+            position,
+        ));
+    }
+
+    return Ok(string);
+}
+
+// Reads `KEY=value` lines, with `#` comments, an optional `export` and quoted values
+pub fn parse_dotenv(content: &str) -> Result<Vec<(String, String)>, ParseError> {
+    let mut vars: Vec<(String, String)> = vec![];
+    let mut offset: usize = 0;
+
+    for (i, line) in content.lines().enumerate() {
+        let mut position: Position = Position::new();
+        position.line = i + 1;
+        position.offset = offset;
+
+        offset += line.len() + 1;
+
+        let line: &str = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let line: &str = line.strip_prefix("export ").unwrap_or(line).trim_start();
+
+        let (name, value) = match line.split_once('=') {
+            Some((name, value)) => (name.trim(), value.trim()),
+            None => return Err(ParseError::new("Expected `NAME=value`.", position)),
+        };
+
+        if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+            return Err(ParseError::new(
+                format!("Invalid variable name '{}'.", name).as_str(),
+                position,
+            ));
+        }
+
+        vars.push((String::from(name), unquote_dotenv_value(value, position)?));
+    }
+
+    return Ok(vars);
+}
+
+fn parse_toml_value(value: &str) -> Option<AST> {
+    let statement: String = format!("v = {}\n", value);
+
+    return match Parser::from_string(statement.as_str()).try_parse() {
+        Ok(AST::ASTCompound(statements)) => match statements.as_slice() {
+            [AST::ASTVariableDefinition(_, value, _)] => Some(*value.clone()),
+            _ => None,
+        },
+        _ => None,
+    };
+}
+
+fn get_ast_type(value: &AST) -> String {
+    return match try_convert_ast_value_to_string(value, true) {
+        Ok(string) => String::from(get_value_type(string.as_str()).unwrap_or("array")),
+        Err(_) => String::from("array"),
+    };
+}
+
+// Converts to the type of the value being replaced
+fn coerce_value(existing: &AST, value: &str) -> Result<AST, String> {
+    return match existing {
+        AST::ASTString(_) => {
+            if value.contains('"') {
+                Err(String::from(
+                    "Strings containing `\"` can't be represented in TOML.",
+                ))
+            } else {
+                Ok(AST::ASTString(String::from(value)))
+            }
+        }
+        AST::ASTInt(_) => match value.trim().parse::<i32>() {
+            Ok(v) => Ok(AST::ASTInt(v)),
+            Err(_) => Err(format!("Expected an integer, found \"{}\".", value)),
+        },
+        AST::ASTBool(_) => match value.trim().to_lowercase().as_str() {
+            "true" | "1" | "yes" | "on" => Ok(AST::ASTBool(true)),
+            "false" | "0" | "no" | "off" => Ok(AST::ASTBool(false)),
+            _ => Err(format!("Expected a bool, found \"{}\".", value)),
+        },
+        AST::ASTDateTime(_) => {
+            if is_datetime(value.trim()) {
+                Ok(AST::ASTDateTime(String::from(value.trim())))
+            } else {
+                Err(format!("Expected a date or time, found \"{}\".", value))
+            }
+        }
+        AST::ASTArray(values) => match parse_toml_value(value) {
+            Some(array @ AST::ASTArray(_)) => {
+                // Empty arrays take any type
+                if !values.is_empty()
+                    && get_ast_type(existing) != get_ast_type(&array)
+                    && get_ast_type(&array) != "array"
+                {
+                    Err(format!(
+                        "Expected an array of `{}`, found an array of `{}`.",
+                        &get_ast_type(existing)["array_".len()..],
+                        &get_ast_type(&array)["array_".len()..]
+                    ))
+                } else {
+                    Ok(array)
+                }
+            }
+            _ => Err(format!("Expected an array, found \"{}\".", value)),
+        },
+        _ => Err(String::from("Unsupported value.")),
+    };
+}
+
+fn infer_value(value: &str) -> Result<AST, String> {
+    return match parse_toml_value(value) {
+        Some(ast) => Ok(ast),
+        None => coerce_value(&AST::ASTString(String::new()), value),
+    };
+}
+
+fn overlay_table(
+    table: &mut TOMLTable,
+    rest: &str,
+    value: &str,
+    options: &EnvOptions,
+) -> Result<(), String> {
+    let separator: &str = options.separator.as_str();
+
+    // Keys may contain the separator, the longest matching key wins
+    let mut matched: Option<(usize, usize)> = None;
+
+    for (i, (key, _)) in table.entries.iter().enumerate() {
+        let len: usize = options.get_segment(key).len();
+
+        let fits: bool = rest.len() >= len
+            && rest.is_char_boundary(len)
+            && options.matches_segment(key, &rest[0..len])
+            && (rest.len() == len || rest[len..].starts_with(separator));
+
+        if fits && matched.map_or(true, |(_, l)| len > l) {
+            matched = Some((i, len));
+        }
+    }
+
+    let (index, len) = match matched {
+        Some(matched) => matched,
+        None => {
+            // New keys create the tables on the way
+            let segments: Vec<&str> = rest.split(separator).collect();
+
+            if segments.iter().any(|s| s.is_empty()) {
+                return Err(String::from("Empty key in the variable name."));
+            }
+
+            let get_key = |segment: &str| match options.case {
+                EnvCase::Preserve => String::from(segment),
+                _ => segment.to_lowercase(),
+            };
+
+            let mut current: &mut TOMLTable = table;
+
+            for segment in &segments[0..segments.len() - 1] {
+                let key: String = get_key(segment);
+                current.insert(key.as_str(), TOMLTree::Table(TOMLTable::new()));
+
+                current = match current.get_mut(key.as_str()) {
+                    Some(TOMLTree::Table(t)) => t,
+                    _ => return Err(String::from("Failed to create a table.")),
+                };
+            }
+
+            let key: String = get_key(segments[segments.len() - 1]);
+            current.insert(key.as_str(), TOMLTree::Value(infer_value(value)?, None));
+
+            return Ok(());
+        }
+    };
+
+    let rest: &str = rest[len..].strip_prefix(separator).unwrap_or("");
+    let key: String = table.entries[index].0.clone();
+
+    return match &mut table.entries[index].1 {
+        TOMLTree::Value(existing, position) if rest.is_empty() => {
+            *existing = coerce_value(existing, value)?;
+            *position = None;
+            Ok(())
+        }
+        TOMLTree::Value(_, _) => Err(format!("`{}` is a value, not a table.", key)),
+        TOMLTree::Table(sub_table) if !rest.is_empty() => {
+            overlay_table(sub_table, rest, value, options)
+        }
+        TOMLTree::Table(_) => Err(format!("`{}` is a table and can't be set.", key)),
+        TOMLTree::TableArray(tables) => {
+            let (index, rest) = rest.split_once(separator).unwrap_or((rest, ""));
+
+            let sub_table: &mut TOMLTable = match index.parse::<usize>() {
+                Ok(i) if i < tables.len() && !rest.is_empty() => &mut tables[i],
+                Ok(i) if i < tables.len() => {
+                    return Err(format!("`{}[{}]` is a table and can't be set.", key, i));
+                }
+                Ok(i) => {
+                    return Err(format!(
+                        "Index {} is out of range, `{}` has {} elements.",
+                        i,
+                        key,
+                        tables.len()
+                    ));
+                }
+                Err(_) => return Err(format!("Expected an index into `{}`.", key)),
+            };
+
+            overlay_table(sub_table, rest, value, options)
+        }
+    };
+}
+
+// Applies every variable starting with the prefix, others are ignored. Without a prefix
+// every variable applies. Returns the names that were applied.
+pub fn overlay_env(
+    table: &mut TOMLTable,
+    vars: &[(String, String)],
+    options: &EnvOptions,
+) -> Result<Vec<String>, EnvError> {
+    let mut applied: Vec<String> = vec![];
+
+    let prefix: String = if options.prefix.is_empty() {
+        String::new()
+    } else {
+        options.get_segment(options.prefix.as_str()) + options.separator.as_str()
+    };
+
+    for (name, value) in vars {
+        let rest: &str = match name.get(0..prefix.len()) {
+            Some(start) if start.eq_ignore_ascii_case(prefix.as_str()) => &name[prefix.len()..],
+            _ => continue,
+        };
+
+        if let Err(message) = overlay_table(table, rest, value, options) {
+            return Err(EnvError {
+                name: name.clone(),
+                message,
+            });
+        }
+
+        applied.push(name.clone());
+    }
+
+    return Ok(applied);
+}
@@ -0,0 +1,472 @@
+use crate::json::{convert_ast_to_json, JSONValue};
+use crate::parser::ast::AST;
+use crate::parser::converter::{get_common_type, get_value_type, try_convert_ast_value_to_string};
+use crate::parser::parser::is_datetime;
+use crate::parser::path::{format_path, PathSegment};
+use crate::parser::token::Token;
+use crate::parser::tree::{TOMLTable, TOMLTree};
+use std::fmt;
+
+// Documents become JSON objects. Plain mode writes values as their closest JSON type,
+// typed mode writes every value as `{"type": "integer", "value": "5"}` so datetimes and
+// integers survive the round trip. Reading JSON only accepts the typed form when asked to,
+// otherwise `{"type": ..., "value": ...}` is an ordinary table.
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct JSONConversionError {
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for JSONConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            return write!(f, "{}", self.message);
+        }
+
+        return write!(f, "`{}`: {}", self.path, self.message);
+    }
+}
+
+impl std::error::Error for JSONConversionError {}
+
+fn error(path: &[PathSegment], message: &str) -> JSONConversionError {
+    return JSONConversionError {
+        path: format_path(path),
+        message: String::from(message),
+    };
+}
+
+fn get_datetime_type(value: &str) -> &str {
+    if value.len() == 10 {
+        return "date-local";
+    }
+
+    // Local times have no date in front
+    return match value.get(11..) {
+        Some(time) if value.as_bytes()[4] == b'-' => {
+            if time.ends_with(['Z', 'z']) || time.contains(['+', '-']) {
+                "datetime"
+            } else {
+                "datetime-local"
+            }
+        }
+        _ => "time-local",
+    };
+}
+
+fn typed_value(value_type: &str, value: String) -> JSONValue {
+    return JSONValue::Object(vec![
+        (
+            String::from("type"),
+            JSONValue::String(String::from(value_type)),
+        ),
+        (String::from("value"), JSONValue::String(value)),
+    ]);
+}
+
+fn convert_ast_to_typed_json(value: &AST) -> JSONValue {
+    return match value {
+        AST::ASTBool(v) => typed_value("bool", v.to_string()),
+        AST::ASTInt(v) => typed_value("integer", v.to_string()),
+        AST::ASTString(v) => typed_value("string", v.clone()),
+        AST::ASTDateTime(v) => typed_value(get_datetime_type(v), v.clone()),
+        AST::ASTArray(values) => {
+            JSONValue::Array(values.iter().map(convert_ast_to_typed_json).collect())
+        }
+        _ => JSONValue::Null,
+    };
+}
+
+pub fn convert_subtree_to_json(tree: &TOMLTree, typed: bool) -> JSONValue {
+    return match tree {
+        TOMLTree::Value(ast, _) if typed => convert_ast_to_typed_json(ast),
+        TOMLTree::Value(ast, _) => convert_ast_to_json(ast).unwrap_or(JSONValue::Null),
+        TOMLTree::Table(table) => convert_tree_to_json(table, typed),
+        TOMLTree::TableArray(tables) => JSONValue::Array(
+            tables
+                .iter()
+                .map(|t| convert_tree_to_json(t, typed))
+                .collect(),
+        ),
+    };
+}
+
+pub fn convert_tree_to_json(table: &TOMLTable, typed: bool) -> JSONValue {
+    return JSONValue::Object(
+        table
+            .entries
+            .iter()
+            .map(|(key, tree)| (key.clone(), convert_subtree_to_json(tree, typed)))
+            .collect(),
+    );
+}
+
+// `{"type": ..., "value": "..."}` with a known type, anything else is a table
+fn get_typed_value(value: &JSONValue) -> Option<(&str, &str)> {
+    if !matches!(value, JSONValue::Object(entries) if entries.len() == 2) {
+        return None;
+    }
+
+    let value_type: &str = value.get("type")?.as_str()?;
+    let typed: &str = value.get("value")?.as_str()?;
+
+    return match value_type {
+        "string" | "integer" | "float" | "bool" | "datetime" | "datetime-local" | "date-local"
+        | "time-local" => Some((value_type, typed)),
+        _ => None,
+    };
+}
+
+fn convert_typed_value_to_ast(
+    value_type: &str,
+    value: &str,
+    path: &[PathSegment],
+) -> Result<AST, JSONConversionError> {
+    return match value_type {
+        "string" => convert_string_to_ast(value, path),
+        "integer" => match value.parse::<i64>() {
+            Ok(v) => convert_int_to_ast(v, path),
+            Err(_) => Err(error(
+                path,
+                format!("\"{}\" isn't an integer.", value).as_str(),
+            )),
+        },
+        "bool" => match value {
+            "true" => Ok(AST::ASTBool(true)),
+            "false" => Ok(AST::ASTBool(false)),
+            _ => Err(error(path, format!("\"{}\" isn't a bool.", value).as_str())),
+        },
+        "float" => Err(error(path, "Floats can't be represented in TOML.")),
+        _ => {
+            if is_datetime(value) {
+                Ok(AST::ASTDateTime(String::from(value)))
+            } else {
+                Err(error(
+                    path,
+                    format!("\"{}\" isn't a date or time.", value).as_str(),
+                ))
+            }
+        }
+    };
+}
+
+fn convert_int_to_ast(value: i64, path: &[PathSegment]) -> Result<AST, JSONConversionError> {
+    return match i32::try_from(value) {
+        Ok(v) => Ok(AST::ASTInt(v)),
+        Err(_) => Err(error(
+            path,
+            format!("{} doesn't fit in a 32 bit integer.", value).as_str(),
+        )),
+    };
+}
+
+fn convert_string_to_ast(value: &str, path: &[PathSegment]) -> Result<AST, JSONConversionError> {
+    // Strings have no escapes
+    if value.contains(['"', '\0']) {
+        return Err(error(
+            path,
+            "Strings containing `\"` can't be represented in TOML.",
+        ));
+    }
+
+    return Ok(AST::ASTString(String::from(value)));
+}
+
+// The converter's type tag, like `int` or `array_string`
+fn get_ast_type(value: &AST) -> String {
+    return match try_convert_ast_value_to_string(value, true) {
+        Ok(string) => String::from(get_value_type(string.as_str()).unwrap_or("array")),
+        Err(_) => String::from("array"),
+    };
+}
+
+fn convert_json_to_ast(
+    value: &JSONValue,
+    typed: bool,
+    path: &mut Vec<PathSegment>,
+) -> Result<AST, JSONConversionError> {
+    if let (true, Some((value_type, v))) = (typed, get_typed_value(value)) {
+        return convert_typed_value_to_ast(value_type, v, path);
+    }
+
+    return match value {
+        JSONValue::Null => Err(error(path, "Null can't be represented in TOML.")),
+        JSONValue::Bool(v) => Ok(AST::ASTBool(*v)),
+        JSONValue::Int(v) => convert_int_to_ast(*v, path),
+        JSONValue::Float(_) => Err(error(path, "Floats can't be represented in TOML.")),
+        JSONValue::String(v) => convert_string_to_ast(v, path),
+        JSONValue::Array(values) => {
+            let mut array: Vec<AST> = vec![];
+
+            for (i, v) in values.iter().enumerate() {
+                path.push(PathSegment::Index(i));
+                array.push(convert_json_to_ast(v, typed, path)?);
+                path.pop();
+            }
+
+            // Arrays hold a single type, empty arrays match any array type
+            let mut array_type: Option<String> = None;
+
+            for t in array.iter().map(get_ast_type) {
+                array_type = match &array_type {
+                    None => Some(t),
+                    Some(current) => match get_common_type(current.as_str(), t.as_str()) {
+                        Some(common) => Some(String::from(common)),
+                        None => {
+                            return Err(error(
+                                path,
+                                format!("Arrays can't mix types, found `{}` and `{}`.", current, t)
+                                    .as_str(),
+                            ));
+                        }
+                    },
+                };
+            }
+
+            Ok(AST::ASTArray(array))
+        }
+        JSONValue::Object(_) => Err(error(path, "Arrays can't hold tables and values.")),
+    };
+}
+
+fn is_table(value: &JSONValue, typed: bool) -> bool {
+    return matches!(value, JSONValue::Object(_)) && !(typed && get_typed_value(value).is_some());
+}
+
+pub fn convert_json_to_subtree(
+    value: &JSONValue,
+    typed: bool,
+    path: &mut Vec<PathSegment>,
+) -> Result<TOMLTree, JSONConversionError> {
+    return match value {
+        _ if is_table(value, typed) => {
+            Ok(TOMLTree::Table(convert_json_to_table(value, typed, path)?))
+        }
+        // Arrays of objects are arrays of tables
+        JSONValue::Array(values)
+            if !values.is_empty() && values.iter().all(|v| is_table(v, typed)) =>
+        {
+            let mut tables: Vec<TOMLTable> = vec![];
+
+            for (i, v) in values.iter().enumerate() {
+                path.push(PathSegment::Index(i));
+                tables.push(convert_json_to_table(v, typed, path)?);
+                path.pop();
+            }
+
+            Ok(TOMLTree::TableArray(tables))
+        }
+        _ => Ok(TOMLTree::Value(
+            convert_json_to_ast(value, typed, path)?,
+            None,
+        )),
+    };
+}
+
+fn convert_json_to_table(
+    value: &JSONValue,
+    typed: bool,
+    path: &mut Vec<PathSegment>,
+) -> Result<TOMLTable, JSONConversionError> {
+    let entries: &Vec<(String, JSONValue)> = match value {
+        JSONValue::Object(entries) if is_table(value, typed) => entries,
+        _ => return Err(error(path, "Expected an object.")),
+    };
+
+    let mut table: TOMLTable = TOMLTable::new();
+
+    for (key, v) in entries {
+        path.push(PathSegment::Key(key.clone()));
+
+        let subtree: TOMLTree = convert_json_to_subtree(v, typed, path)?;
+
+        // Headers need bare keys, dots would be read back as nested tables. Other keys are
+        // quoted when needed, but quoted keys have no escapes.
+        let is_header: bool = !matches!(subtree, TOMLTree::Value(_, _));
+
+        if is_header && (key.contains(['.', '#']) || !Token::is_valid_id(key)) {
+            return Err(error(path, "Table name can't be represented in TOML."));
+        }
+
+        if key.contains(['"', '\\', '\0']) {
+            return Err(error(path, "Key can't be represented in TOML."));
+        }
+
+        table.insert(key, subtree);
+        path.pop();
+    }
+
+    return Ok(table);
+}
+
+// The top level has to be an object, it becomes the document. With `typed`, objects like
+// `{"type": "integer", "value": "5"}` are read as values.
+pub fn convert_json_to_tree(
+    value: &JSONValue,
+    typed: bool,
+) -> Result<TOMLTable, JSONConversionError> {
+    if !is_table(value, typed) {
+        return Err(error(
+            &[],
+            "The top level of the document must be an object.",
+        ));
+    }
+
+    return convert_json_to_table(value, typed, &mut vec![]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json::{convert_json_to_string, parse_json};
+    use crate::parser::tree::parse_document;
+    use crate::parser::writer::convert_tree_to_toml;
+
+    fn parse_toml(content: &str) -> TOMLTable {
+        return parse_document(content).unwrap();
+    }
+
+    fn to_json(content: &str, typed: bool) -> String {
+        return convert_json_to_string(&convert_tree_to_json(&parse_toml(content), typed), false);
+    }
+
+    fn from_json(content: &str, typed: bool) -> Result<String, String> {
+        return convert_json_to_tree(&parse_json(content).unwrap(), typed)
+            .map(|table| convert_tree_to_toml(&table, false))
+            .map_err(|e| e.to_string());
+    }
+
+    const DOCUMENT: &str = "name = \"app\"\nport = 80\ndebug = true\nstarted = 1979-05-27T07:32:00Z\nday = 1979-05-27\nmatrix = [[], [1, 2]]\n\"a b\" = [\"x\"]\n\n[[servers]]\nhost = \"a\"\n\n[[servers]]\nhost = \"b\"\nat = 07:32:00\n";
+
+    #[test]
+    fn plain_values() {
+        assert_eq!(
+            to_json(DOCUMENT, false),
+            concat!(
+                "{\"name\":\"app\",\"port\":80,\"debug\":true,\"started\":\"1979-05-27T07:32:00Z\",",
+                "\"day\":\"1979-05-27\",\"matrix\":[[],[1,2]],\"a b\":[\"x\"],",
+                "\"servers\":[{\"host\":\"a\"},{\"host\":\"b\",\"at\":\"07:32:00\"}]}"
+            )
+        );
+    }
+
+    #[test]
+    fn typed_values() {
+        assert_eq!(
+            to_json("a = true\nb = 5\nc = \"s\"\nd = 1979-05-27T07:32:00-07:00\ne = 1979-05-27T07:32:00\nf = 1979-05-27\ng = 07:32:00\n", true),
+            concat!(
+                "{\"a\":{\"type\":\"bool\",\"value\":\"true\"},",
+                "\"b\":{\"type\":\"integer\",\"value\":\"5\"},",
+                "\"c\":{\"type\":\"string\",\"value\":\"s\"},",
+                "\"d\":{\"type\":\"datetime\",\"value\":\"1979-05-27T07:32:00-07:00\"},",
+                "\"e\":{\"type\":\"datetime-local\",\"value\":\"1979-05-27T07:32:00\"},",
+                "\"f\":{\"type\":\"date-local\",\"value\":\"1979-05-27\"},",
+                "\"g\":{\"type\":\"time-local\",\"value\":\"07:32:00\"}}"
+            )
+        );
+    }
+
+    #[test]
+    fn typed_arrays_of_tables() {
+        assert_eq!(
+            to_json("[[servers]]\nport = 1\n\n[[servers]]\nports = [2]\n", true),
+            concat!(
+                "{\"servers\":[{\"port\":{\"type\":\"integer\",\"value\":\"1\"}},",
+                "{\"ports\":[{\"type\":\"integer\",\"value\":\"2\"}]}]}"
+            )
+        );
+    }
+
+    #[test]
+    fn typed_round_trip() {
+        let json: String = to_json(DOCUMENT, true);
+        let toml: String = from_json(json.as_str(), true).unwrap();
+
+        assert_eq!(to_json(toml.as_str(), true), json);
+    }
+
+    #[test]
+    fn plain_round_trip_keeps_strings() {
+        // Without `typed` the datetimes come back as strings
+        let json: String = to_json(DOCUMENT, false);
+        let toml: String = from_json(json.as_str(), false).unwrap();
+
+        assert_eq!(to_json(toml.as_str(), false), json);
+        assert!(toml.contains("started = \"1979-05-27T07:32:00Z\""));
+    }
+
+    #[test]
+    fn typed_objects_are_tables_by_default() {
+        let json: &str = "{\"a\":{\"type\":\"integer\",\"value\":\"5\"}}";
+
+        assert_eq!(
+            from_json(json, false),
+            Ok(String::from("[a]\ntype = \"integer\"\nvalue = \"5\"\n"))
+        );
+        assert_eq!(from_json(json, true), Ok(String::from("a = 5\n")));
+    }
+
+    #[test]
+    fn nested_empty_arrays() {
+        assert_eq!(
+            from_json("{\"a\":[[],[1]],\"b\":[[1],[]]}", false),
+            Ok(String::from("a = [[], [1]]\nb = [[1], []]\n"))
+        );
+        assert_eq!(
+            from_json("{\"a\":[[1],[true]]}", false),
+            Err(String::from(
+                "`a`: Arrays can't mix types, found `array_int` and `array_bool`."
+            ))
+        );
+    }
+
+    #[test]
+    fn quoted_keys() {
+        let json: &str = "{\"a b\":1,\"#c\":2,\"t\":{\"x.y\":[1]}}";
+        let toml: String = from_json(json, false).unwrap();
+
+        assert_eq!(toml, "\"a b\" = 1\n\"#c\" = 2\n[t]\n\"x.y\" = [1]\n");
+        assert_eq!(to_json(toml.as_str(), false), json);
+    }
+
+    #[test]
+    fn unrepresentable_keys() {
+        assert_eq!(
+            from_json("{\"a b\":{\"c\":1}}", false),
+            Err(String::from(
+                "`\"a b\"`: Table name can't be represented in TOML."
+            ))
+        );
+        assert_eq!(
+            from_json("{\"a\\\"b\":1}", false),
+            Err(String::from(
+                "`\"a\\\"b\"`: Key can't be represented in TOML."
+            ))
+        );
+    }
+
+    #[test]
+    fn unrepresentable_values() {
+        assert_eq!(
+            from_json("{\"a\":null}", false),
+            Err(String::from("`a`: Null can't be represented in TOML."))
+        );
+        assert_eq!(
+            from_json("{\"a\":[1.5]}", false),
+            Err(String::from("`a[0]`: Floats can't be represented in TOML."))
+        );
+        assert_eq!(
+            from_json("{\"a\":[1,\"b\"]}", false),
+            Err(String::from(
+                "`a`: Arrays can't mix types, found `int` and `string`."
+            ))
+        );
+        assert_eq!(
+            from_json("[1]", false),
+            Err(String::from(
+                "The top level of the document must be an object."
+            ))
+        );
+    }
+}
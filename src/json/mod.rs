@@ -0,0 +1,432 @@
+pub mod convert;
+
+use crate::parser::ast::AST;
+use crate::parser::error::ParseError;
+use crate::parser::token::Position;
+
+// Objects keep their keys in source order
+#[derive(Debug, Clone, PartialEq)]
+pub enum JSONValue {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Array(Vec<JSONValue>),
+    Object(Vec<(String, JSONValue)>),
+}
+
+impl JSONValue {
+    pub fn get(&self, key: &str) -> Option<&JSONValue> {
+        return match self {
+            JSONValue::Object(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        };
+    }
+
+    pub fn insert(&mut self, key: &str, value: JSONValue) {
+        if let JSONValue::Object(entries) = self {
+            if let Some(entry) = entries.iter_mut().find(|(k, _)| k == key) {
+                entry.1 = value;
+            } else {
+                entries.push((String::from(key), value));
+            }
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        return match self {
+            JSONValue::String(v) => Some(v.as_str()),
+            _ => None,
+        };
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        return match self {
+            JSONValue::Int(v) => Some(*v),
+            JSONValue::Float(v) if v.fract() == 0.0 && v.abs() < 9.0e15 => Some(*v as i64),
+            _ => None,
+        };
+    }
+}
+
+struct JSONParser {
+    chars: Vec<char>,
+    index: usize,
+    position: Position,
+}
+
+impl JSONParser {
+    fn peek(&self) -> Option<char> {
+        return self.chars.get(self.index).copied();
+    }
+
+    fn advance(&mut self) -> Option<char> {
+        let c: char = self.peek()?;
+
+        self.index += 1;
+        self.position.offset += c.len_utf8();
+
+        if c == '\n' {
+            self.position.line += 1;
+            self.position.column = 1;
+        } else {
+            self.position.column += 1;
+        }
+
+        return Some(c);
+    }
+
+    fn error(&self, message: &str) -> ParseError {
+        return ParseError::new(message, self.position);
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(' ' | '\t' | '\n' | '\r') = self.peek() {
+            self.advance();
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), ParseError> {
+        return match self.advance() {
+            Some(c) if c == expected => Ok(()),
+            Some(c) => Err(self.error(format!("Expected '{}', found '{}'.", expected, c).as_str())),
+            None => Err(self
+                .error(format!("Expected '{}', found the end of the input.", expected).as_str())),
+        };
+    }
+
+    fn parse_value(&mut self) -> Result<JSONValue, ParseError> {
+        self.skip_whitespace();
+
+        return match self.peek() {
+            Some('{') => self.parse_object(),
+            Some('[') => self.parse_array(),
+            Some('"') => Ok(JSONValue::String(self.parse_string()?)),
+            Some('-' | '0'..='9') => self.parse_number(),
+            Some('t') => self.parse_literal("true", JSONValue::Bool(true)),
+            Some('f') => self.parse_literal("false", JSONValue::Bool(false)),
+            Some('n') => self.parse_literal("null", JSONValue::Null),
+            Some(c) => Err(self.error(format!("Unexpected character '{}'.", c).as_str())),
+            None => Err(self.error("Unexpected end of the input.")),
+        };
+    }
+
+    fn parse_literal(&mut self, literal: &str, value: JSONValue) -> Result<JSONValue, ParseError> {
+        for c in literal.chars() {
+            self.expect(c)?;
+        }
+
+        return Ok(value);
+    }
+
+    fn parse_object(&mut self) -> Result<JSONValue, ParseError> {
+        self.expect('{')?;
+
+        let mut entries: Vec<(String, JSONValue)> = vec![];
+
+        self.skip_whitespace();
+
+        if self.peek() == Some('}') {
+            self.advance();
+            return Ok(JSONValue::Object(entries));
+        }
+
+        loop {
+            self.skip_whitespace();
+
+            if self.peek() != Some('"') {
+                return Err(self.error("Expected a string key."));
+            }
+
+            let key: String = self.parse_string()?;
+
+            self.skip_whitespace();
+            self.expect(':')?;
+
+            let value: JSONValue = self.parse_value()?;
+
+            // Later keys win, like most parsers
+            if let Some(entry) = entries.iter_mut().find(|(k, _)| *k == key) {
+                entry.1 = value;
+            } else {
+                entries.push((key, value));
+            }
+
+            self.skip_whitespace();
+
+            match self.advance() {
+                Some(',') => {}
+                Some('}') => break,
+                _ => return Err(self.error("Expected ',' or '}' in object.")),
+            }
+        }
+
+        return Ok(JSONValue::Object(entries));
+    }
+
+    fn parse_array(&mut self) -> Result<JSONValue, ParseError> {
+        self.expect('[')?;
+
+        let mut values: Vec<JSONValue> = vec![];
+
+        self.skip_whitespace();
+
+        if self.peek() == Some(']') {
+            self.advance();
+            return Ok(JSONValue::Array(values));
+        }
+
+        loop {
+            values.push(self.parse_value()?);
+
+            self.skip_whitespace();
+
+            match self.advance() {
+                Some(',') => {}
+                Some(']') => break,
+                _ => return Err(self.error("Expected ',' or ']' in array.")),
+            }
+        }
+
+        return Ok(JSONValue::Array(values));
+    }
+
+    fn parse_hex(&mut self) -> Result<u32, ParseError> {
+        let mut value: u32 = 0;
+
+        for _ in 0..4 {
+            let digit: u32 = match self.advance().and_then(|c| c.to_digit(16)) {
+                Some(digit) => digit,
+                None => return Err(self.error("Invalid unicode escape.")),
+            };
+
+            value = value * 16 + digit;
+        }
+
+        return Ok(value);
+    }
+
+    fn parse_string(&mut self) -> Result<String, ParseError> {
+        self.expect('"')?;
+
+        let mut string: String = String::new();
+
+        loop {
+            let c: char = match self.advance() {
+                Some(c) => c,
+                None => return Err(self.error("Unterminated string.")),
+            };
+
+            match c {
+                '"' => break,
+                '\\' => {
+                    let escaped: char = match self.advance() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => {
+                            let mut code: u32 = self.parse_hex()?;
+
+                            // Surrogate pairs
+                            if (0xD800..0xDC00).contains(&code) {
+                                self.expect('\\')?;
+                                self.expect('u')?;
+
+                                let low: u32 = self.parse_hex()?;
+
+                                if !(0xDC00..0xE000).contains(&low) {
+                                    return Err(self.error("Invalid surrogate pair."));
+                                }
+
+                                code = 0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00);
+                            }
+
+                            match char::from_u32(code) {
+                                Some(c) => c,
+                                None => return Err(self.error("Invalid unicode escape.")),
+                            }
+                        }
+                        _ => return Err(self.error("Invalid escape.")),
+                    };
+
+                    string.push(escaped);
+                }
+                c if (c as u32) < 0x20 => {
+                    return Err(self.error("Control characters must be escaped."));
+                }
+                c => string.push(c),
+            }
+        }
+
+        return Ok(string);
+    }
+
+    fn parse_number(&mut self) -> Result<JSONValue, ParseError> {
+        let start: usize = self.index;
+
+        while let Some('-' | '+' | '.' | 'e' | 'E' | '0'..='9') = self.peek() {
+            self.advance();
+        }
+
+        let number: String = self.chars[start..self.index].iter().collect();
+
+        if let Ok(v) = number.parse::<i64>() {
+            return Ok(JSONValue::Int(v));
+        }
+
+        return match number.parse::<f64>() {
+            Ok(v) => Ok(JSONValue::Float(v)),
+            Err(_) => Err(self.error(format!("Invalid number '{}'.", number).as_str())),
+        };
+    }
+}
+
+pub fn parse_json(content: &str) -> Result<JSONValue, ParseError> {
+    let mut parser: JSONParser = JSONParser {
+        chars: content.chars().collect(),
+        index: 0,
+        position: Position::new(),
+    };
+
+    let value: JSONValue = parser.parse_value()?;
+
+    parser.skip_whitespace();
+
+    if parser.peek().is_some() {
+        return Err(parser.error("Unexpected data after the value."));
+    }
+
+    return Ok(value);
+}
+
+fn convert_str_to_json(value: &str) -> String {
+    let mut string: String = String::from("\"");
+
+    for c in value.chars() {
+        match c {
+            '"' => string += "\\\"",
+            '\\' => string += "\\\\",
+            '\n' => string += "\\n",
+            '\r' => string += "\\r",
+            '\t' => string += "\\t",
+            c if (c as u32) < 0x20 => string += format!("\\u{:04x}", c as u32).as_str(),
+            c => string.push(c),
+        }
+    }
+
+    string.push('"');
+
+    return string;
+}
+
+fn convert_json_to_string_at(value: &JSONValue, pretty: bool, depth: usize, string: &mut String) {
+    let indent = |string: &mut String, depth: usize| {
+        if pretty {
+            string.push('\n');
+            string.push_str("  ".repeat(depth).as_str());
+        }
+    };
+
+    match value {
+        JSONValue::Null => *string += "null",
+        JSONValue::Bool(v) => *string += v.to_string().as_str(),
+        JSONValue::Int(v) => *string += v.to_string().as_str(),
+        JSONValue::Float(v) => {
+            // JSON has no infinity or NaN
+            if !v.is_finite() {
+                *string += "null";
+            } else if v.fract() == 0.0 && v.abs() < 1e16 {
+                *string += format!("{:.1}", v).as_str();
+            } else {
+                *string += v.to_string().as_str();
+            }
+        }
+        JSONValue::String(v) => *string += convert_str_to_json(v).as_str(),
+        JSONValue::Array(values) => {
+            if values.is_empty() {
+                *string += "[]";
+                return;
+            }
+
+            string.push('[');
+
+            for (i, v) in values.iter().enumerate() {
+                if i > 0 {
+                    string.push(',');
+                }
+
+                indent(string, depth + 1);
+                convert_json_to_string_at(v, pretty, depth + 1, string);
+            }
+
+            indent(string, depth);
+            string.push(']');
+        }
+        JSONValue::Object(entries) => {
+            if entries.is_empty() {
+                *string += "{}";
+                return;
+            }
+
+            string.push('{');
+
+            for (i, (k, v)) in entries.iter().enumerate() {
+                if i > 0 {
+                    string.push(',');
+                }
+
+                indent(string, depth + 1);
+                *string += convert_str_to_json(k).as_str();
+                *string += if pretty { ": " } else { ":" };
+                convert_json_to_string_at(v, pretty, depth + 1, string);
+            }
+
+            indent(string, depth);
+            string.push('}');
+        }
+    }
+}
+
+pub fn convert_json_to_string(value: &JSONValue, pretty: bool) -> String {
+    let mut string: String = String::new();
+
+    convert_json_to_string_at(value, pretty, 0, &mut string);
+
+    return string;
+}
+
+// Dates and times have no JSON type and become strings
+pub fn convert_ast_to_json(value: &AST) -> Option<JSONValue> {
+    return match value {
+        AST::ASTBool(v) => Some(JSONValue::Bool(*v)),
+        AST::ASTInt(v) => Some(JSONValue::Int(*v as i64)),
+        AST::ASTString(v) | AST::ASTDateTime(v) => Some(JSONValue::String(v.clone())),
+        AST::ASTArray(values) => values
+            .iter()
+            .map(convert_ast_to_json)
+            .collect::<Option<Vec<JSONValue>>>()
+            .map(JSONValue::Array),
+        _ => None,
+    };
+}
+
+// Only values TOML can hold, integers have to fit in an `i32`
+pub fn convert_json_to_ast(value: &JSONValue) -> Option<AST> {
+    return match value {
+        JSONValue::Bool(v) => Some(AST::ASTBool(*v)),
+        JSONValue::Int(v) => i32::try_from(*v).ok().map(AST::ASTInt),
+        JSONValue::String(v) => Some(AST::ASTString(v.clone())),
+        JSONValue::Array(values) => values
+            .iter()
+            .map(convert_json_to_ast)
+            .collect::<Option<Vec<AST>>>()
+            .map(AST::ASTArray),
+        _ => None,
+    };
+}
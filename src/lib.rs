@@ -3,33 +3,78 @@
 use core;
 
 use crate::parser::ast::AST;
-use crate::parser::converter::{convert_ast_to_string, TOMLStringMap};
+use crate::parser::converter::{try_convert_ast_to_string, TOMLStringMap};
 use crate::parser::parser::Parser;
 use std::collections::HashMap;
 use std::ffi::{CStr, CString};
 use std::os::raw::c_char;
+use std::ptr::null_mut;
 
 mod c;
-mod parser;
+#[cfg(feature = "serde")]
+pub mod de;
+pub mod diff;
+pub mod env;
+pub mod json;
+pub mod lint;
+pub mod lsp;
+pub mod merge;
+pub mod parser;
+pub mod patch;
+pub mod schema;
+#[cfg(feature = "serde")]
+pub mod ser;
+#[cfg(feature = "yaml")]
+pub mod yaml;
 
+// Lets `#[derive(FromToml)]` refer to `::toml_parser` from inside the crate
+extern crate self as toml_parser;
+
+pub use parser::from_toml::{from_toml_file, from_toml_str, FromToml};
+#[cfg(feature = "derive")]
+pub use toml_parser_derive::FromToml;
+
+#[cfg(feature = "serde")]
+pub use de::{from_path, from_str};
+#[cfg(feature = "serde")]
+pub use ser::{to_string, to_string_pretty};
+
+// Sets `cmap` to null if the file can't be read or isn't valid TOML.
 #[no_mangle]
 pub unsafe extern "C" fn toml_parser_parse(cmap: &mut *mut TOMLStringMap, filepath: *const c_char) {
+    *cmap = null_mut();
+
     // Conver to string
     let raw = CStr::from_ptr(filepath);
     let filepath = if let Ok(path) = raw.to_str() {
         path
     } else {
-        panic!("[ERROR] Failed to get filepath.");
+        return;
+    };
+
+    // Read the file, the parser borrows its tokens from it
+    let content: String = if let Ok(content) = std::fs::read_to_string(filepath) {
+        content
+    } else {
+        return;
     };
 
     // Create parser
-    let mut parser: Parser = Parser::new(filepath);
+    let mut parser: Parser = Parser::from_string(content.as_str());
 
     // Parse
-    let ast: AST = parser.parse();
+    let ast: AST = if let Ok(ast) = parser.try_parse() {
+        ast
+    } else {
+        return;
+    };
 
     // Convert to string
-    let map: TOMLStringMap = convert_ast_to_string(&ast, true);
+    let map: TOMLStringMap = if let Ok(map) = try_convert_ast_to_string(&ast, true) {
+        map
+    } else {
+        return;
+    };
 
     // Convert map to C map
     *cmap = Box::into_raw(Box::new(TOMLStringMap { tags: map.tags }));
@@ -37,5 +82,48 @@ pub unsafe extern "C" fn toml_parser_parse(cmap: &mut *mut TOMLStringMap, filepa
 
 #[no_mangle]
 pub unsafe extern "C" fn toml_parser_free(map: *mut TOMLStringMap) {
+    if map.is_null() {
+        return;
+    }
+
     Box::from_raw(map);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+
+    unsafe fn parse_file(name: &str, content: Option<&str>) -> *mut TOMLStringMap {
+        let path: PathBuf = std::env::temp_dir().join(name);
+        if let Some(content) = content {
+            fs::write(&path, content).unwrap();
+        }
+
+        let filepath: CString = CString::new(path.to_str().unwrap()).unwrap();
+        let mut map: *mut TOMLStringMap = null_mut();
+        toml_parser_parse(&mut map, filepath.as_ptr());
+
+        return map;
+    }
+
+    #[test]
+    fn parse_valid_file() {
+        unsafe {
+            let map: *mut TOMLStringMap = parse_file("toml_parser_valid.toml", Some("a = 1\n"));
+            assert!(!map.is_null());
+            toml_parser_free(map);
+        }
+    }
+
+    #[test]
+    fn parse_invalid_file_gives_null() {
+        unsafe {
+            assert!(parse_file("toml_parser_syntax.toml", Some("a = = 1\n")).is_null());
+            assert!(parse_file("toml_parser_types.toml", Some("a = [1, true]\n")).is_null());
+            assert!(parse_file("toml_parser_dup.toml", Some("a = 1\na = 2\n")).is_null());
+            assert!(parse_file("toml_parser_missing.toml", None).is_null());
+        }
+    }
+}
@@ -0,0 +1,1093 @@
+use crate::json::JSONValue;
+use crate::parser::error::ParseError;
+use crate::parser::layout::{get_document_layout, get_token_end, DocumentLayout, SectionLayout};
+use crate::parser::lexer::Lexer;
+use crate::parser::parser::is_datetime;
+use crate::parser::path::{format_path, PathSegment};
+use crate::parser::token::{Position, Token, TokenType};
+use crate::parser::tree::parse_document;
+use std::collections::HashMap;
+
+// Style and correctness lints for documents that parse. The rules work on the tokens and
+// the layout of the source, so every diagnostic has a span and fixes are edits of the text.
+// `# lint: allow(rule-id)` after a value silences the rule on that line, on its own line
+// on the next one, and `# lint: allow-file(rule-id)` in the whole file. `all` matches
+// every rule. Safe fixes keep the data of the document the same, the others rename keys or
+// change values and are only applied when asked for.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl Severity {
+    // Same names as SARIF levels
+    pub fn from_name(name: &str) -> Option<Self> {
+        return match name {
+            "error" => Some(Severity::Error),
+            "warning" => Some(Severity::Warning),
+            "note" => Some(Severity::Note),
+            _ => None,
+        };
+    }
+
+    pub fn get_name(&self) -> &'static str {
+        return match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "note",
+        };
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LintRule {
+    pub id: &'static str,
+    pub severity: Severity,
+    pub description: &'static str,
+}
+
+pub const RULES: [LintRule; 9] = [
+    LintRule {
+        id: "duplicate-key",
+        severity: Severity::Error,
+        description: "A key is defined twice in the same table.",
+    },
+    LintRule {
+        id: "global-key-after-table",
+        severity: Severity::Warning,
+        description: "An empty line ends a table, the keys after it are global.",
+    },
+    LintRule {
+        id: "table-order",
+        severity: Severity::Warning,
+        description: "Tables under the same key are split by other tables.",
+    },
+    LintRule {
+        id: "empty-table",
+        severity: Severity::Warning,
+        description: "A table has no keys and no tables under it.",
+    },
+    LintRule {
+        id: "string-as-value",
+        severity: Severity::Warning,
+        description: "A string holds a boolean, an integer or a date.",
+    },
+    LintRule {
+        id: "duplicate-value",
+        severity: Severity::Warning,
+        description: "An array holds the same value twice.",
+    },
+    LintRule {
+        id: "key-case",
+        severity: Severity::Note,
+        description: "A key mixes upper and lower case.",
+    },
+    LintRule {
+        id: "quoted-key",
+        severity: Severity::Note,
+        description: "A quoted key could be bare.",
+    },
+    LintRule {
+        id: "max-depth",
+        severity: Severity::Note,
+        description: "A table is nested deeper than the maximum depth.",
+    },
+];
+
+pub fn get_rule(id: &str) -> Option<&'static LintRule> {
+    return RULES.iter().find(|rule| rule.id == id);
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LintOptions {
+    // Severity by rule id, `None` turns a rule off
+    pub rules: HashMap<String, Option<Severity>>,
+    // Keys in the deepest allowed path, `a.b.c` is 3
+    pub max_depth: usize,
+}
+
+impl LintOptions {
+    pub fn new() -> Self {
+        return LintOptions {
+            rules: HashMap::new(),
+            max_depth: 4,
+        };
+    }
+
+    fn get_severity(&self, rule: &LintRule) -> Option<Severity> {
+        return match self.rules.get(rule.id) {
+            Some(severity) => *severity,
+            None => Some(rule.severity),
+        };
+    }
+}
+
+// Replaces the text between `start` and `end`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fix {
+    pub message: String,
+    pub start: Position,
+    pub end: Position,
+    pub replacement: String,
+    pub safe: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub rule: &'static str,
+    pub severity: Severity,
+    pub message: String,
+    pub start: Position,
+    pub end: Position,
+    pub fix: Option<Fix>,
+}
+
+// Line and column like the lexer counts them
+fn get_position(source: &str, offset: usize) -> Position {
+    let before: &str = &source[..offset];
+
+    return Position {
+        line: before.matches('\n').count() + 1,
+        column: before[before.rfind('\n').map_or(0, |i| i + 1)..]
+            .chars()
+            .count()
+            + 1,
+        offset,
+    };
+}
+
+fn get_line_range(source: &str, offset: usize) -> (usize, usize) {
+    let start: usize = source[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let end: usize = source[offset..]
+        .find('\n')
+        .map_or(source.len(), |i| offset + i + 1);
+
+    return (start, end);
+}
+
+fn count_keys(path: &[PathSegment]) -> usize {
+    return path
+        .iter()
+        .filter(|s| matches!(s, PathSegment::Key(_)))
+        .count();
+}
+
+fn get_root(path: &[PathSegment]) -> Option<&PathSegment> {
+    return path.first();
+}
+
+fn is_mixed_case(key: &str) -> bool {
+    return key.chars().any(|c| c.is_ascii_uppercase())
+        && key.chars().any(|c| c.is_ascii_lowercase());
+}
+
+// The path after the `key-case` fix
+fn get_renamed_path(path: &[PathSegment]) -> Vec<PathSegment> {
+    let mut renamed: Vec<PathSegment> = path.to_vec();
+
+    if let Some(PathSegment::Key(key)) = renamed.last_mut() {
+        if is_mixed_case(key) {
+            *key = convert_to_snake_case(key);
+        }
+    }
+
+    return renamed;
+}
+
+// `maxSize` and `MaxSize` become `max_size`
+fn convert_to_snake_case(key: &str) -> String {
+    let mut snake: String = String::new();
+    let mut previous: Option<char> = None;
+
+    for c in key.chars() {
+        if c.is_ascii_uppercase() {
+            if previous.map_or(false, |p| p.is_ascii_lowercase() || p.is_ascii_digit()) {
+                snake.push('_');
+            }
+
+            snake.push(c.to_ascii_lowercase());
+        } else {
+            snake.push(c);
+        }
+
+        previous = Some(c);
+    }
+
+    return snake;
+}
+
+struct Suppressions {
+    // Line and rule
+    lines: Vec<(usize, String)>,
+    file: Vec<String>,
+}
+
+impl Suppressions {
+    fn from_source(source: &str) -> Self {
+        let mut suppressions: Suppressions = Suppressions {
+            lines: vec![],
+            file: vec![],
+        };
+
+        for (i, line) in source.split('\n').enumerate() {
+            // Strings have no escapes, a `#` after an even number of quotes starts a comment
+            let mut in_string: bool = false;
+            let mut comment: Option<usize> = None;
+
+            for (j, c) in line.char_indices() {
+                match c {
+                    '"' => in_string = !in_string,
+                    '#' if !in_string => {
+                        comment = Some(j);
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+
+            let start: usize = match comment {
+                Some(start) => start,
+                None => continue,
+            };
+
+            let directive: &str = match line[start + 1..].trim().strip_prefix("lint:") {
+                Some(directive) => directive.trim(),
+                None => continue,
+            };
+
+            let (file, rules) = if let Some(rules) = directive.strip_prefix("allow-file(") {
+                (true, rules)
+            } else if let Some(rules) = directive.strip_prefix("allow(") {
+                (false, rules)
+            } else {
+                continue;
+            };
+
+            let rules: &str = rules.split(')').next().unwrap_or("");
+
+            // Comments on their own line cover the next line
+            let line_number: usize = if line[..start].trim().is_empty() {
+                i + 2
+            } else {
+                i + 1
+            };
+
+            for rule in rules.split(',').map(|r| String::from(r.trim())) {
+                if file {
+                    suppressions.file.push(rule);
+                } else {
+                    suppressions.lines.push((line_number, rule));
+                }
+            }
+        }
+
+        return suppressions;
+    }
+
+    fn is_suppressed(&self, diagnostic: &Diagnostic) -> bool {
+        let matches = |rule: &String| rule == diagnostic.rule || rule == "all";
+
+        return self.file.iter().any(matches)
+            || self
+                .lines
+                .iter()
+                .any(|(line, rule)| *line == diagnostic.start.line && matches(rule));
+    }
+}
+
+struct Linter<'a> {
+    source: &'a str,
+    options: &'a LintOptions,
+    layout: DocumentLayout,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Linter<'a> {
+    fn report(&mut self, id: &str, span: (usize, usize), message: String, fix: Option<Fix>) {
+        let rule: &'static LintRule = get_rule(id).unwrap();
+
+        if let Some(severity) = self.options.get_severity(rule) {
+            self.diagnostics.push(Diagnostic {
+                rule: rule.id,
+                severity,
+                message,
+                start: get_position(self.source, span.0),
+                end: get_position(self.source, span.1),
+                fix,
+            });
+        }
+    }
+
+    fn create_fix(
+        &self,
+        message: String,
+        span: (usize, usize),
+        replacement: &str,
+        safe: bool,
+    ) -> Fix {
+        return Fix {
+            message,
+            start: get_position(self.source, span.0),
+            end: get_position(self.source, span.1),
+            replacement: String::from(replacement),
+            safe,
+        };
+    }
+
+    fn get_header_text(&self, section: &SectionLayout) -> &'a str {
+        return match section.header {
+            Some((start, end)) => &self.source[start..end],
+            None => "",
+        };
+    }
+
+    fn lint_keys(&mut self, tokens: &[Token]) {
+        let mut defined: HashMap<String, usize> = HashMap::new();
+        let sections: Vec<SectionLayout> = self.layout.sections.clone();
+
+        // Paths once every key is renamed, a rename can't give two keys the same one
+        let mut renamed: HashMap<String, usize> = HashMap::new();
+
+        for key in sections.iter().flat_map(|s| &s.keys) {
+            *renamed
+                .entry(format_path(&get_renamed_path(&key.path)))
+                .or_insert(0) += 1;
+        }
+
+        for section in &sections {
+            for key in &section.keys {
+                let token: &Token = match tokens.iter().find(|t| t.position.offset == key.start) {
+                    Some(token) => token,
+                    None => continue,
+                };
+
+                let name: String = token.value.to_string();
+                let quoted: bool = token.token_type == TokenType::TokenString;
+                let span: (usize, usize) = (key.start, get_token_end(token));
+
+                let path: String = format_path(&key.path);
+
+                match defined.get(&path) {
+                    Some(line) => {
+                        let message: String =
+                            format!("`{}` is already defined on line {}.", path, line);
+
+                        self.report("duplicate-key", span, message, None);
+                    }
+                    None => {
+                        let line: usize = get_position(self.source, key.start).line;
+                        defined.insert(path, line);
+                    }
+                }
+
+                if quoted && Token::is_valid_id(&name) && !name.contains(['.', '#']) {
+                    let fix: Fix = self.create_fix(
+                        format!("Write `{}` without quotes.", name),
+                        span,
+                        &name,
+                        true,
+                    );
+
+                    self.report(
+                        "quoted-key",
+                        span,
+                        format!("`\"{}\"` doesn't need quotes.", name),
+                        Some(fix),
+                    );
+                }
+
+                if is_mixed_case(&name) {
+                    let snake: String = convert_to_snake_case(&name);
+                    let replacement: String = format_path(&[PathSegment::Key(snake.clone())]);
+
+                    let fix: Option<Fix> =
+                        if renamed[&format_path(&get_renamed_path(&key.path))] > 1 {
+                            None
+                        } else {
+                            Some(self.create_fix(
+                                format!("Rename to `{}`.", snake),
+                                span,
+                                &replacement,
+                                false,
+                            ))
+                        };
+
+                    self.report(
+                        "key-case",
+                        span,
+                        format!("`{}` mixes upper and lower case.", name),
+                        fix,
+                    );
+                }
+
+                // Keys right under a table at the maximum depth, deeper tables are
+                // reported at their header
+                let depth: usize = count_keys(&key.path);
+
+                if depth > self.options.max_depth && depth - 1 == self.options.max_depth {
+                    self.report(
+                        "max-depth",
+                        span,
+                        format!(
+                            "`{}` is {} keys deep, more than {}.",
+                            format_path(&key.path),
+                            depth,
+                            self.options.max_depth
+                        ),
+                        None,
+                    );
+                }
+
+                self.lint_value(tokens, key.value_start, key.value_end);
+            }
+        }
+    }
+
+    fn lint_value(&mut self, tokens: &[Token], start: usize, end: usize) {
+        let tokens: Vec<&Token> = tokens
+            .iter()
+            .filter(|t| t.position.offset >= start && t.position.offset < end)
+            .collect();
+
+        // A single string
+        if let [token] = tokens.as_slice() {
+            if token.token_type != TokenType::TokenString {
+                return;
+            }
+
+            let value: &str = token.value;
+
+            let kind: &str = if value == "true" || value == "false" {
+                "a boolean"
+            } else if value.parse::<i32>().is_ok() {
+                "an integer"
+            } else if is_datetime(value) {
+                "a date"
+            } else {
+                return;
+            };
+
+            let fix: Fix = self.create_fix(
+                format!("Write `{}` without quotes.", value),
+                (start, end),
+                value,
+                false,
+            );
+
+            self.report(
+                "string-as-value",
+                (start, end),
+                format!("`\"{}\"` is a string, not {}.", value, kind),
+                Some(fix),
+            );
+
+            return;
+        }
+
+        // Arrays of arrays aren't checked
+        let nested: usize = tokens
+            .iter()
+            .filter(|t| t.token_type == TokenType::TokenLBracket)
+            .count();
+
+        if nested != 1 {
+            return;
+        }
+
+        let elements: Vec<&Token> = tokens
+            .into_iter()
+            .filter(|t| matches!(t.token_type, TokenType::TokenID | TokenType::TokenString))
+            .collect();
+
+        for (i, element) in elements.iter().enumerate().skip(1) {
+            let first: Option<&&Token> = elements[..i]
+                .iter()
+                .find(|e| e.token_type == element.token_type && e.value == element.value);
+
+            let first: &Token = match first {
+                Some(first) => first,
+                None => continue,
+            };
+
+            let span: (usize, usize) = (element.position.offset, get_token_end(element));
+            let written: &str = &self.source[span.0..span.1];
+
+            // From the end of the element before, so the comma goes too
+            let removed: (usize, usize) = (get_token_end(elements[i - 1]), span.1);
+
+            let fix: Option<Fix> = if self.source[removed.0..removed.1].contains('#') {
+                None
+            } else {
+                Some(self.create_fix(
+                    format!("Remove the second `{}`.", written),
+                    removed,
+                    "",
+                    false,
+                ))
+            };
+
+            self.report(
+                "duplicate-value",
+                span,
+                format!(
+                    "`{}` is already in the array at column {}.",
+                    written, first.position.column
+                ),
+                fix,
+            );
+        }
+    }
+
+    fn lint_sections(&mut self) {
+        let sections: Vec<SectionLayout> = self.layout.sections.clone();
+        let headers: Vec<&SectionLayout> = sections.iter().filter(|s| s.header.is_some()).collect();
+
+        for (i, section) in sections.iter().enumerate() {
+            let (start, end) = match section.header {
+                Some(header) => header,
+                None => {
+                    self.lint_global_section(&sections, i);
+                    continue;
+                }
+            };
+
+            let header: &str = self.get_header_text(section);
+            let is_array: bool = header.starts_with("[[");
+
+            let has_children: bool = sections.iter().any(|s| {
+                s.header.is_some()
+                    && s.path.len() > section.path.len()
+                    && s.path.starts_with(&section.path)
+            });
+
+            if !is_array && section.keys.is_empty() && !has_children {
+                let mut line: (usize, usize) = get_line_range(self.source, start);
+
+                // Between empty lines one of them goes too
+                if self.source[line.1..].starts_with('\n')
+                    && (line.0 == 0 || self.source[..line.0].ends_with("\n\n"))
+                {
+                    line.1 += 1;
+                }
+
+                // At the end, the empty line before it
+                if line.1 == self.source.len() && self.source[..line.0].ends_with("\n\n") {
+                    line.0 -= 1;
+                }
+
+                let fix: Fix = self.create_fix(format!("Remove `{}`.", header), line, "", false);
+
+                self.report(
+                    "empty-table",
+                    (start, end),
+                    format!("`{}` is empty.", header),
+                    Some(fix),
+                );
+            }
+
+            if let Some(PathSegment::Key(name)) = section
+                .path
+                .iter()
+                .rev()
+                .find(|s| matches!(s, PathSegment::Key(_)))
+            {
+                if is_mixed_case(name) {
+                    self.report(
+                        "key-case",
+                        (start, end),
+                        format!("`{}` mixes upper and lower case.", name),
+                        None,
+                    );
+                }
+            }
+
+            let depth: usize = count_keys(&section.path);
+
+            if depth > self.options.max_depth {
+                self.report(
+                    "max-depth",
+                    (start, end),
+                    format!(
+                        "`{}` is {} keys deep, more than {}.",
+                        header, depth, self.options.max_depth
+                    ),
+                    None,
+                );
+            }
+        }
+
+        // `[a]`, `[b]` and then `[a.c]`
+        for (i, section) in headers.iter().enumerate().skip(1) {
+            let root: Option<&PathSegment> = get_root(&section.path);
+
+            if root == get_root(&headers[i - 1].path) {
+                continue;
+            }
+
+            if headers[..i - 1].iter().any(|h| get_root(&h.path) == root) {
+                let header: &str = self.get_header_text(section);
+                let between: &str = self.get_header_text(headers[i - 1]);
+
+                self.report(
+                    "table-order",
+                    section.header.unwrap(),
+                    format!(
+                        "`{}` is split from the tables before it by `{}`.",
+                        header, between
+                    ),
+                    None,
+                );
+            }
+        }
+    }
+
+    // Keys after an empty line that ended a table
+    fn lint_global_section(&mut self, sections: &[SectionLayout], index: usize) {
+        let key: (usize, usize, String) = match sections[index].keys.first() {
+            Some(key) => (key.start, key.value_end, format_path(&key.path)),
+            None => return,
+        };
+
+        let table: Option<&SectionLayout> =
+            sections[..index].iter().rev().find(|s| s.header.is_some());
+
+        if let Some(table) = table {
+            let header: &str = self.get_header_text(table);
+
+            self.report(
+                "global-key-after-table",
+                (key.0, key.1),
+                format!(
+                    "`{}` is global, the empty line before it ends `{}`.",
+                    key.2, header
+                ),
+                None,
+            );
+        }
+    }
+}
+
+pub fn lint_document(source: &str, options: &LintOptions) -> Result<Vec<Diagnostic>, ParseError> {
+    parse_document(source)?;
+
+    let mut lexer: Lexer = Lexer::from_string(source);
+    let mut tokens: Vec<Token> = vec![];
+
+    while let Some(token) = lexer.get_next_token() {
+        tokens.push(token);
+    }
+
+    let mut linter: Linter = Linter {
+        source,
+        options,
+        layout: get_document_layout(source),
+        diagnostics: vec![],
+    };
+
+    linter.lint_keys(&tokens);
+    linter.lint_sections();
+
+    let suppressions: Suppressions = Suppressions::from_source(source);
+
+    let mut diagnostics: Vec<Diagnostic> = linter
+        .diagnostics
+        .into_iter()
+        .filter(|d| !suppressions.is_suppressed(d))
+        .collect();
+
+    diagnostics.sort_by_key(|d| d.start.offset);
+
+    return Ok(diagnostics);
+}
+
+// Applies the fixes that don't overlap, from the end so offsets stay valid. The others are
+// only applied with `unsafe_fixes`, and the result has to parse.
+pub fn apply_fixes(
+    source: &str,
+    diagnostics: &[Diagnostic],
+    unsafe_fixes: bool,
+) -> Result<String, ParseError> {
+    let mut fixes: Vec<&Fix> = diagnostics
+        .iter()
+        .filter_map(|d| d.fix.as_ref())
+        .filter(|f| f.safe || unsafe_fixes)
+        .collect();
+    fixes.sort_by_key(|f| std::cmp::Reverse(f.start.offset));
+
+    let mut fixed: String = String::from(source);
+    let mut limit: usize = source.len();
+
+    for fix in fixes {
+        if fix.end.offset > limit {
+            continue;
+        }
+
+        fixed.replace_range(fix.start.offset..fix.end.offset, fix.replacement.as_str());
+        limit = fix.start.offset;
+    }
+
+    parse_document(fixed.as_str())?;
+
+    return Ok(fixed);
+}
+
+// `file:line:column: warning[rule] message` and the fix under it
+pub fn convert_diagnostics_to_text(file: &str, diagnostics: &[Diagnostic]) -> String {
+    let mut text: String = String::new();
+
+    for d in diagnostics {
+        text += format!(
+            "{}:{}:{}: {}[{}] {}\n",
+            file,
+            d.start.line,
+            d.start.column,
+            d.severity.get_name(),
+            d.rule,
+            d.message
+        )
+        .as_str();
+
+        // Unsafe fixes need `--unsafe-fixes`
+        match &d.fix {
+            Some(fix) if fix.safe => text += format!("  fix: {}\n", fix.message).as_str(),
+            Some(fix) => text += format!("  suggestion: {}\n", fix.message).as_str(),
+            None => {}
+        }
+    }
+
+    return text;
+}
+
+fn convert_position_to_json(object: &mut JSONValue, prefix: &str, position: &Position) {
+    object.insert(
+        format!("{}line", prefix).as_str(),
+        JSONValue::Int(position.line as i64),
+    );
+    object.insert(
+        format!("{}column", prefix).as_str(),
+        JSONValue::Int(position.column as i64),
+    );
+}
+
+pub fn convert_diagnostics_to_json(file: &str, diagnostics: &[Diagnostic]) -> JSONValue {
+    return JSONValue::Array(
+        diagnostics
+            .iter()
+            .map(|d| {
+                let mut object: JSONValue = JSONValue::Object(vec![
+                    (String::from("file"), JSONValue::String(String::from(file))),
+                    (
+                        String::from("rule"),
+                        JSONValue::String(String::from(d.rule)),
+                    ),
+                    (
+                        String::from("severity"),
+                        JSONValue::String(String::from(d.severity.get_name())),
+                    ),
+                    (
+                        String::from("message"),
+                        JSONValue::String(d.message.clone()),
+                    ),
+                ]);
+
+                convert_position_to_json(&mut object, "", &d.start);
+                convert_position_to_json(&mut object, "end_", &d.end);
+
+                if let Some(fix) = &d.fix {
+                    object.insert(
+                        "fix",
+                        JSONValue::Object(vec![
+                            (
+                                String::from("message"),
+                                JSONValue::String(fix.message.clone()),
+                            ),
+                            (
+                                String::from("replacement"),
+                                JSONValue::String(fix.replacement.clone()),
+                            ),
+                            (String::from("safe"), JSONValue::Bool(fix.safe)),
+                        ]),
+                    );
+                }
+
+                object
+            })
+            .collect(),
+    );
+}
+
+fn create_text(text: &str) -> JSONValue {
+    return JSONValue::Object(vec![(
+        String::from("text"),
+        JSONValue::String(String::from(text)),
+    )]);
+}
+
+fn create_region(start: &Position, end: &Position) -> JSONValue {
+    return JSONValue::Object(vec![
+        (String::from("startLine"), JSONValue::Int(start.line as i64)),
+        (
+            String::from("startColumn"),
+            JSONValue::Int(start.column as i64),
+        ),
+        (String::from("endLine"), JSONValue::Int(end.line as i64)),
+        (String::from("endColumn"), JSONValue::Int(end.column as i64)),
+    ]);
+}
+
+fn create_artifact(file: &str) -> JSONValue {
+    return JSONValue::Object(vec![(
+        String::from("uri"),
+        JSONValue::String(String::from(file)),
+    )]);
+}
+
+// SARIF 2.1.0, one run for all the files with the rules in the driver
+pub fn convert_diagnostics_to_sarif(files: &[(String, Vec<Diagnostic>)]) -> JSONValue {
+    let rules: Vec<JSONValue> = RULES
+        .iter()
+        .map(|rule| {
+            JSONValue::Object(vec![
+                (String::from("id"), JSONValue::String(String::from(rule.id))),
+                (
+                    String::from("shortDescription"),
+                    create_text(rule.description),
+                ),
+                (
+                    String::from("defaultConfiguration"),
+                    JSONValue::Object(vec![(
+                        String::from("level"),
+                        JSONValue::String(String::from(rule.severity.get_name())),
+                    )]),
+                ),
+            ])
+        })
+        .collect();
+
+    let mut results: Vec<JSONValue> = vec![];
+
+    for (file, diagnostics) in files {
+        for d in diagnostics {
+            let index: usize = RULES.iter().position(|rule| rule.id == d.rule).unwrap_or(0);
+
+            let mut result: JSONValue = JSONValue::Object(vec![
+                (
+                    String::from("ruleId"),
+                    JSONValue::String(String::from(d.rule)),
+                ),
+                (String::from("ruleIndex"), JSONValue::Int(index as i64)),
+                (
+                    String::from("level"),
+                    JSONValue::String(String::from(d.severity.get_name())),
+                ),
+                (String::from("message"), create_text(d.message.as_str())),
+                (
+                    String::from("locations"),
+                    JSONValue::Array(vec![JSONValue::Object(vec![(
+                        String::from("physicalLocation"),
+                        JSONValue::Object(vec![
+                            (String::from("artifactLocation"), create_artifact(file)),
+                            (String::from("region"), create_region(&d.start, &d.end)),
+                        ]),
+                    )])]),
+                ),
+            ]);
+
+            if let Some(fix) = &d.fix {
+                let replacement: JSONValue = JSONValue::Object(vec![
+                    (
+                        String::from("deletedRegion"),
+                        create_region(&fix.start, &fix.end),
+                    ),
+                    (
+                        String::from("insertedContent"),
+                        create_text(fix.replacement.as_str()),
+                    ),
+                ]);
+
+                result.insert(
+                    "fixes",
+                    JSONValue::Array(vec![JSONValue::Object(vec![
+                        (
+                            String::from("description"),
+                            create_text(fix.message.as_str()),
+                        ),
+                        (
+                            String::from("artifactChanges"),
+                            JSONValue::Array(vec![JSONValue::Object(vec![
+                                (String::from("artifactLocation"), create_artifact(file)),
+                                (
+                                    String::from("replacements"),
+                                    JSONValue::Array(vec![replacement]),
+                                ),
+                            ])]),
+                        ),
+                    ])]),
+                );
+            }
+
+            results.push(result);
+        }
+    }
+
+    return JSONValue::Object(vec![
+        (
+            String::from("$schema"),
+            JSONValue::String(String::from(
+                "https://json.schemastore.org/sarif-2.1.0.json",
+            )),
+        ),
+        (
+            String::from("version"),
+            JSONValue::String(String::from("2.1.0")),
+        ),
+        (
+            String::from("runs"),
+            JSONValue::Array(vec![JSONValue::Object(vec![
+                (
+                    String::from("tool"),
+                    JSONValue::Object(vec![(
+                        String::from("driver"),
+                        JSONValue::Object(vec![
+                            (
+                                String::from("name"),
+                                JSONValue::String(String::from("toml")),
+                            ),
+                            (String::from("rules"), JSONValue::Array(rules)),
+                        ]),
+                    )]),
+                ),
+                (String::from("results"), JSONValue::Array(results)),
+            ])]),
+        ),
+    ]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lint(source: &str) -> Vec<(&'static str, usize)> {
+        return lint_document(source, &LintOptions::new())
+            .unwrap()
+            .iter()
+            .map(|d| (d.rule, d.start.line))
+            .collect();
+    }
+
+    fn fix(source: &str, unsafe_fixes: bool) -> String {
+        let diagnostics: Vec<Diagnostic> = lint_document(source, &LintOptions::new()).unwrap();
+
+        return apply_fixes(source, &diagnostics, unsafe_fixes).unwrap();
+    }
+
+    #[test]
+    fn rules() {
+        assert_eq!(
+            lint("[t]\na = 1\n\nb = 2\n"),
+            vec![("global-key-after-table", 4)]
+        );
+        assert_eq!(
+            lint("[a]\nx = 1\n[b]\ny = 1\n[a.c]\nz = 1\n"),
+            vec![("table-order", 5)]
+        );
+        assert_eq!(lint("[t]\n"), vec![("empty-table", 1)]);
+        assert_eq!(
+            lint("a = \"true\"\nb = \"12\"\nc = \"x\"\n"),
+            vec![("string-as-value", 1), ("string-as-value", 2)]
+        );
+        assert_eq!(lint("a = [1, 2, 1]\n"), vec![("duplicate-value", 1)]);
+        assert_eq!(lint("maxSize = 1\n"), vec![("key-case", 1)]);
+        assert_eq!(lint("\"a\" = 1\n"), vec![("quoted-key", 1)]);
+        assert_eq!(lint("[a.b.c.d.e]\nx = 1\n"), vec![("max-depth", 1)]);
+        // Keys right under a table at the maximum depth
+        assert_eq!(lint("[a.b.c.d]\nx = 1\n"), vec![("max-depth", 2)]);
+    }
+
+    #[test]
+    fn rule_options() {
+        let mut options: LintOptions = LintOptions::new();
+        options.rules.insert(String::from("key-case"), None);
+        options
+            .rules
+            .insert(String::from("quoted-key"), Some(Severity::Error));
+
+        let diagnostics: Vec<Diagnostic> =
+            lint_document("maxSize = 1\n\"a\" = 1\n", &options).unwrap();
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].rule, "quoted-key");
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+    }
+
+    #[test]
+    fn suppressions() {
+        assert_eq!(lint("maxSize = 1 # lint: allow(key-case)\n"), vec![]);
+        assert_eq!(lint("# lint: allow(key-case)\nmaxSize = 1\n"), vec![]);
+        assert_eq!(
+            lint("# lint: allow(key-case)\nmaxSize = 1\nminSize = 1\n"),
+            vec![("key-case", 3)]
+        );
+        assert_eq!(
+            lint("maxSize = 1 # lint: allow(quoted-key)\n"),
+            vec![("key-case", 1)]
+        );
+        assert_eq!(
+            lint("# lint: allow-file(key-case, quoted-key)\nmaxSize = 1\n\"a\" = 1\n"),
+            vec![]
+        );
+        assert_eq!(lint("# lint: allow-file(all)\nmaxSize = \"1\"\n"), vec![]);
+        // A `#` in a string isn't a comment
+        assert_eq!(
+            lint("maxSize = \"# lint: allow(key-case)\"\n"),
+            vec![("key-case", 1)]
+        );
+    }
+
+    #[test]
+    fn safe_fixes_keep_data() {
+        let source: &str = "\"a\" = 1\nmaxSize = \"2\"\nb = [1, 1]\n\n[t]\n";
+
+        assert_eq!(
+            fix(source, false),
+            "a = 1\nmaxSize = \"2\"\nb = [1, 1]\n\n[t]\n"
+        );
+        assert_eq!(fix(source, true), "a = 1\nmax_size = 2\nb = [1]\n");
+    }
+
+    #[test]
+    fn renames_dont_duplicate_keys() {
+        let source: &str = "fooBar = 1\nfoo_bar = 2\n\n[t]\nFooBaz = 1\nfooBaz = 2\nkeyCase = 3\n";
+        let diagnostics: Vec<Diagnostic> = lint_document(source, &LintOptions::new()).unwrap();
+
+        let fixed: Vec<bool> = diagnostics.iter().map(|d| d.fix.is_some()).collect();
+        assert_eq!(fixed, vec![false, false, false, true]);
+
+        assert_eq!(
+            fix(source, true),
+            "fooBar = 1\nfoo_bar = 2\n\n[t]\nFooBaz = 1\nfooBaz = 2\nkey_case = 3\n"
+        );
+    }
+
+    #[test]
+    fn fixes_must_parse() {
+        let mut diagnostics: Vec<Diagnostic> =
+            lint_document("\"a\" = 1\n", &LintOptions::new()).unwrap();
+
+        if let Some(fix) = diagnostics[0].fix.as_mut() {
+            fix.replacement = String::from("a =");
+        }
+
+        assert!(apply_fixes("\"a\" = 1\n", &diagnostics, false).is_err());
+    }
+
+    #[test]
+    fn overlapping_fixes() {
+        // Only one of the fixes of the same key is applied, the next run gets the other
+        assert_eq!(fix("\"maxSize\" = 1\n", true), "maxSize = 1\n");
+        assert_eq!(fix("maxSize = 1\n", true), "max_size = 1\n");
+    }
+}
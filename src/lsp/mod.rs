@@ -0,0 +1,1223 @@
+use crate::json::{convert_json_to_string, parse_json, JSONValue};
+use crate::lint::{lint_document, LintOptions, Severity};
+use crate::parser::converter::{convert_ast_value_to_string, get_value_type, resolve_tag_name};
+use crate::parser::format::format_document;
+use crate::parser::incremental::{IncrementalParse, TextEdit};
+use crate::parser::layout::{
+    convert_tag_name_to_path, get_document_layout, DocumentLayout, KeyLayout, SectionLayout,
+};
+use crate::parser::path::{format_path, PathSegment};
+use crate::parser::tree::{parse_document, TOMLNode, TOMLTable};
+use crate::parser::writer::convert_ast_value_to_toml;
+use crate::schema::validator::{matches_path, SchemaError};
+use crate::schema::{Schema, SchemaSegment};
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+
+// Language server over JSON-RPC. Documents are synced by edits that reparse incrementally,
+// every change publishes the diagnostics of the parser, the linter and the schema given as
+// `schema` in the initialization options. `LanguageServer::handle_message` takes one
+// message and returns what the server sends back, so it runs in-process with `LocalClient`
+// as well as over stdio with `run_server`.
+
+// Standard JSON-RPC error codes
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+// LSP symbol and completion kinds
+const SYMBOL_ARRAY: i64 = 18;
+const SYMBOL_OBJECT: i64 = 19;
+const SYMBOL_PROPERTY: i64 = 7;
+const COMPLETION_PROPERTY: i64 = 10;
+const COMPLETION_MODULE: i64 = 9;
+const COMPLETION_VALUE: i64 = 12;
+
+fn create_object(entries: Vec<(&str, JSONValue)>) -> JSONValue {
+    return JSONValue::Object(
+        entries
+            .into_iter()
+            .map(|(key, value)| (String::from(key), value))
+            .collect(),
+    );
+}
+
+fn create_string(value: &str) -> JSONValue {
+    return JSONValue::String(String::from(value));
+}
+
+fn create_error_response(id: JSONValue, code: i64, message: &str) -> JSONValue {
+    return create_object(vec![
+        ("jsonrpc", create_string("2.0")),
+        ("id", id),
+        (
+            "error",
+            create_object(vec![
+                ("code", JSONValue::Int(code)),
+                ("message", create_string(message)),
+            ]),
+        ),
+    ]);
+}
+
+fn get_value<'a>(value: &'a JSONValue, path: &[&str]) -> Option<&'a JSONValue> {
+    let mut current: &JSONValue = value;
+
+    for key in path {
+        current = current.get(key)?;
+    }
+
+    return Some(current);
+}
+
+// LSP positions count lines from 0 and characters in UTF-16 units
+fn get_lsp_position(text: &str, offset: usize) -> JSONValue {
+    let offset: usize = offset.min(text.len());
+    let line_start: usize = text[..offset].rfind('\n').map_or(0, |i| i + 1);
+
+    return create_object(vec![
+        (
+            "line",
+            JSONValue::Int(text[..offset].matches('\n').count() as i64),
+        ),
+        (
+            "character",
+            JSONValue::Int(text[line_start..offset].encode_utf16().count() as i64),
+        ),
+    ]);
+}
+
+fn get_lsp_range(text: &str, start: usize, end: usize) -> JSONValue {
+    return create_object(vec![
+        ("start", get_lsp_position(text, start)),
+        ("end", get_lsp_position(text, end)),
+    ]);
+}
+
+fn get_offset(text: &str, position: &JSONValue) -> Option<usize> {
+    let line: usize = position.get("line")?.as_i64()? as usize;
+    let character: usize = position.get("character")?.as_i64()? as usize;
+
+    let line_start: usize = match line {
+        0 => 0,
+        _ => text.match_indices('\n').nth(line - 1)?.0 + 1,
+    };
+
+    let mut units: usize = 0;
+
+    for (i, c) in text[line_start..].char_indices() {
+        if units >= character || c == '\n' {
+            return Some(line_start + i);
+        }
+
+        units += c.len_utf16();
+    }
+
+    return Some(text.len());
+}
+
+// A change with a `range` replaces it, one without is the whole new text
+fn apply_change(document: &mut IncrementalParse, change: &JSONValue) {
+    let text: &str = match change.get("text").and_then(|t| t.as_str()) {
+        Some(text) => text,
+        None => return,
+    };
+
+    let range: Option<(usize, usize)> = change.get("range").and_then(|r| {
+        let start: usize = get_offset(document.get_text(), r.get("start")?)?;
+        let end: usize = get_offset(document.get_text(), r.get("end")?)?;
+
+        return Some((start, end.max(start)));
+    });
+
+    match range {
+        Some((start, end)) => {
+            // Offsets from `get_offset` are on character boundaries
+            let _ = document.apply_edit(&TextEdit::new(start, end, text));
+        }
+        None => *document = IncrementalParse::new(text),
+    }
+}
+
+fn get_line_end(text: &str, offset: usize) -> usize {
+    let offset: usize = offset.min(text.len());
+
+    return text[offset..].find('\n').map_or(text.len(), |i| offset + i);
+}
+
+// `server` for `[server]` and `[[server]]`
+fn get_header_name<'a>(text: &'a str, section: &SectionLayout) -> &'a str {
+    return match section.header {
+        Some((start, end)) => {
+            text[start..end].trim_matches(|c: char| c == '[' || c == ']' || c.is_whitespace())
+        }
+        None => "",
+    };
+}
+
+fn create_diagnostic(
+    text: &str,
+    span: (usize, usize),
+    severity: i64,
+    code: Option<&str>,
+    source: &str,
+    message: &str,
+) -> JSONValue {
+    let mut diagnostic: JSONValue = create_object(vec![
+        ("range", get_lsp_range(text, span.0, span.1)),
+        ("severity", JSONValue::Int(severity)),
+        ("source", create_string(source)),
+        ("message", create_string(message)),
+    ]);
+
+    if let Some(code) = code {
+        diagnostic.insert("code", create_string(code));
+    }
+
+    return diagnostic;
+}
+
+pub struct LanguageServer {
+    documents: HashMap<String, IncrementalParse>,
+    schema: Option<Schema>,
+    lint_options: LintOptions,
+    shutdown: bool,
+    exited: bool,
+}
+
+impl LanguageServer {
+    pub fn new() -> Self {
+        return LanguageServer {
+            documents: HashMap::new(),
+            schema: None,
+            lint_options: LintOptions::new(),
+            shutdown: false,
+            exited: false,
+        };
+    }
+
+    pub fn set_schema(&mut self, schema: Option<Schema>) {
+        self.schema = schema;
+    }
+
+    // After `exit`
+    pub fn has_exited(&self) -> bool {
+        return self.exited;
+    }
+
+    // Exit code for `exit`, 1 without a `shutdown` before it
+    pub fn get_exit_code(&self) -> i32 {
+        return if self.shutdown { 0 } else { 1 };
+    }
+
+    pub fn get_document(&self, uri: &str) -> Option<&str> {
+        return self.documents.get(uri).map(|d| d.get_text());
+    }
+
+    // Responses and notifications to send back for one message
+    pub fn handle_message(&mut self, message: &JSONValue) -> Vec<JSONValue> {
+        let method: &str = message.get("method").and_then(|m| m.as_str()).unwrap_or("");
+        let params: JSONValue = message.get("params").cloned().unwrap_or(JSONValue::Null);
+
+        // Requests have an id, notifications don't
+        let id: JSONValue = match message.get("id") {
+            Some(id) => id.clone(),
+            None => return self.handle_notification(method, &params),
+        };
+
+        let result: Result<JSONValue, (i64, String)> = match method {
+            "initialize" => self.initialize(&params),
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(JSONValue::Null)
+            }
+            "textDocument/documentSymbol" => self.with_document(&params, Self::get_symbols),
+            "textDocument/hover" => self.with_document(&params, Self::get_hover),
+            "textDocument/definition" => self.with_document(&params, Self::get_definition),
+            "textDocument/formatting" => self.with_document(&params, Self::get_formatting),
+            "textDocument/completion" => self.with_document(&params, Self::get_completion),
+            _ => Err((METHOD_NOT_FOUND, format!("Unknown method `{}`.", method))),
+        };
+
+        let response: JSONValue = match result {
+            Ok(result) => create_object(vec![
+                ("jsonrpc", create_string("2.0")),
+                ("id", id),
+                ("result", result),
+            ]),
+            Err((code, message)) => create_error_response(id, code, message.as_str()),
+        };
+
+        return vec![response];
+    }
+
+    fn handle_notification(&mut self, method: &str, params: &JSONValue) -> Vec<JSONValue> {
+        if method == "exit" {
+            self.exited = true;
+            return vec![];
+        }
+
+        let uri: String = match get_value(params, &["textDocument", "uri"]).and_then(|u| u.as_str())
+        {
+            Some(uri) => String::from(uri),
+            None => return vec![],
+        };
+
+        match method {
+            "textDocument/didOpen" => {
+                let text: &str = get_value(params, &["textDocument", "text"])
+                    .and_then(|t| t.as_str())
+                    .unwrap_or("");
+
+                self.documents
+                    .insert(uri.clone(), IncrementalParse::new(text));
+            }
+            "textDocument/didChange" => {
+                if let (Some(document), Some(JSONValue::Array(changes))) =
+                    (self.documents.get_mut(&uri), params.get("contentChanges"))
+                {
+                    for change in changes {
+                        apply_change(document, change);
+                    }
+                }
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+            }
+            _ => return vec![],
+        }
+
+        let diagnostics: Vec<JSONValue> = match self.documents.get(&uri) {
+            Some(document) => self.get_diagnostics(document),
+            None => vec![],
+        };
+
+        return vec![create_object(vec![
+            ("jsonrpc", create_string("2.0")),
+            ("method", create_string("textDocument/publishDiagnostics")),
+            (
+                "params",
+                create_object(vec![
+                    ("uri", JSONValue::String(uri)),
+                    ("diagnostics", JSONValue::Array(diagnostics)),
+                ]),
+            ),
+        ])];
+    }
+
+    fn initialize(&mut self, params: &JSONValue) -> Result<JSONValue, (i64, String)> {
+        if let Some(path) =
+            get_value(params, &["initializationOptions", "schema"]).and_then(|s| s.as_str())
+        {
+            match Schema::from_file(path) {
+                Ok(schema) => self.schema = Some(schema),
+                Err(e) => {
+                    return Err((INVALID_PARAMS, format!("Invalid schema `{}`: {}", path, e)));
+                }
+            }
+        }
+
+        return Ok(create_object(vec![
+            (
+                "capabilities",
+                create_object(vec![
+                    ("textDocumentSync", JSONValue::Int(2)),
+                    ("documentSymbolProvider", JSONValue::Bool(true)),
+                    ("hoverProvider", JSONValue::Bool(true)),
+                    ("definitionProvider", JSONValue::Bool(true)),
+                    ("documentFormattingProvider", JSONValue::Bool(true)),
+                    (
+                        "completionProvider",
+                        create_object(vec![(
+                            "triggerCharacters",
+                            JSONValue::Array(vec![create_string("["), create_string(".")]),
+                        )]),
+                    ),
+                ]),
+            ),
+            (
+                "serverInfo",
+                create_object(vec![("name", create_string("toml-lsp"))]),
+            ),
+        ]));
+    }
+
+    fn with_document(
+        &self,
+        params: &JSONValue,
+        handler: fn(&Self, &str, &JSONValue) -> JSONValue,
+    ) -> Result<JSONValue, (i64, String)> {
+        let uri: &str = get_value(params, &["textDocument", "uri"])
+            .and_then(|u| u.as_str())
+            .ok_or((INVALID_PARAMS, String::from("Missing `textDocument.uri`.")))?;
+
+        let document: &IncrementalParse = self
+            .documents
+            .get(uri)
+            .ok_or((INVALID_PARAMS, format!("`{}` isn't open.", uri)))?;
+
+        return Ok(handler(self, document.get_text(), params));
+    }
+
+    fn get_diagnostics(&self, parse: &IncrementalParse) -> Vec<JSONValue> {
+        let text: &str = parse.get_text();
+
+        // Lint and schema only run on documents that parse, a partial one would give
+        // errors about what's missing
+        let (document, errors) = parse.get_document();
+
+        if !errors.is_empty() {
+            return errors
+                .iter()
+                .map(|e| {
+                    let mut start: usize = e.position.offset.min(text.len());
+                    let end: usize = get_line_end(text, start);
+
+                    // Errors at the end of a line cover the line
+                    if start == end {
+                        start = text[..start].rfind('\n').map_or(0, |i| i + 1);
+                    }
+
+                    return create_diagnostic(
+                        text,
+                        (start, end),
+                        1,
+                        None,
+                        "toml",
+                        e.message.as_str(),
+                    );
+                })
+                .collect();
+        }
+
+        let mut diagnostics: Vec<JSONValue> = vec![];
+
+        for d in lint_document(text, &self.lint_options).unwrap_or_default() {
+            let severity: i64 = match d.severity {
+                Severity::Error => 1,
+                Severity::Warning => 2,
+                Severity::Note => 3,
+            };
+
+            diagnostics.push(create_diagnostic(
+                text,
+                (d.start.offset, d.end.offset),
+                severity,
+                Some(d.rule),
+                "toml-lint",
+                d.message.as_str(),
+            ));
+        }
+
+        if let Some(schema) = &self.schema {
+            let errors: Vec<SchemaError> = schema.validate(&document);
+
+            for e in errors {
+                // Errors without a position are about the whole document
+                let start: usize = e.position.map_or(0, |p| p.offset);
+                let message: String = if e.path.is_empty() {
+                    e.message.clone()
+                } else {
+                    format!("`{}`: {}", e.path, e.message)
+                };
+
+                diagnostics.push(create_diagnostic(
+                    text,
+                    (start, get_line_end(text, start)),
+                    1,
+                    None,
+                    "toml-schema",
+                    message.as_str(),
+                ));
+            }
+        }
+
+        return diagnostics;
+    }
+
+    fn get_key_symbol(text: &str, document: Option<&TOMLTable>, key: &KeyLayout) -> JSONValue {
+        let name: String = match key.path.last() {
+            Some(PathSegment::Key(name)) => name.clone(),
+            _ => String::new(),
+        };
+
+        let detail: String = match document.and_then(|d| d.get_node(&key.path)) {
+            Some(TOMLNode::Value(value, _)) => String::from(
+                get_value_type(&convert_ast_value_to_string(value, true)).unwrap_or(""),
+            ),
+            _ => String::new(),
+        };
+
+        let name_end: usize = key.start
+            + text[key.start..]
+                .find(|c: char| c == '=' || c.is_whitespace())
+                .unwrap_or(0);
+
+        return create_object(vec![
+            ("name", JSONValue::String(name)),
+            ("detail", JSONValue::String(detail)),
+            ("kind", JSONValue::Int(SYMBOL_PROPERTY)),
+            ("range", get_lsp_range(text, key.start, key.value_end)),
+            ("selectionRange", get_lsp_range(text, key.start, name_end)),
+        ]);
+    }
+
+    // Tables with their keys, global keys at the top
+    fn get_symbols(&self, text: &str, _params: &JSONValue) -> JSONValue {
+        let document: Option<TOMLTable> = parse_document(text).ok();
+
+        if document.is_none() {
+            return JSONValue::Array(vec![]);
+        }
+
+        let layout: DocumentLayout = get_document_layout(text);
+        let mut symbols: Vec<JSONValue> = vec![];
+
+        for section in &layout.sections {
+            let keys: Vec<JSONValue> = section
+                .keys
+                .iter()
+                .map(|key| Self::get_key_symbol(text, document.as_ref(), key))
+                .collect();
+
+            let (start, end) = match section.header {
+                Some(header) => header,
+                None => {
+                    symbols.extend(keys);
+                    continue;
+                }
+            };
+
+            let is_array: bool = text[start..].starts_with("[[");
+
+            symbols.push(create_object(vec![
+                ("name", create_string(get_header_name(text, section))),
+                (
+                    "detail",
+                    create_string(if is_array { "array of tables" } else { "table" }),
+                ),
+                (
+                    "kind",
+                    JSONValue::Int(if is_array {
+                        SYMBOL_ARRAY
+                    } else {
+                        SYMBOL_OBJECT
+                    }),
+                ),
+                ("range", get_lsp_range(text, start, section.end.max(end))),
+                ("selectionRange", get_lsp_range(text, start, end)),
+                ("children", JSONValue::Array(keys)),
+            ]));
+        }
+
+        return JSONValue::Array(symbols);
+    }
+
+    // The type tag the converter gives the value, and the value
+    fn get_hover(&self, text: &str, params: &JSONValue) -> JSONValue {
+        let offset: usize = match params.get("position").and_then(|p| get_offset(text, p)) {
+            Some(offset) => offset,
+            None => return JSONValue::Null,
+        };
+
+        let document: TOMLTable = match parse_document(text) {
+            Ok(document) => document,
+            Err(_) => return JSONValue::Null,
+        };
+
+        let layout: DocumentLayout = get_document_layout(text);
+
+        for section in &layout.sections {
+            if let Some((start, end)) = section.header {
+                if offset >= start && offset < end {
+                    let kind: String = match document.get_node(&section.path) {
+                        Some(TOMLNode::Table(table)) => {
+                            format!("table with {} entries", table.entries.len())
+                        }
+                        _ => String::from("table"),
+                    };
+
+                    return self.create_hover(
+                        text,
+                        (start, end),
+                        format!("`{}`: {}", format_path(&section.path), kind),
+                    );
+                }
+            }
+
+            for key in &section.keys {
+                if offset < key.start || offset >= key.value_end {
+                    continue;
+                }
+
+                let value = match document.get_node(&key.path) {
+                    Some(TOMLNode::Value(value, _)) => value,
+                    _ => return JSONValue::Null,
+                };
+
+                let tag: String = convert_ast_value_to_string(value, true);
+                let kind: &str = get_value_type(&tag).unwrap_or("value");
+
+                return self.create_hover(
+                    text,
+                    (key.start, key.value_end),
+                    format!(
+                        "`{}`: {}\n\n```toml\n{}\n```",
+                        format_path(&key.path),
+                        kind,
+                        convert_ast_value_to_toml(value)
+                    ),
+                );
+            }
+        }
+
+        return JSONValue::Null;
+    }
+
+    fn create_hover(&self, text: &str, span: (usize, usize), markdown: String) -> JSONValue {
+        return create_object(vec![
+            (
+                "contents",
+                create_object(vec![
+                    ("kind", create_string("markdown")),
+                    ("value", JSONValue::String(markdown)),
+                ]),
+            ),
+            ("range", get_lsp_range(text, span.0, span.1)),
+        ]);
+    }
+
+    // Other headers of the same table, `[[name]]` elements and `[name.sub]` tables
+    fn get_definition(&self, text: &str, params: &JSONValue) -> JSONValue {
+        let offset: Option<usize> = params.get("position").and_then(|p| get_offset(text, p));
+        let uri: JSONValue = get_value(params, &["textDocument", "uri"])
+            .cloned()
+            .unwrap_or(JSONValue::Null);
+
+        let (offset, layout) = match (offset, parse_document(text)) {
+            (Some(offset), Ok(_)) => (offset, get_document_layout(text)),
+            _ => return JSONValue::Null,
+        };
+
+        let headers: Vec<&SectionLayout> = layout
+            .sections
+            .iter()
+            .filter(|s| s.header.is_some())
+            .collect();
+
+        let current: &SectionLayout = match headers.iter().find(|s| {
+            let (start, end) = s.header.unwrap();
+            offset >= start && offset < end
+        }) {
+            Some(current) => current,
+            None => return JSONValue::Null,
+        };
+
+        let name: &str = get_header_name(text, current);
+
+        let locations: Vec<JSONValue> = headers
+            .iter()
+            .filter(|s| s.header != current.header)
+            .filter(|s| {
+                let other: &str = get_header_name(text, s);
+
+                other == name
+                    || other.starts_with(format!("{}.", name).as_str())
+                    || name.starts_with(format!("{}.", other).as_str())
+            })
+            .map(|s| {
+                let (start, end) = s.header.unwrap();
+
+                create_object(vec![
+                    ("uri", uri.clone()),
+                    ("range", get_lsp_range(text, start, end)),
+                ])
+            })
+            .collect();
+
+        return JSONValue::Array(locations);
+    }
+
+    fn get_formatting(&self, text: &str, _params: &JSONValue) -> JSONValue {
+        let formatted: String = match format_document(text) {
+            Ok(formatted) if formatted != text => formatted,
+            _ => return JSONValue::Array(vec![]),
+        };
+
+        return JSONValue::Array(vec![create_object(vec![
+            ("range", get_lsp_range(text, 0, text.len())),
+            ("newText", JSONValue::String(formatted)),
+        ])]);
+    }
+
+    // Keys the schema has for the table at the cursor, tables in headers and the allowed
+    // values after `=`
+    fn get_completion(&self, text: &str, params: &JSONValue) -> JSONValue {
+        let schema: &Schema = match &self.schema {
+            Some(schema) => schema,
+            None => return JSONValue::Array(vec![]),
+        };
+
+        let offset: usize = match params.get("position").and_then(|p| get_offset(text, p)) {
+            Some(offset) => offset,
+            None => return JSONValue::Array(vec![]),
+        };
+
+        let line_start: usize = text[..offset].rfind('\n').map_or(0, |i| i + 1);
+        let line: &str = text[line_start..offset].trim_start();
+        let path: Vec<PathSegment> = get_table_at(text, line_start);
+        let document: Option<TOMLTable> = parse_document(text).ok();
+
+        let mut items: Vec<JSONValue> = vec![];
+
+        if line.starts_with('[') {
+            let mut names: Vec<String> = vec![];
+
+            for rule in &schema.rules {
+                let keys: Option<Vec<PathSegment>> = rule
+                    .path
+                    .iter()
+                    .map(|s| match s {
+                        SchemaSegment::Key(key) => Some(PathSegment::Key(key.clone())),
+                        SchemaSegment::Any => None,
+                    })
+                    .collect();
+
+                let is_table: bool = rule
+                    .value_type
+                    .as_ref()
+                    .map_or(false, |t| t.get_name() == "table");
+
+                match keys {
+                    Some(keys) if is_table && !keys.is_empty() => {
+                        let name: String = format_path(&keys);
+
+                        if !names.contains(&name) {
+                            items.push(create_object(vec![
+                                ("label", create_string(name.as_str())),
+                                ("kind", JSONValue::Int(COMPLETION_MODULE)),
+                            ]));
+
+                            names.push(name);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+
+            return JSONValue::Array(items);
+        }
+
+        let rules = schema.rules.iter().filter(|rule| {
+            rule.path.len() == path.len() + 1 && matches_path(&rule.path[..path.len()], &path)
+        });
+
+        // Values of the key before `=`
+        if let Some((key, _)) = line.split_once('=') {
+            let key: &str = key.trim().trim_matches('"');
+
+            for rule in rules {
+                if rule.path.last() != Some(&SchemaSegment::Key(String::from(key))) {
+                    continue;
+                }
+
+                for value in rule.values.iter().flatten() {
+                    items.push(create_object(vec![
+                        ("label", JSONValue::String(convert_ast_value_to_toml(value))),
+                        ("kind", JSONValue::Int(COMPLETION_VALUE)),
+                    ]));
+                }
+            }
+
+            return JSONValue::Array(items);
+        }
+
+        for rule in rules {
+            let key: &String = match rule.path.last() {
+                Some(SchemaSegment::Key(key)) => key,
+                _ => continue,
+            };
+
+            // Keys already in the table aren't offered
+            let mut key_path: Vec<PathSegment> = path.clone();
+            key_path.push(PathSegment::Key(key.clone()));
+
+            if document
+                .as_ref()
+                .map_or(false, |d| d.get_node(&key_path).is_some())
+            {
+                continue;
+            }
+
+            let mut detail: String =
+                String::from(rule.value_type.as_ref().map_or("value", |t| t.get_name()));
+
+            if rule.required {
+                detail += ", required";
+            }
+
+            let name: String = format_path(&[PathSegment::Key(key.clone())]);
+
+            items.push(create_object(vec![
+                ("label", JSONValue::String(name.clone())),
+                ("kind", JSONValue::Int(COMPLETION_PROPERTY)),
+                ("detail", JSONValue::String(detail)),
+                ("insertText", JSONValue::String(format!("{} = ", name))),
+            ]));
+        }
+
+        return JSONValue::Array(items);
+    }
+}
+
+// The table the line at `offset` belongs to, read line by line like the converter so it
+// works while the document doesn't parse
+fn get_table_at(text: &str, offset: usize) -> Vec<PathSegment> {
+    let mut tag_arrays: HashMap<String, usize> = HashMap::new();
+    let mut current: String = String::new();
+
+    for line in text[..offset].lines() {
+        let line: &str = line.trim();
+
+        if line.is_empty() {
+            current = String::new();
+        } else if let Some(name) = line.strip_prefix("[[").and_then(|l| l.split("]]").next()) {
+            let array: String = resolve_tag_name(name.trim(), &tag_arrays, false);
+            let count: &mut usize = tag_arrays.entry(array.clone()).or_insert(0);
+            current = format!("{}[{}]", array, count);
+            *count += 1;
+        } else if let Some(name) = line.strip_prefix('[').and_then(|l| l.split(']').next()) {
+            current = resolve_tag_name(name.trim(), &tag_arrays, true);
+        }
+    }
+
+    if current.is_empty() {
+        return vec![];
+    }
+
+    return convert_tag_name_to_path(current.as_str());
+}
+
+// A client in the same process, for tests and tools that drive the server directly
+pub struct LocalClient {
+    pub server: LanguageServer,
+    // Notifications the server sent, like `textDocument/publishDiagnostics`
+    pub notifications: Vec<JSONValue>,
+    next_id: i64,
+}
+
+impl LocalClient {
+    pub fn new() -> Self {
+        return LocalClient {
+            server: LanguageServer::new(),
+            notifications: vec![],
+            next_id: 1,
+        };
+    }
+
+    // The result, or the error object of the response
+    pub fn request(&mut self, method: &str, params: JSONValue) -> Result<JSONValue, JSONValue> {
+        let id: i64 = self.next_id;
+        self.next_id += 1;
+
+        let message: JSONValue = create_object(vec![
+            ("jsonrpc", create_string("2.0")),
+            ("id", JSONValue::Int(id)),
+            ("method", create_string(method)),
+            ("params", params),
+        ]);
+
+        let mut result: Result<JSONValue, JSONValue> = Err(JSONValue::Null);
+
+        for reply in self.server.handle_message(&message) {
+            if reply.get("id").and_then(|i| i.as_i64()) != Some(id) {
+                self.notifications.push(reply);
+            } else if let Some(error) = reply.get("error") {
+                result = Err(error.clone());
+            } else {
+                result = Ok(reply.get("result").cloned().unwrap_or(JSONValue::Null));
+            }
+        }
+
+        return result;
+    }
+
+    pub fn notify(&mut self, method: &str, params: JSONValue) {
+        let message: JSONValue = create_object(vec![
+            ("jsonrpc", create_string("2.0")),
+            ("method", create_string(method)),
+            ("params", params),
+        ]);
+
+        let replies: Vec<JSONValue> = self.server.handle_message(&message);
+        self.notifications.extend(replies);
+    }
+
+    pub fn open(&mut self, uri: &str, text: &str) {
+        self.notify(
+            "textDocument/didOpen",
+            create_object(vec![(
+                "textDocument",
+                create_object(vec![
+                    ("uri", create_string(uri)),
+                    ("languageId", create_string("toml")),
+                    ("version", JSONValue::Int(1)),
+                    ("text", create_string(text)),
+                ]),
+            )]),
+        );
+    }
+
+    // Diagnostics of the last `publishDiagnostics` for `uri`
+    pub fn get_diagnostics(&self, uri: &str) -> Option<&JSONValue> {
+        return self.notifications.iter().rev().find_map(|n| {
+            let params: &JSONValue = n.get("params")?;
+
+            if params.get("uri")?.as_str()? == uri {
+                params.get("diagnostics")
+            } else {
+                None
+            }
+        });
+    }
+}
+
+// `Content-Length` framed messages, `None` at the end of the input
+pub fn read_message(reader: &mut impl BufRead) -> std::io::Result<Option<JSONValue>> {
+    let mut length: Option<usize> = None;
+
+    loop {
+        let mut header: String = String::new();
+
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+
+        let header: &str = header.trim_end();
+
+        if header.is_empty() {
+            break;
+        }
+
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+
+    // Without a length the end of the content isn't known, so the next headers can't be found
+    let length: usize = length.ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "Missing `Content-Length`.",
+        )
+    })?;
+
+    let mut content: Vec<u8> = vec![0; length];
+    reader.read_exact(&mut content)?;
+
+    let content: String = String::from_utf8(content)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+
+    return parse_json(content.as_str())
+        .map(Some)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()));
+}
+
+pub fn write_message(writer: &mut impl Write, message: &JSONValue) -> std::io::Result<()> {
+    let content: String = convert_json_to_string(message, false);
+
+    write!(
+        writer,
+        "Content-Length: {}\r\n\r\n{}",
+        content.len(),
+        content
+    )?;
+
+    return writer.flush();
+}
+
+// Serves until `exit` or the end of the input and returns the exit code
+pub fn run_server(reader: &mut impl BufRead, writer: &mut impl Write) -> i32 {
+    let mut server: LanguageServer = LanguageServer::new();
+
+    loop {
+        let replies: Vec<JSONValue> = match read_message(reader) {
+            Ok(Some(message)) => server.handle_message(&message),
+            Ok(None) => return 1,
+            // The id of a message that can't be read is unknown, broken headers close the
+            // connection
+            Err(e) if e.kind() == std::io::ErrorKind::InvalidData => {
+                vec![create_error_response(
+                    JSONValue::Null,
+                    PARSE_ERROR,
+                    e.to_string().as_str(),
+                )]
+            }
+            Err(e) => {
+                eprintln!("[ERROR] Failed to read a message: {}", e);
+                return 1;
+            }
+        };
+
+        for reply in replies {
+            if let Err(e) = write_message(writer, &reply) {
+                eprintln!("[ERROR] Failed to write a message: {}", e);
+                return 1;
+            }
+        }
+
+        if server.has_exited() {
+            return server.get_exit_code();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const URI: &str = "file:///config.toml";
+
+    fn create_position(line: i64, character: i64) -> JSONValue {
+        return create_object(vec![
+            ("line", JSONValue::Int(line)),
+            ("character", JSONValue::Int(character)),
+        ]);
+    }
+
+    fn create_position_params(line: i64, character: i64) -> JSONValue {
+        return create_object(vec![
+            (
+                "textDocument",
+                create_object(vec![("uri", create_string(URI))]),
+            ),
+            ("position", create_position(line, character)),
+        ]);
+    }
+
+    fn get_messages(diagnostics: &JSONValue) -> Vec<(i64, &str)> {
+        let diagnostics: &Vec<JSONValue> = match diagnostics {
+            JSONValue::Array(diagnostics) => diagnostics,
+            _ => panic!("Diagnostics aren't an array."),
+        };
+
+        return diagnostics
+            .iter()
+            .map(|d| {
+                let line: i64 = get_value(d, &["range", "start", "line"])
+                    .and_then(|l| l.as_i64())
+                    .unwrap();
+                let message: &str = d.get("message").and_then(|m| m.as_str()).unwrap();
+
+                (line, message)
+            })
+            .collect();
+    }
+
+    fn get_labels(items: &JSONValue) -> Vec<&str> {
+        return match items {
+            JSONValue::Array(items) => items
+                .iter()
+                .map(|i| i.get("label").and_then(|l| l.as_str()).unwrap())
+                .collect(),
+            _ => panic!("Completion items aren't an array."),
+        };
+    }
+
+    fn change(client: &mut LocalClient, start: (i64, i64), end: (i64, i64), text: &str) {
+        client.notify(
+            "textDocument/didChange",
+            create_object(vec![
+                (
+                    "textDocument",
+                    create_object(vec![("uri", create_string(URI))]),
+                ),
+                (
+                    "contentChanges",
+                    JSONValue::Array(vec![create_object(vec![
+                        (
+                            "range",
+                            create_object(vec![
+                                ("start", create_position(start.0, start.1)),
+                                ("end", create_position(end.0, end.1)),
+                            ]),
+                        ),
+                        ("text", create_string(text)),
+                    ])]),
+                ),
+            ]),
+        );
+    }
+
+    #[test]
+    fn initialize() {
+        let mut client: LocalClient = LocalClient::new();
+
+        let result: JSONValue = client.request("initialize", create_object(vec![])).unwrap();
+
+        assert_eq!(
+            get_value(&result, &["capabilities", "textDocumentSync"]),
+            Some(&JSONValue::Int(2))
+        );
+        assert_eq!(
+            get_value(&result, &["capabilities", "hoverProvider"]),
+            Some(&JSONValue::Bool(true))
+        );
+
+        let error: JSONValue = client
+            .request("textDocument/unknown", create_object(vec![]))
+            .unwrap_err();
+
+        assert_eq!(error.get("code"), Some(&JSONValue::Int(METHOD_NOT_FOUND)));
+    }
+
+    #[test]
+    fn did_open_diagnostics() {
+        let mut client: LocalClient = LocalClient::new();
+
+        client.open(URI, "a = 1\nb = [1, \"x\"]\n[t\n");
+
+        assert_eq!(
+            get_messages(client.get_diagnostics(URI).unwrap()),
+            vec![
+                (
+                    1,
+                    "Wrong type for array element, expected `array_int` found `array_string`."
+                ),
+                (2, "Unexpected token TokenNewLine, with value '\\n'."),
+            ]
+        );
+
+        client.open(URI, "a = 1\n");
+
+        assert!(get_messages(client.get_diagnostics(URI).unwrap()).is_empty());
+    }
+
+    #[test]
+    fn did_change_incremental() {
+        let mut client: LocalClient = LocalClient::new();
+
+        client.open(URI, "a = 1\n\n[t]\nb = 2\n");
+
+        change(&mut client, (3, 4), (3, 5), "[1,");
+
+        assert_eq!(
+            client.server.documents[URI].get_text(),
+            "a = 1\n\n[t]\nb = [1,\n"
+        );
+        assert_eq!(
+            get_messages(client.get_diagnostics(URI).unwrap()),
+            vec![(4, "Unexpected end of input.")]
+        );
+
+        change(&mut client, (3, 7), (3, 7), " 2]");
+        change(&mut client, (0, 0), (0, 0), "c = \"é\"\n");
+
+        assert_eq!(
+            client.server.documents[URI].get_text(),
+            "c = \"é\"\na = 1\n\n[t]\nb = [1, 2]\n"
+        );
+        assert!(get_messages(client.get_diagnostics(URI).unwrap()).is_empty());
+    }
+
+    #[test]
+    fn hover() {
+        let mut client: LocalClient = LocalClient::new();
+
+        client.open(URI, "[server]\nport = 80\nname = \"x\"\n");
+
+        let key: JSONValue = client
+            .request("textDocument/hover", create_position_params(1, 1))
+            .unwrap();
+
+        assert_eq!(
+            get_value(&key, &["contents", "value"]).and_then(|v| v.as_str()),
+            Some("`server.port`: int\n\n```toml\n80\n```")
+        );
+
+        let header: JSONValue = client
+            .request("textDocument/hover", create_position_params(0, 2))
+            .unwrap();
+
+        assert_eq!(
+            get_value(&header, &["contents", "value"]).and_then(|v| v.as_str()),
+            Some("`server`: table with 2 entries")
+        );
+    }
+
+    #[test]
+    fn completion() {
+        let schema: std::path::PathBuf =
+            std::env::temp_dir().join(format!("toml-lsp-test-{}.toml", std::process::id()));
+
+        std::fs::write(
+            &schema,
+            "[server]\ntype = \"table\"\n\n[server.port]\ntype = \"int\"\n\n\
+             [server.mode]\ntype = \"string\"\nenum = [\"dev\", \"prod\"]\n",
+        )
+        .unwrap();
+
+        let mut client: LocalClient = LocalClient::new();
+
+        let result = client.request(
+            "initialize",
+            create_object(vec![(
+                "initializationOptions",
+                create_object(vec![("schema", create_string(schema.to_str().unwrap()))]),
+            )]),
+        );
+
+        std::fs::remove_file(&schema).unwrap();
+        result.unwrap();
+
+        client.open(URI, "[server]\n\n");
+
+        let keys: JSONValue = client
+            .request("textDocument/completion", create_position_params(1, 0))
+            .unwrap();
+
+        assert_eq!(get_labels(&keys), vec!["mode", "port"]);
+
+        client.open(URI, "[server]\nport = 80\nmode = \"dev\"\n");
+
+        let values: JSONValue = client
+            .request("textDocument/completion", create_position_params(2, 7))
+            .unwrap();
+
+        assert_eq!(get_labels(&values), vec!["\"dev\"", "\"prod\""]);
+    }
+
+    #[test]
+    fn parse_error() {
+        let mut input: Vec<u8> = vec![];
+
+        for content in ["{not json", "{\"jsonrpc\": \"2.0\", \"method\": \"exit\"}"] {
+            input.extend(format!("Content-Length: {}\r\n\r\n{}", content.len(), content).bytes());
+        }
+
+        let mut output: Vec<u8> = vec![];
+
+        assert_eq!(run_server(&mut input.as_slice(), &mut output), 1);
+
+        let reply: JSONValue = read_message(&mut output.as_slice()).unwrap().unwrap();
+
+        assert_eq!(reply.get("id"), Some(&JSONValue::Null));
+        assert_eq!(
+            get_value(&reply, &["error", "code"]),
+            Some(&JSONValue::Int(PARSE_ERROR))
+        );
+    }
+
+    #[test]
+    fn missing_content_length() {
+        // The content would be read as headers
+        let content: &str = "{\"jsonrpc\": \"2.0\", \"method\": \"exit\"}";
+        let input: String = format!("Content-Type: json\r\n\r\n{}\r\n\r\n", content);
+        let mut output: Vec<u8> = vec![];
+
+        assert_eq!(run_server(&mut input.as_bytes(), &mut output), 1);
+        assert!(output.is_empty());
+    }
+}
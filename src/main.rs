@@ -1,60 +1,1619 @@
 extern crate core;
 
-use crate::parser::ast::AST;
-use crate::parser::converter::convert_ast_to_string;
-use crate::parser::parser::Parser;
-use crate::parser::token::Token;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::path::PathBuf;
+use std::process;
+use toml_parser::diff::{
+    convert_diff_to_json, convert_diff_to_string, convert_diff_to_text, diff_documents, DiffEntry,
+};
+use toml_parser::env::{
+    convert_env_to_dotenv, convert_tree_to_env, overlay_env, parse_dotenv, EnvCase, EnvOptions,
+};
+use toml_parser::json::convert::{
+    convert_json_to_tree, convert_subtree_to_json, convert_tree_to_json,
+};
+use toml_parser::json::{convert_ast_to_json, convert_json_to_string, parse_json, JSONValue};
+use toml_parser::lint::{
+    apply_fixes, convert_diagnostics_to_json, convert_diagnostics_to_sarif,
+    convert_diagnostics_to_text, get_rule, lint_document, Diagnostic, LintOptions, Severity,
+};
+use toml_parser::merge::{MergeOptions, MergeStrategy, MergedDocument};
+use toml_parser::parser::ast::AST;
+use toml_parser::parser::converter::{try_convert_ast_to_string, TOMLStringMap};
+use toml_parser::parser::error::ParseError;
+use toml_parser::parser::format::format_document;
+use toml_parser::parser::include::{parse_file_with_includes, IncludeOptions};
+use toml_parser::parser::interpolate::{interpolate_document, InterpolationOptions};
+use toml_parser::parser::lexer::Lexer;
+use toml_parser::parser::parser::{parse_document_recovering, Parser};
+use toml_parser::parser::path::{format_path, parse_path, PathSegment};
+use toml_parser::parser::tree::{parse_document, parse_string_map, TOMLNode, TOMLTable, TOMLTree};
+use toml_parser::parser::writer::{
+    convert_ast_value_to_toml, convert_tree_to_annotated_toml, convert_tree_to_toml,
+};
+use toml_parser::patch::{apply_patch, parse_json_patch, parse_toml_patch, PatchOperation};
+use toml_parser::schema::infer::SchemaInference;
+use toml_parser::schema::validator::SchemaError;
+use toml_parser::schema::Schema;
+#[cfg(feature = "yaml")]
+use toml_parser::yaml::{convert_tree_to_yaml, convert_yaml_to_tree};
 
-mod parser;
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Output {
+    Text,
+    JSON,
+}
 
-fn main() {
-    let mut lexer = parser::lexer::Lexer::new("product/test.toml");
-
-    loop {
-        let token = lexer.get_next_token();
-        match token {
-            None => {
-                break;
-            }
-            Some(t) => {
-                println!(
-                    "Token {{ {:?}, '{}' }}",
-                    t.token_type,
-                    if t.value == "\n" {
-                        "\\n"
-                    } else {
-                        t.value.as_str()
+// `-` is stdin
+fn read_input(filepath: &str) -> std::io::Result<String> {
+    if filepath == "-" {
+        let mut content: String = String::new();
+        std::io::stdin().read_to_string(&mut content)?;
+        return Ok(content);
+    }
+
+    return fs::read_to_string(filepath);
+}
+
+fn get_input_name(filepath: &str) -> &str {
+    return if filepath == "-" { "<stdin>" } else { filepath };
+}
+
+fn print_json(value: &JSONValue) {
+    println!("{}", convert_json_to_string(value, true));
+}
+
+// Errors go to stderr in both output modes, stdout only has results
+fn eprint_json(value: &JSONValue) {
+    eprintln!("{}", convert_json_to_string(value, true));
+}
+
+fn print_document(output: Output, document: &TOMLTable) {
+    match output {
+        Output::Text => print!("{}", convert_tree_to_toml(document, true)),
+        Output::JSON => print_json(&convert_tree_to_json(document, false)),
+    }
+}
+
+// `{"error": {"message": ...}}` for JSON output
+fn report_error(output: Output, message: &str, code: i32) -> i32 {
+    match output {
+        Output::Text => eprintln!("[ERROR] {}", message),
+        Output::JSON => eprint_json(&JSONValue::Object(vec![(
+            String::from("error"),
+            JSONValue::Object(vec![(
+                String::from("message"),
+                JSONValue::String(String::from(message)),
+            )]),
+        )])),
+    }
+
+    return code;
+}
+
+fn report_usage_error(output: Output) -> i32 {
+    return report_error(output, "Invalid arguments.", 2);
+}
+
+// Every syntax error of a document that doesn't parse, the JSON keeps `message` as the
+// first one like `report_error`
+fn report_parse_errors(output: Output, filepath: &str, content: &str) -> i32 {
+    let (_, errors) = parse_document_recovering(content);
+
+    match output {
+        Output::Text => {
+            for e in &errors {
+                eprintln!("[ERROR] {}: {}", get_input_name(filepath), e);
+            }
+        }
+        Output::JSON => eprint_json(&JSONValue::Object(vec![(
+            String::from("error"),
+            JSONValue::Object(vec![
+                (
+                    String::from("message"),
+                    errors.first().map_or(JSONValue::Null, |e| {
+                        JSONValue::String(format!("{}: {}", get_input_name(filepath), e))
+                    }),
+                ),
+                (
+                    String::from("errors"),
+                    JSONValue::Array(
+                        errors
+                            .iter()
+                            .map(|e| convert_parse_error_to_json(filepath, e))
+                            .collect(),
+                    ),
+                ),
+            ]),
+        )])),
+    }
+
+    return 1;
+}
+
+// Errors with where they happened, for `check`
+fn convert_parse_error_to_json(filepath: &str, error: &ParseError) -> JSONValue {
+    return JSONValue::Object(vec![
+        (
+            String::from("file"),
+            JSONValue::String(String::from(get_input_name(filepath))),
+        ),
+        (
+            String::from("message"),
+            JSONValue::String(error.message.clone()),
+        ),
+        (
+            String::from("line"),
+            JSONValue::Int(error.position.line as i64),
+        ),
+        (
+            String::from("column"),
+            JSONValue::Int(error.position.column as i64),
+        ),
+    ]);
+}
+
+fn validate(filepath: &str, schema_path: &str, output: Output) -> i32 {
+    let schema: Schema = match Schema::from_file(schema_path) {
+        Ok(schema) => schema,
+        Err(e) => {
+            return report_error(
+                output,
+                format!("Invalid schema `{}`: {}", schema_path, e).as_str(),
+                2,
+            );
+        }
+    };
+
+    let content: String = match read_input(filepath) {
+        Ok(content) => content,
+        Err(e) => {
+            return report_error(
+                output,
+                format!("Failed to read `{}`: {}", filepath, e).as_str(),
+                2,
+            );
+        }
+    };
+
+    // Parse
+    let document: TOMLTable = match parse_document(content.as_str()) {
+        Ok(document) => document,
+        Err(e) => {
+            return report_error(output, format!("{}: {}", filepath, e).as_str(), 2);
+        }
+    };
+
+    let errors: Vec<SchemaError> = schema.validate(&document);
+
+    match output {
+        Output::Text if errors.is_empty() => println!("[INFO] {} is valid.", filepath),
+        Output::Text => {
+            for e in &errors {
+                eprintln!("[ERROR] {}: {}", filepath, e);
+            }
+        }
+        Output::JSON => print_json(&JSONValue::Object(vec![
+            (
+                String::from("file"),
+                JSONValue::String(String::from(get_input_name(filepath))),
+            ),
+            (String::from("valid"), JSONValue::Bool(errors.is_empty())),
+            (
+                String::from("errors"),
+                JSONValue::Array(
+                    errors
+                        .iter()
+                        .map(|e| JSONValue::String(e.to_string()))
+                        .collect(),
+                ),
+            ),
+        ])),
+    }
+
+    return if errors.is_empty() { 0 } else { 1 };
+}
+
+fn convert_schema(filepath: &str, to_json: bool, output: Output) -> i32 {
+    let content: String = match read_input(filepath) {
+        Ok(content) => content,
+        Err(e) => {
+            return report_error(
+                output,
+                format!("Failed to read `{}`: {}", filepath, e).as_str(),
+                2,
+            );
+        }
+    };
+
+    let result: Result<String, String> = if to_json {
+        Schema::from_string(content.as_str())
+            .map(|schema| convert_json_to_string(&schema.to_json_schema(), true))
+            .map_err(|e| e.to_string())
+    } else {
+        parse_json(content.as_str())
+            .map_err(|e| e.to_string())
+            .and_then(|json| Schema::from_json_schema(&json))
+            .and_then(|schema| schema.to_toml_string())
+    };
+
+    return match result {
+        Ok(text) => {
+            println!("{}", text);
+            0
+        }
+        Err(e) => report_error(output, format!("{}: {}", filepath, e).as_str(), 1),
+    };
+}
+
+fn infer(filepaths: &[String], structs: bool, output: Output) -> i32 {
+    let mut inference: SchemaInference = SchemaInference::new();
+
+    for filepath in filepaths {
+        let content: String = match read_input(filepath) {
+            Ok(content) => content,
+            Err(e) => {
+                return report_error(
+                    output,
+                    format!("Failed to read `{}`: {}", filepath, e).as_str(),
+                    2,
+                );
+            }
+        };
+
+        match parse_string_map(content.as_str()) {
+            Ok(map) => inference.add_document(&map),
+            Err(e) => {
+                return report_error(output, format!("{}: {}", filepath, e).as_str(), 2);
+            }
+        }
+    }
+
+    if structs {
+        print!("{}", inference.to_rust_structs("Config"));
+        return 0;
+    }
+
+    if output == Output::JSON {
+        print_json(&inference.to_schema().to_json_schema());
+        return 0;
+    }
+
+    return match inference.to_schema().to_toml_string() {
+        Ok(text) => {
+            print!("{}", text);
+            0
+        }
+        Err(e) => report_error(output, e.to_string().as_str(), 2),
+    };
+}
+
+fn convert_document(args: &[String], to_json: bool, output: Output) -> i32 {
+    let (flags, filepath) = match parse_input_flags(args, &[]) {
+        Some(parsed) => parsed,
+        None => return report_usage_error(output),
+    };
+
+    let content: String = match read_input(filepath.as_str()) {
+        Ok(content) => content,
+        Err(e) => {
+            return report_error(
+                output,
+                format!("Failed to read `{}`: {}", get_input_name(&filepath), e).as_str(),
+                2,
+            );
+        }
+    };
+
+    let typed: bool = flags.contains_key("--typed");
+
+    let result: Result<String, String> = if to_json {
+        parse_document(content.as_str())
+            .map(|document| convert_json_to_string(&convert_tree_to_json(&document, typed), true))
+            .map_err(|e| e.to_string())
+    } else {
+        parse_json(content.as_str())
+            .map_err(|e| e.to_string())
+            .and_then(|json| convert_json_to_tree(&json, typed).map_err(|e| e.to_string()))
+            .map(|document| convert_tree_to_toml(&document, true))
+    };
+
+    return match result {
+        Ok(text) => {
+            println!("{}", text.trim_end());
+            0
+        }
+        Err(e) => report_error(
+            output,
+            format!("{}: {}", get_input_name(&filepath), e).as_str(),
+            1,
+        ),
+    };
+}
+
+#[cfg(feature = "yaml")]
+fn convert_yaml(filepath: &str, to_yaml: bool, output: Output) -> i32 {
+    let content: String = match read_input(filepath) {
+        Ok(content) => content,
+        Err(e) => {
+            return report_error(
+                output,
+                format!("Failed to read `{}`: {}", filepath, e).as_str(),
+                2,
+            );
+        }
+    };
+
+    let result: Result<String, ParseError> = if to_yaml {
+        parse_document(content.as_str()).map(|document| convert_tree_to_yaml(&document))
+    } else {
+        convert_yaml_to_tree(content.as_str()).map(|document| convert_tree_to_toml(&document, true))
+    };
+
+    return match result {
+        Ok(text) => {
+            println!("{}", text.trim_end());
+            0
+        }
+        Err(e) => report_error(output, format!("{}: {}", filepath, e).as_str(), 1),
+    };
+}
+
+// Flags like `--prefix APP` anywhere, everything else is positional
+fn parse_args(
+    args: &[String],
+    with_value: &[&str],
+) -> Option<(HashMap<String, String>, Vec<String>)> {
+    let mut flags: HashMap<String, String> = HashMap::new();
+    let mut positional: Vec<String> = vec![];
+    let mut i: usize = 0;
+
+    while i < args.len() {
+        let arg: &str = args[i].as_str();
+
+        if with_value.contains(&arg) {
+            flags.insert(String::from(arg), args.get(i + 1)?.clone());
+            i += 1;
+        } else if arg.starts_with("--") {
+            flags.insert(String::from(arg), String::new());
+        } else {
+            positional.push(String::from(arg));
+        }
+
+        i += 1;
+    }
+
+    return Some((flags, positional));
+}
+
+// Flags and a single file
+fn parse_flags(args: &[String], with_value: &[&str]) -> Option<(HashMap<String, String>, String)> {
+    let (flags, mut positional) = parse_args(args, with_value)?;
+
+    return if positional.len() == 1 {
+        Some((flags, positional.remove(0)))
+    } else {
+        None
+    };
+}
+
+// Flags and an optional file, stdin without one
+fn parse_input_flags(
+    args: &[String],
+    with_value: &[&str],
+) -> Option<(HashMap<String, String>, String)> {
+    let (flags, mut positional) = parse_args(args, with_value)?;
+
+    return match positional.len() {
+        0 => Some((flags, String::from("-"))),
+        1 => Some((flags, positional.remove(0))),
+        _ => None,
+    };
+}
+
+fn read_document(filepath: &str) -> Result<TOMLTable, String> {
+    let content: String = match read_input(filepath) {
+        Ok(content) => content,
+        Err(e) => {
+            return Err(format!(
+                "Failed to read `{}`: {}",
+                get_input_name(filepath),
+                e
+            ));
+        }
+    };
+
+    return parse_document(content.as_str())
+        .map_err(|e| format!("{}: {}", get_input_name(filepath), e));
+}
+
+fn convert_env(args: &[String], overlay: bool, output: Output) -> i32 {
+    let (flags, filepath) = match parse_flags(args, &["--prefix", "--separator", "--env-file"]) {
+        Some(parsed) => parsed,
+        None => {
+            return report_usage_error(output);
+        }
+    };
+
+    let mut options: EnvOptions = EnvOptions::new();
+
+    // Keys often contain `_`, overlays separate tables with `__` unless told otherwise
+    if overlay {
+        options.separator = String::from("__");
+    }
+
+    if let Some(prefix) = flags.get("--prefix") {
+        options.prefix = prefix.clone();
+    }
+
+    if let Some(separator) = flags.get("--separator") {
+        options.separator = separator.clone();
+    }
+
+    if flags.contains_key("--lower") {
+        options.case = EnvCase::Lower;
+    } else if flags.contains_key("--preserve") {
+        options.case = EnvCase::Preserve;
+    }
+
+    let mut document: TOMLTable = match read_document(filepath.as_str()) {
+        Ok(document) => document,
+        Err(e) => {
+            return report_error(output, e.to_string().as_str(), 2);
+        }
+    };
+
+    if !overlay {
+        let vars: Vec<(String, String)> = convert_tree_to_env(&document, &options);
+
+        match output {
+            Output::Text => print!("{}", convert_env_to_dotenv(&vars)),
+            Output::JSON => print_json(&JSONValue::Object(
+                vars.into_iter()
+                    .map(|(name, value)| (name, JSONValue::String(value)))
+                    .collect(),
+            )),
+        }
+
+        return 0;
+    }
+
+    let vars: Vec<(String, String)> = if let Some(env_file) = flags.get("--env-file") {
+        match read_input(env_file) {
+            Ok(content) => match parse_dotenv(content.as_str()) {
+                Ok(vars) => vars,
+                Err(e) => {
+                    return report_error(output, format!("{}: {}", env_file, e).as_str(), 2);
+                }
+            },
+            Err(e) => {
+                return report_error(
+                    output,
+                    format!("Failed to read `{}`: {}", env_file, e).as_str(),
+                    2,
+                );
+            }
+        }
+    } else if options.prefix.is_empty() {
+        // Without a prefix every variable of the process would apply
+        return report_error(output, "A prefix is needed to read the environment.", 2);
+    } else {
+        std::env::vars().collect()
+    };
+
+    return match overlay_env(&mut document, &vars, &options) {
+        Ok(_) => {
+            print_document(output, &document);
+            0
+        }
+        Err(e) => report_error(output, e.to_string().as_str(), 1),
+    };
+}
+
+fn merge(args: &[String], output: Output) -> i32 {
+    let mut options: MergeOptions = MergeOptions::new();
+    let mut annotate: bool = true;
+    let mut filepaths: Vec<&String> = vec![];
+    let mut i: usize = 0;
+
+    while i < args.len() {
+        match args[i].as_str() {
+            "--plain" => annotate = false,
+            "--strategy" => {
+                let parsed = args.get(i + 1).and_then(|rule| {
+                    let (path, name) = rule.split_once('=')?;
+                    Some((path, MergeStrategy::from_name(name)?))
+                });
+
+                match parsed {
+                    Some((path, strategy)) => options.set_strategy(path, strategy),
+                    None => {
+                        return report_error(
+                            output,
+                            "Expected `--strategy <path>=<replace|append|merge|error>`.",
+                            2,
+                        );
                     }
+                }
+
+                i += 1;
+            }
+            _ => filepaths.push(&args[i]),
+        }
+
+        i += 1;
+    }
+
+    let mut merged: MergedDocument = MergedDocument::new();
+
+    for filepath in filepaths {
+        let document: TOMLTable = match read_document(filepath) {
+            Ok(document) => document,
+            Err(e) => {
+                return report_error(output, e.to_string().as_str(), 2);
+            }
+        };
+
+        if let Err(e) = merged.merge(filepath, &document, &options) {
+            return report_error(output, e.to_string().as_str(), 1);
+        }
+    }
+
+    if !annotate || output == Output::JSON {
+        print_document(output, &merged.document);
+        return 0;
+    }
+
+    let origins = |path: &[PathSegment]| {
+        merged.origins.get(&format_path(path)).map(|origins| {
+            origins
+                .iter()
+                .map(|o| o.to_string())
+                .collect::<Vec<String>>()
+                .join(", ")
+        })
+    };
+
+    print!(
+        "{}",
+        convert_tree_to_annotated_toml(&merged.document, true, &origins)
+    );
+
+    return 0;
+}
+
+fn resolve(args: &[String], output: Output) -> i32 {
+    let (flags, filepath) = match parse_flags(args, &["--root", "--max-depth"]) {
+        Some(parsed) => parsed,
+        None => {
+            return report_usage_error(output);
+        }
+    };
+
+    let mut options: IncludeOptions = IncludeOptions::new();
+
+    if let Some(root) = flags.get("--root") {
+        options.root = Some(PathBuf::from(root));
+    }
+
+    if let Some(max_depth) = flags.get("--max-depth") {
+        options.max_depth = match max_depth.parse::<usize>() {
+            Ok(max_depth) => max_depth,
+            Err(_) => {
+                return report_error(output, "Expected a number for `--max-depth`.", 2);
+            }
+        };
+    }
+
+    return match parse_file_with_includes(filepath.as_str(), &options) {
+        Ok(merged) => {
+            print_document(output, &merged.document);
+            0
+        }
+        Err(e) => report_error(output, e.to_string().as_str(), 1),
+    };
+}
+
+fn interpolate(args: &[String], output: Output) -> i32 {
+    let (flags, filepath) = match parse_flags(args, &[]) {
+        Some(parsed) => parsed,
+        None => {
+            return report_usage_error(output);
+        }
+    };
+
+    let document: TOMLTable = match read_document(filepath.as_str()) {
+        Ok(document) => document,
+        Err(e) => {
+            return report_error(output, e.to_string().as_str(), 2);
+        }
+    };
+
+    let mut options: InterpolationOptions = InterpolationOptions::new();
+    options.env = !flags.contains_key("--no-env");
+
+    return match interpolate_document(&document, &options) {
+        Ok(document) => {
+            print_document(output, &document);
+            0
+        }
+        Err(e) => report_error(output, format!("{}: {}", filepath, e).as_str(), 1),
+    };
+}
+
+fn diff(args: &[String], output: Output) -> i32 {
+    // The last two arguments are the files
+    let parsed = args
+        .split_last()
+        .and_then(|(new, args)| Some((parse_flags(args, &["--format"])?, new)));
+
+    let ((flags, old_path), new_path) = match parsed {
+        Some(parsed) => parsed,
+        None => {
+            return report_usage_error(output);
+        }
+    };
+
+    let mut documents: Vec<TOMLTable> = vec![];
+
+    for filepath in [&old_path, new_path] {
+        match read_document(filepath) {
+            Ok(document) => documents.push(document),
+            Err(e) => {
+                return report_error(output, e.to_string().as_str(), 2);
+            }
+        }
+    }
+
+    let diff: Vec<DiffEntry> = diff_documents(&documents[0], &documents[1]);
+    let format: &str = match (flags.get("--format"), output) {
+        (Some(format), _) => format.as_str(),
+        (None, Output::Text) => "text",
+        (None, Output::JSON) => "json",
+    };
+
+    return match convert_diff_to_string(&diff, format) {
+        Some(text) => {
+            print!("{}", text);
+
+            // Like diff(1), 1 when the documents differ
+            if diff.is_empty() {
+                0
+            } else {
+                1
+            }
+        }
+        None => report_error(
+            output,
+            format!(
+                "Unknown format `{}`, expected `text`, `json` or `patch`.",
+                format
+            )
+            .as_str(),
+            2,
+        ),
+    };
+}
+
+fn patch(args: &[String], output: Output) -> i32 {
+    // The last two arguments are the patch and the document
+    let parsed = args
+        .split_last()
+        .and_then(|(filepath, args)| Some((parse_flags(args, &[])?, filepath)));
+
+    let ((flags, patch_path), filepath) = match parsed {
+        Some(parsed) => parsed,
+        None => {
+            return report_usage_error(output);
+        }
+    };
+
+    let read =
+        |path: &str| read_input(path).map_err(|e| format!("Failed to read `{}`: {}", path, e));
+
+    // JSON patches are arrays of operations, TOML patches have `[[operations]]`
+    let operations: Result<Vec<PatchOperation>, String> = if patch_path.ends_with(".json") {
+        read(patch_path.as_str()).and_then(|content| {
+            parse_json(content.as_str())
+                .map_err(|e| e.to_string())
+                .and_then(|json| parse_json_patch(&json).map_err(|e| e.to_string()))
+                .map_err(|e| format!("{}: {}", patch_path, e))
+        })
+    } else {
+        read_document(patch_path.as_str()).and_then(|patch| {
+            parse_toml_patch(&patch).map_err(|e| format!("{}: {}", patch_path, e))
+        })
+    };
+
+    if flags.contains_key("--in-place") && filepath == "-" {
+        return report_error(output, "`--in-place` needs a file.", 2);
+    }
+
+    let (operations, content) = match (operations, read(filepath)) {
+        (Ok(operations), Ok(content)) => (operations, content),
+        (Err(e), _) | (_, Err(e)) => {
+            return report_error(output, e.to_string().as_str(), 2);
+        }
+    };
+
+    let patched: String = match apply_patch(content.as_str(), &operations) {
+        Ok(patched) => patched,
+        Err(e) => {
+            return report_error(output, format!("{}: {}", filepath, e).as_str(), 1);
+        }
+    };
+
+    if flags.contains_key("--dry-run") {
+        let documents = read_document(filepath).and_then(|old| {
+            parse_document(patched.as_str())
+                .map(|new| (old, new))
+                .map_err(|e| e.to_string())
+        });
+
+        return match documents {
+            Ok((old, new)) => {
+                let diff: Vec<DiffEntry> = diff_documents(&old, &new);
+
+                match output {
+                    Output::Text => print!("{}", convert_diff_to_text(&diff)),
+                    Output::JSON => print_json(&convert_diff_to_json(&diff)),
+                }
+
+                0
+            }
+            Err(e) => report_error(output, e.to_string().as_str(), 1),
+        };
+    }
+
+    return write_or_print(
+        filepath,
+        patched.as_str(),
+        flags.contains_key("--in-place"),
+        output,
+    );
+}
+
+// Like `fmt`, edits are printed unless `--in-place` is given
+fn write_or_print(filepath: &str, content: &str, in_place: bool, output: Output) -> i32 {
+    if in_place {
+        if let Err(e) = write_file(filepath, content) {
+            return report_error(
+                output,
+                format!("Failed to write `{}`: {}", filepath, e).as_str(),
+                2,
+            );
+        }
+
+        return 0;
+    }
+
+    match (output, parse_document(content)) {
+        (Output::JSON, Ok(document)) => print_json(&convert_tree_to_json(&document, false)),
+        _ => print!("{}", content),
+    }
+
+    return 0;
+}
+
+// Written next to the file and renamed over it, so it's never half written
+fn write_file(filepath: &str, content: &str) -> std::io::Result<()> {
+    let temporary: String = format!("{}.tmp", filepath);
+
+    return fs::write(&temporary, content)
+        .and_then(|_| fs::rename(&temporary, filepath))
+        .map_err(|e| {
+            let _ = fs::remove_file(&temporary);
+            e
+        });
+}
+
+fn check(args: &[String], output: Output) -> i32 {
+    let mut filepaths: Vec<String> = match parse_args(args, &[]) {
+        Some((_, filepaths)) => filepaths,
+        None => return report_usage_error(output),
+    };
+
+    if filepaths.is_empty() {
+        filepaths.push(String::from("-"));
+    }
+
+    let mut results: Vec<JSONValue> = vec![];
+    let mut code: i32 = 0;
+
+    for filepath in &filepaths {
+        let name: &str = get_input_name(filepath);
+
+        let content: String = match read_input(filepath) {
+            Ok(content) => content,
+            Err(e) => {
+                return report_error(
+                    output,
+                    format!("Failed to read `{}`: {}", name, e).as_str(),
+                    2,
+                );
+            }
+        };
+
+        // Every error is reported, `error` stays the first one for older scripts
+        let (_, errors) = parse_document_recovering(content.as_str());
+
+        match output {
+            Output::Text if errors.is_empty() => println!("[INFO] {} is valid.", name),
+            Output::Text => {
+                for e in &errors {
+                    eprintln!("[ERROR] {}: {}", name, e);
+                }
+            }
+            Output::JSON => results.push(JSONValue::Object(vec![
+                (String::from("file"), JSONValue::String(String::from(name))),
+                (String::from("valid"), JSONValue::Bool(errors.is_empty())),
+                (
+                    String::from("error"),
+                    errors.first().map_or(JSONValue::Null, |e| {
+                        convert_parse_error_to_json(filepath, e)
+                    }),
+                ),
+                (
+                    String::from("errors"),
+                    JSONValue::Array(
+                        errors
+                            .iter()
+                            .map(|e| convert_parse_error_to_json(filepath, e))
+                            .collect(),
+                    ),
+                ),
+            ])),
+        }
+
+        if !errors.is_empty() {
+            code = 1;
+        }
+    }
+
+    if output == Output::JSON {
+        print_json(&JSONValue::Array(results));
+    }
+
+    return code;
+}
+
+fn read_document_or_report(filepath: &str, output: Output) -> Result<TOMLTable, i32> {
+    let content: String = read_input(filepath).map_err(|e| {
+        report_error(
+            output,
+            format!("Failed to read `{}`: {}", get_input_name(filepath), e).as_str(),
+            2,
+        )
+    })?;
+
+    return parse_document(content.as_str()).map_err(|e| {
+        report_error(
+            output,
+            format!("{}: {}", get_input_name(filepath), e).as_str(),
+            1,
+        )
+    });
+}
+
+// Strings are printed as they are, other values in TOML syntax and tables as documents
+fn get(args: &[String], output: Output) -> i32 {
+    let (path, filepath) = match parse_args(args, &[]) {
+        Some((_, positional)) if positional.len() == 1 => {
+            (positional[0].clone(), String::from("-"))
+        }
+        Some((_, positional)) if positional.len() == 2 => {
+            (positional[0].clone(), positional[1].clone())
+        }
+        _ => return report_usage_error(output),
+    };
+
+    let segments: Vec<PathSegment> = match parse_path(path.as_str()) {
+        Ok(segments) => segments,
+        Err(e) => return report_error(output, e.to_string().as_str(), 2),
+    };
+
+    let document: TOMLTable = match read_document_or_report(filepath.as_str(), output) {
+        Ok(document) => document,
+        Err(code) => return code,
+    };
+
+    let node: TOMLNode = match document.get_node(&segments) {
+        Some(node) => node,
+        None => {
+            return report_error(
+                output,
+                format!("`{}` isn't in the document.", path).as_str(),
+                1,
+            );
+        }
+    };
+
+    if output == Output::JSON {
+        print_json(&convert_subtree_to_json(&node.to_tree(), false));
+        return 0;
+    }
+
+    match node {
+        TOMLNode::Value(AST::ASTString(value), _) => println!("{}", value),
+        TOMLNode::Value(value, _) => println!("{}", convert_ast_value_to_toml(value)),
+        TOMLNode::Table(table) => print!("{}", convert_tree_to_toml(table, true)),
+        TOMLNode::TableArray(tables) => {
+            // Written as `[[name]]` under the last key of the path
+            let name: String = match segments.last() {
+                Some(PathSegment::Key(name)) => name.clone(),
+                _ => String::from("value"),
+            };
+
+            let mut table: TOMLTable = TOMLTable::new();
+            table.insert(name.as_str(), TOMLTree::TableArray(tables.clone()));
+
+            print!("{}", convert_tree_to_toml(&table, true));
+        }
+    }
+
+    return 0;
+}
+
+// `1`, `true` or `[1, 2]` are parsed as TOML, anything else is a string
+fn parse_value(value: &str, string: bool) -> AST {
+    if !string {
+        let parsed = parse_document(format!("value = {}\n", value).as_str())
+            .ok()
+            .and_then(|document| document.get("value").cloned());
+
+        if let Some(TOMLTree::Value(value, _)) = parsed {
+            return value;
+        }
+    }
+
+    return AST::ASTString(String::from(value));
+}
+
+// Edits the source so comments and layout are kept, missing tables are created
+fn set(args: &[String], output: Output) -> i32 {
+    let (flags, positional) = match parse_args(args, &[]) {
+        Some(parsed) => parsed,
+        None => return report_usage_error(output),
+    };
+
+    let (path, value, filepath) = match positional.as_slice() {
+        [path, value] => (path, value, String::from("-")),
+        [path, value, filepath] => (path, value, filepath.clone()),
+        _ => return report_usage_error(output),
+    };
+
+    if flags.contains_key("--in-place") && filepath == "-" {
+        return report_error(output, "`--in-place` needs a file.", 2);
+    }
+
+    let segments: Vec<PathSegment> = match parse_path(path.as_str()) {
+        Ok(segments) if !segments.is_empty() => segments,
+        Ok(_) => return report_error(output, "The path is empty.", 2),
+        Err(e) => return report_error(output, e.to_string().as_str(), 2),
+    };
+
+    let content: String = match read_input(filepath.as_str()) {
+        Ok(content) => content,
+        Err(e) => {
+            return report_error(
+                output,
+                format!("Failed to read `{}`: {}", get_input_name(&filepath), e).as_str(),
+                2,
+            );
+        }
+    };
+
+    let document: TOMLTable = match parse_document(content.as_str()) {
+        Ok(document) => document,
+        Err(e) => {
+            return report_error(
+                output,
+                format!("{}: {}", get_input_name(&filepath), e).as_str(),
+                1,
+            );
+        }
+    };
+
+    let mut tree: TOMLTree =
+        TOMLTree::Value(parse_value(value, flags.contains_key("--string")), None);
+
+    // Existing values are replaced, the first missing key gets the value wrapped in the tables
+    // after it
+    let missing: usize = (1..segments.len())
+        .find(|i| document.get_node(&segments[..*i]).is_none())
+        .unwrap_or(segments.len());
+
+    for segment in segments[missing..].iter().rev() {
+        let mut table: TOMLTable = TOMLTable::new();
+
+        match segment {
+            PathSegment::Key(key) => table.insert(key.as_str(), tree),
+            PathSegment::Index(_) => {
+                return report_error(
+                    output,
+                    format!(
+                        "`{}` isn't in the document.",
+                        format_path(&segments[..missing])
+                    )
+                    .as_str(),
+                    1,
+                );
+            }
+        }
+
+        tree = TOMLTree::Table(table);
+    }
+
+    let operation: PatchOperation = if document.get_node(&segments).is_some() {
+        PatchOperation::Replace(segments.clone(), tree)
+    } else {
+        PatchOperation::Add(segments[..missing].to_vec(), tree)
+    };
+
+    let patched: String = match apply_patch(content.as_str(), &[operation]) {
+        Ok(patched) => patched,
+        Err(e) => {
+            return report_error(
+                output,
+                format!("{}: {}", get_input_name(&filepath), e.message).as_str(),
+                1,
+            );
+        }
+    };
+
+    return write_or_print(
+        filepath.as_str(),
+        patched.as_str(),
+        flags.contains_key("--in-place"),
+        output,
+    );
+}
+
+fn fmt(args: &[String], output: Output) -> i32 {
+    let (flags, mut filepaths) = match parse_args(args, &[]) {
+        Some(parsed) => parsed,
+        None => return report_usage_error(output),
+    };
+
+    if filepaths.is_empty() {
+        filepaths.push(String::from("-"));
+    }
+
+    let in_place: bool = flags.contains_key("--in-place");
+    let check: bool = flags.contains_key("--check");
+
+    if in_place && filepaths.iter().any(|f| f == "-") {
+        return report_error(output, "`--in-place` needs a file.", 2);
+    }
+
+    let mut results: Vec<JSONValue> = vec![];
+    let mut code: i32 = 0;
+
+    for filepath in &filepaths {
+        let name: &str = get_input_name(filepath);
+
+        let content: String = match read_input(filepath) {
+            Ok(content) => content,
+            Err(e) => {
+                return report_error(
+                    output,
+                    format!("Failed to read `{}`: {}", name, e).as_str(),
+                    2,
+                );
+            }
+        };
+
+        let formatted: String = match format_document(content.as_str()) {
+            Ok(formatted) => formatted,
+            Err(e) => return report_error(output, format!("{}: {}", name, e).as_str(), 1),
+        };
+
+        let changed: bool = formatted != content;
+
+        if check && changed {
+            code = 1;
+        }
+
+        if in_place && changed {
+            if let Err(e) = write_file(filepath, formatted.as_str()) {
+                return report_error(
+                    output,
+                    format!("Failed to write `{}`: {}", name, e).as_str(),
+                    2,
                 );
             }
         }
+
+        match output {
+            Output::Text if check && changed => eprintln!("[ERROR] {} isn't formatted.", name),
+            Output::Text if check || in_place => {}
+            Output::Text => print!("{}", formatted),
+            Output::JSON => results.push(JSONValue::Object(vec![
+                (String::from("file"), JSONValue::String(String::from(name))),
+                (String::from("changed"), JSONValue::Bool(changed)),
+                (String::from("formatted"), JSONValue::String(formatted)),
+            ])),
+        }
+    }
+
+    if output == Output::JSON {
+        print_json(&JSONValue::Array(results));
     }
 
-    println!("[INFO] PARSING.\n");
+    return code;
+}
+
+fn tokens(args: &[String], output: Output) -> i32 {
+    let filepath: String = match parse_input_flags(args, &[]) {
+        Some((_, filepath)) => filepath,
+        None => return report_usage_error(output),
+    };
 
-    let mut pars: Parser = Parser::new("product/test.toml");
+    let content: String = match read_input(filepath.as_str()) {
+        Ok(content) => content,
+        Err(e) => {
+            return report_error(
+                output,
+                format!("Failed to read `{}`: {}", get_input_name(&filepath), e).as_str(),
+                2,
+            );
+        }
+    };
 
-    let ast: AST = pars.parse();
+    let mut lexer: Lexer = Lexer::from_string(content.as_str());
+    let mut tokens: Vec<JSONValue> = vec![];
 
-    if let AST::ASTCompound(vec) = &ast {
-        for a in vec {
-            println!("AST: {:?}", a);
+    while let Some(t) = lexer.get_next_token() {
+        if output == Output::Text {
+            println!(
+                "Token {{ {:?}, '{}' }}",
+                t.token_type,
+                if t.value == "\n" { "\\n" } else { t.value }
+            );
+            continue;
         }
+
+        tokens.push(JSONValue::Object(vec![
+            (
+                String::from("type"),
+                JSONValue::String(format!("{:?}", t.token_type)),
+            ),
+            (
+                String::from("value"),
+                JSONValue::String(String::from(t.value)),
+            ),
+            (String::from("line"), JSONValue::Int(t.position.line as i64)),
+            (
+                String::from("column"),
+                JSONValue::Int(t.position.column as i64),
+            ),
+            (
+                String::from("offset"),
+                JSONValue::Int(t.position.offset as i64),
+            ),
+        ]));
     }
 
-    println!();
+    if output == Output::JSON {
+        print_json(&JSONValue::Array(tokens));
+    }
 
-    let map = convert_ast_to_string(&ast, true);
+    return 0;
+}
 
-    for (name, tag) in map.tags.iter() {
+fn convert_statement_to_json(statement: &AST) -> JSONValue {
+    let (kind, name, value, position) = match statement {
+        AST::ASTTagDefinition(name, position) => ("table", Some(name), None, Some(position)),
+        AST::ASTTagArrayDefinition(name, position) => {
+            ("table-array", Some(name), None, Some(position))
+        }
+        AST::ASTVariableDefinition(name, value, position) => {
+            ("key", Some(name), Some(value.as_ref()), Some(position))
+        }
+        AST::ASTSeparator() => ("separator", None, None, None),
+        value => ("value", None, Some(value), None),
+    };
+
+    let mut object: JSONValue = JSONValue::Object(vec![(
+        String::from("kind"),
+        JSONValue::String(String::from(kind)),
+    )]);
+
+    if let Some(name) = name {
+        object.insert("name", JSONValue::String(name.clone()));
+    }
+
+    if let Some(value) = value {
+        object.insert(
+            "value",
+            convert_ast_to_json(value).unwrap_or(JSONValue::Null),
+        );
+    }
+
+    if let Some(position) = position {
+        object.insert("line", JSONValue::Int(position.line as i64));
+        object.insert("column", JSONValue::Int(position.column as i64));
+    }
+
+    return object;
+}
+
+// `--map` prints the tables and their keys the converter gives the tree
+fn ast(args: &[String], output: Output) -> i32 {
+    let (flags, filepath) = match parse_input_flags(args, &[]) {
+        Some(parsed) => parsed,
+        None => return report_usage_error(output),
+    };
+
+    let content: String = match read_input(filepath.as_str()) {
+        Ok(content) => content,
+        Err(e) => {
+            return report_error(
+                output,
+                format!("Failed to read `{}`: {}", get_input_name(&filepath), e).as_str(),
+                2,
+            );
+        }
+    };
+
+    let ast: AST = match Parser::from_string(content.as_str()).try_parse() {
+        Ok(ast) => ast,
+        Err(e) => {
+            return report_error(
+                output,
+                format!("{}: {}", get_input_name(&filepath), e).as_str(),
+                1,
+            );
+        }
+    };
+
+    let statements: Vec<AST> = match ast {
+        AST::ASTCompound(statements) => statements,
+        ast => vec![ast],
+    };
+
+    if !flags.contains_key("--map") {
+        match output {
+            Output::Text => {
+                for statement in &statements {
+                    println!("AST: {:?}", statement);
+                }
+            }
+            Output::JSON => print_json(&JSONValue::Array(
+                statements.iter().map(convert_statement_to_json).collect(),
+            )),
+        }
+
+        return 0;
+    }
+
+    let map: TOMLStringMap = match try_convert_ast_to_string(&AST::ASTCompound(statements), true) {
+        Ok(map) => map,
+        Err(e) => {
+            return report_error(
+                output,
+                format!("{}: {}", get_input_name(&filepath), e).as_str(),
+                1,
+            );
+        }
+    };
+
+    let mut names: Vec<&String> = map.tags.keys().collect();
+    names.sort();
+
+    if output == Output::JSON {
+        print_json(&JSONValue::Object(
+            names
+                .iter()
+                .map(|name| {
+                    let mut keys: Vec<(&String, &String)> = map.tags[*name].values.iter().collect();
+                    keys.sort();
+
+                    let values: Vec<(String, JSONValue)> = keys
+                        .into_iter()
+                        .map(|(k, v)| (k.clone(), JSONValue::String(v.clone())))
+                        .collect();
+
+                    ((*name).clone(), JSONValue::Object(values))
+                })
+                .collect(),
+        ));
+
+        return 0;
+    }
+
+    for name in names {
         println!("[{}]", name.to_uppercase());
-        for (k, v) in tag.values.iter() {
+
+        let mut keys: Vec<(&String, &String)> = map.tags[name].values.iter().collect();
+        keys.sort();
+
+        for (k, v) in keys {
             if v.contains(';') {
                 println!("{{ K: {}, V: [{}] }}", k, v);
             } else {
                 println!("{{ K: {}, V: {} }}", k, v);
             }
         }
+
         println!();
     }
+
+    return 0;
+}
+
+fn lint(args: &[String], output: Output) -> i32 {
+    let mut options: LintOptions = LintOptions::new();
+    let mut fix: bool = false;
+    let mut unsafe_fixes: bool = false;
+    let mut sarif: bool = false;
+    let mut filepaths: Vec<String> = vec![];
+    let mut i: usize = 0;
+
+    while i < args.len() {
+        match args[i].as_str() {
+            "--fix" => fix = true,
+            "--unsafe-fixes" => {
+                fix = true;
+                unsafe_fixes = true;
+            }
+            "--format" => {
+                match args.get(i + 1).map(|f| f.as_str()) {
+                    Some("text") => sarif = false,
+                    Some("sarif") => sarif = true,
+                    _ => return report_error(output, "Expected `--format text|sarif`.", 2),
+                }
+
+                i += 1;
+            }
+            "--rule" => {
+                let parsed = args.get(i + 1).and_then(|rule| {
+                    let (id, level) = rule.split_once('=')?;
+                    get_rule(id)?;
+
+                    match level {
+                        "off" => Some((id, None)),
+                        level => Some((id, Some(Severity::from_name(level)?))),
+                    }
+                });
+
+                match parsed {
+                    Some((id, severity)) => {
+                        options.rules.insert(String::from(id), severity);
+                    }
+                    None => {
+                        return report_error(
+                            output,
+                            "Expected `--rule <rule>=<off|note|warning|error>`.",
+                            2,
+                        );
+                    }
+                }
+
+                i += 1;
+            }
+            "--max-depth" => {
+                match args.get(i + 1).and_then(|d| d.parse::<usize>().ok()) {
+                    Some(depth) => options.max_depth = depth,
+                    None => {
+                        return report_error(output, "Expected a number for `--max-depth`.", 2);
+                    }
+                }
+
+                i += 1;
+            }
+            _ => filepaths.push(args[i].clone()),
+        }
+
+        i += 1;
+    }
+
+    if filepaths.is_empty() {
+        filepaths.push(String::from("-"));
+    }
+
+    let mut results: Vec<(String, Vec<Diagnostic>)> = vec![];
+
+    for filepath in &filepaths {
+        let name: &str = get_input_name(filepath);
+
+        let mut content: String = match read_input(filepath) {
+            Ok(content) => content,
+            Err(e) => {
+                return report_error(
+                    output,
+                    format!("Failed to read `{}`: {}", name, e).as_str(),
+                    2,
+                );
+            }
+        };
+
+        let mut diagnostics: Vec<Diagnostic> = match lint_document(content.as_str(), &options) {
+            Ok(diagnostics) => diagnostics,
+            Err(_) => return report_parse_errors(output, filepath, content.as_str()),
+        };
+
+        // Fixed documents are linted again for what is left
+        let fixable: bool = diagnostics
+            .iter()
+            .any(|d| d.fix.as_ref().map_or(false, |f| f.safe || unsafe_fixes));
+
+        if fix && fixable {
+            content = match apply_fixes(content.as_str(), &diagnostics, unsafe_fixes) {
+                Ok(content) => content,
+                Err(e) => {
+                    return report_error(
+                        output,
+                        format!("{}: The fixes would break the document: {}", name, e).as_str(),
+                        1,
+                    );
+                }
+            };
+
+            let written = if filepath == "-" {
+                print!("{}", content);
+                Ok(())
+            } else {
+                write_file(filepath, content.as_str())
+            };
+
+            if let Err(e) = written {
+                return report_error(
+                    output,
+                    format!("Failed to write `{}`: {}", name, e).as_str(),
+                    2,
+                );
+            }
+
+            diagnostics = match lint_document(content.as_str(), &options) {
+                Ok(diagnostics) => diagnostics,
+                Err(_) => return report_parse_errors(output, filepath, content.as_str()),
+            };
+        }
+
+        results.push((String::from(name), diagnostics));
+    }
+
+    if sarif {
+        print_json(&convert_diagnostics_to_sarif(&results));
+    } else if output == Output::JSON {
+        let mut diagnostics: Vec<JSONValue> = vec![];
+
+        for (name, result) in &results {
+            if let JSONValue::Array(values) = convert_diagnostics_to_json(name, result) {
+                diagnostics.extend(values);
+            }
+        }
+
+        print_json(&JSONValue::Array(diagnostics));
+    } else {
+        for (name, result) in &results {
+            print!("{}", convert_diagnostics_to_text(name, result));
+        }
+    }
+
+    // Notes don't fail the run
+    let failed: bool = results
+        .iter()
+        .flat_map(|(_, diagnostics)| diagnostics)
+        .any(|d| d.severity != Severity::Note);
+
+    return if failed { 1 } else { 0 };
+}
+
+const USAGE: &str = "Usage: toml <command> [--output text|json] [arguments]
+
+Files can be `-` or left out to read stdin.
+
+  check [<file>...]                        Validates the syntax
+  get <path> [<file>]                      Prints the value at the path
+  set [--string] [--in-place] <path> <value> [<file>]
+                                           Sets a value, keeping comments and layout
+  fmt [--check] [--in-place] [<file>...]   Formats documents
+  to-json [--typed] [<file>]               Converts TOML to JSON
+  from-json [--typed] [<file>]             Converts JSON to TOML
+  tokens [<file>]                          Prints the tokens
+  ast [--map] [<file>]                     Prints the syntax tree or the tables and keys
+  lint [--fix] [--unsafe-fixes] [--format text|sarif] [--max-depth <n>]
+       [--rule <rule>=<off|note|warning|error>]... [<file>...]
+                                           Reports style and correctness problems
+  schema-to-json <schema.toml>
+  schema-from-json <schema.json>
+  to-yaml <file>, from-yaml <file.yaml>    With the `yaml` feature
+  to-env [--prefix APP] [--separator _] [--lower | --preserve] <file>
+  overlay-env [--prefix APP] [--separator __] [--env-file .env] <file>
+  merge [--strategy <path>=<strategy>]... [--plain] <file>...
+  resolve [--root <dir>] [--max-depth <n>] <file>
+  interpolate [--no-env] <file>
+  diff [--format text|json|patch] <old> <new>
+  patch [--dry-run] [--in-place] <patch.toml | patch.json> <file>
+  infer-schema <file>..., infer-structs <file>...
+  validate <file> <schema>
+
+Exit codes are 0 on success, 1 when a document is invalid or a check fails and 2 for usage
+and IO errors.";
+
+fn main() {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+
+    // `--output json` can go anywhere
+    let output: Output = match args.iter().position(|a| a == "--output") {
+        None => Output::Text,
+        Some(i) if i + 1 < args.len() => {
+            let output: Output = match args[i + 1].as_str() {
+                "text" => Output::Text,
+                "json" => Output::JSON,
+                _ => process::exit(report_error(
+                    Output::Text,
+                    format!(
+                        "Unknown output `{}`, expected `text` or `json`.",
+                        args[i + 1]
+                    )
+                    .as_str(),
+                    2,
+                )),
+            };
+
+            args.drain(i..i + 2);
+            output
+        }
+        Some(_) => process::exit(report_usage_error(Output::Text)),
+    };
+
+    let (command, args) = match args.split_first() {
+        Some((command, args)) => (command.as_str(), args),
+        None => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+
+    let code: i32 = match (command, args) {
+        ("help" | "--help" | "-h", _) => {
+            println!("{}", USAGE);
+            0
+        }
+        ("check", _) => check(args, output),
+        ("get", _) => get(args, output),
+        ("set", _) => set(args, output),
+        ("fmt", _) => fmt(args, output),
+        ("to-json" | "from-json", _) => convert_document(args, command == "to-json", output),
+        ("tokens", _) => tokens(args, output),
+        ("ast", _) => ast(args, output),
+        ("lint", _) => lint(args, output),
+        ("schema-to-json" | "schema-from-json", [filepath]) => {
+            convert_schema(filepath.as_str(), command == "schema-to-json", output)
+        }
+        #[cfg(feature = "yaml")]
+        ("to-yaml" | "from-yaml", [filepath]) => {
+            convert_yaml(filepath.as_str(), command == "to-yaml", output)
+        }
+        ("to-env" | "overlay-env", [_, ..]) => convert_env(args, command == "overlay-env", output),
+        ("merge", [_, ..]) => merge(args, output),
+        ("resolve", [_, ..]) => resolve(args, output),
+        ("interpolate", [_, ..]) => interpolate(args, output),
+        ("diff", [_, _, ..]) => diff(args, output),
+        ("patch", [_, _, ..]) => patch(args, output),
+        ("infer-schema" | "infer-structs", [_, ..]) => {
+            infer(args, command == "infer-structs", output)
+        }
+        ("validate", [filepath, schema_path]) => {
+            validate(filepath.as_str(), schema_path.as_str(), output)
+        }
+        (
+            "schema-to-json" | "schema-from-json" | "to-yaml" | "from-yaml" | "to-env"
+            | "overlay-env" | "merge" | "resolve" | "interpolate" | "diff" | "patch"
+            | "infer-schema" | "infer-structs" | "validate",
+            _,
+        ) => report_usage_error(output),
+        _ => report_error(
+            output,
+            format!("Unknown command `{}`, see `toml help`.", command).as_str(),
+            2,
+        ),
+    };
+
+    process::exit(code);
 }
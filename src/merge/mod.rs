@@ -0,0 +1,368 @@
+use crate::parser::ast::AST;
+use crate::parser::converter::{get_value_type, try_convert_ast_value_to_string};
+use crate::parser::path::{format_path, parse_path, PathSegment};
+use crate::parser::token::Position;
+use crate::parser::tree::{TOMLTable, TOMLTree};
+use crate::schema::{parse_schema_path, SchemaSegment};
+use std::collections::BTreeMap;
+use std::fmt;
+
+// Layers documents on top of each other, later documents win. Without a strategy tables
+// are merged key by key and everything else is replaced. Strategies are set per path,
+// with `*` matching any key or array index, like `servers.*.ports`. `append` and `error`
+// on a table apply to everything inside it.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MergeStrategy {
+    // The later value replaces the whole subtree
+    Replace,
+    // Arrays and arrays of tables are concatenated
+    Append,
+    // Tables are merged key by key, arrays of tables element by element
+    Merge,
+    // Different values are an error
+    Error,
+}
+
+impl MergeStrategy {
+    pub fn from_name(name: &str) -> Option<Self> {
+        return match name {
+            "replace" => Some(MergeStrategy::Replace),
+            "append" => Some(MergeStrategy::Append),
+            "merge" => Some(MergeStrategy::Merge),
+            "error" => Some(MergeStrategy::Error),
+            _ => None,
+        };
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MergeOptions {
+    pub strategies: Vec<(Vec<SchemaSegment>, MergeStrategy)>,
+}
+
+impl MergeOptions {
+    pub fn new() -> Self {
+        return MergeOptions { strategies: vec![] };
+    }
+
+    pub fn set_strategy(&mut self, path: &str, strategy: MergeStrategy) {
+        self.strategies.push((parse_schema_path(path), strategy));
+    }
+
+    // The last matching strategy wins
+    fn get_strategy(&self, path: &[PathSegment]) -> Option<MergeStrategy> {
+        return self
+            .strategies
+            .iter()
+            .rev()
+            .find(|(pattern, _)| {
+                pattern.len() == path.len()
+                    && pattern.iter().zip(path).all(|segments| match segments {
+                        (SchemaSegment::Any, _) => true,
+                        (SchemaSegment::Key(a), PathSegment::Key(b)) => a == b,
+                        _ => false,
+                    })
+            })
+            .map(|(_, strategy)| *strategy);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Origin {
+    pub source: String,
+    pub position: Option<Position>,
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match &self.position {
+            Some(position) => write!(f, "{}:{}", self.source, position.line),
+            None => write!(f, "{}", self.source),
+        };
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MergeError {
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for MergeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return write!(f, "`{}`: {}", self.path, self.message);
+    }
+}
+
+impl std::error::Error for MergeError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MergedDocument {
+    pub document: TOMLTable,
+    // Where every value comes from, appended arrays have one origin per source
+    pub origins: BTreeMap<String, Vec<Origin>>,
+}
+
+// Compares values without their positions
+pub(crate) fn is_same_tree(a: &TOMLTree, b: &TOMLTree) -> bool {
+    return match (a, b) {
+        (TOMLTree::Value(a, _), TOMLTree::Value(b, _)) => a == b,
+        (TOMLTree::Table(a), TOMLTree::Table(b)) => is_same_table(a, b),
+        (TOMLTree::TableArray(a), TOMLTree::TableArray(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| is_same_table(a, b))
+        }
+        _ => false,
+    };
+}
+
+fn is_same_table(a: &TOMLTable, b: &TOMLTable) -> bool {
+    return a.entries.len() == b.entries.len()
+        && a.entries
+            .iter()
+            .all(|(key, tree)| b.get(key).map_or(false, |other| is_same_tree(tree, other)));
+}
+
+fn get_array_type(values: &[AST]) -> Option<String> {
+    let string: String = try_convert_ast_value_to_string(values.first()?, true).ok()?;
+
+    return get_value_type(string.as_str()).map(String::from);
+}
+
+struct Merger<'a> {
+    options: &'a MergeOptions,
+    source: &'a str,
+    origins: &'a mut BTreeMap<String, Vec<Origin>>,
+}
+
+impl<'a> Merger<'a> {
+    fn origin(&self, position: Option<Position>) -> Origin {
+        return Origin {
+            source: String::from(self.source),
+            position,
+        };
+    }
+
+    fn error(&self, path: &[PathSegment], message: &str) -> MergeError {
+        return MergeError {
+            path: format_path(path),
+            message: String::from(message),
+        };
+    }
+
+    fn record(&mut self, tree: &TOMLTree, path: &mut Vec<PathSegment>) {
+        match tree {
+            TOMLTree::Value(_, position) => {
+                let origin: Origin = self.origin(*position);
+                self.origins.insert(format_path(path), vec![origin]);
+            }
+            TOMLTree::Table(table) => self.record_table(table, path),
+            TOMLTree::TableArray(tables) => {
+                for (i, table) in tables.iter().enumerate() {
+                    path.push(PathSegment::Index(i));
+                    self.record_table(table, path);
+                    path.pop();
+                }
+            }
+        }
+    }
+
+    fn record_table(&mut self, table: &TOMLTable, path: &mut Vec<PathSegment>) {
+        for (key, tree) in &table.entries {
+            path.push(PathSegment::Key(key.clone()));
+            self.record(tree, path);
+            path.pop();
+        }
+    }
+
+    fn forget(&mut self, path: &[PathSegment]) {
+        let prefix: String = format_path(path);
+
+        self.origins.retain(|key, _| {
+            !(key == &prefix
+                || key.starts_with(format!("{}.", prefix).as_str())
+                || key.starts_with(format!("{}[", prefix).as_str()))
+        });
+    }
+
+    fn replace(&mut self, base: &mut TOMLTree, tree: &TOMLTree, path: &mut Vec<PathSegment>) {
+        self.forget(path);
+        *base = tree.clone();
+        self.record(tree, path);
+    }
+
+    fn merge_tree(
+        &mut self,
+        base: &mut TOMLTree,
+        tree: &TOMLTree,
+        path: &mut Vec<PathSegment>,
+        inherited: Option<MergeStrategy>,
+    ) -> Result<(), MergeError> {
+        let explicit: Option<MergeStrategy> = self.options.get_strategy(path);
+        let strategy: Option<MergeStrategy> = explicit.or(inherited);
+
+        match (strategy, base, tree) {
+            (
+                Some(strategy @ (MergeStrategy::Error | MergeStrategy::Append)),
+                TOMLTree::Table(table),
+                TOMLTree::Table(other),
+            ) => {
+                self.merge_table(table, other, path, Some(strategy))?;
+            }
+            (Some(MergeStrategy::Error), base, tree) => {
+                if !is_same_tree(base, tree) {
+                    let before: String = match self.origins.get(&format_path(path)) {
+                        Some(origins) => origins
+                            .iter()
+                            .map(|o| o.to_string())
+                            .collect::<Vec<String>>()
+                            .join(", "),
+                        None => String::from("an earlier source"),
+                    };
+
+                    return Err(self.error(
+                        path,
+                        format!(
+                            "Conflicting values from {} and {}.",
+                            before,
+                            self.origin(tree.get_position())
+                        )
+                        .as_str(),
+                    ));
+                }
+            }
+            (Some(MergeStrategy::Replace), base, tree) => self.replace(base, tree, path),
+            (
+                Some(MergeStrategy::Append),
+                TOMLTree::Value(AST::ASTArray(values), _),
+                TOMLTree::Value(AST::ASTArray(others), position),
+            ) => {
+                // Arrays hold a single type, empty arrays take any
+                if let (Some(a), Some(b)) = (get_array_type(values), get_array_type(others)) {
+                    if a != b {
+                        return Err(self.error(
+                            path,
+                            format!("Can't append `{}` values to `{}` values.", b, a).as_str(),
+                        ));
+                    }
+                }
+
+                values.extend(others.iter().cloned());
+
+                let origin: Origin = self.origin(*position);
+                self.origins
+                    .entry(format_path(path))
+                    .or_default()
+                    .push(origin);
+            }
+            (
+                Some(MergeStrategy::Append),
+                TOMLTree::TableArray(tables),
+                TOMLTree::TableArray(others),
+            ) => {
+                for table in others {
+                    path.push(PathSegment::Index(tables.len()));
+                    self.record_table(table, path);
+                    path.pop();
+
+                    tables.push(table.clone());
+                }
+            }
+            (Some(MergeStrategy::Append), _, _) if explicit.is_some() => {
+                return Err(self.error(path, "Only arrays can be appended."));
+            }
+            (_, TOMLTree::Table(table), TOMLTree::Table(other)) => {
+                self.merge_table(table, other, path, None)?;
+            }
+            (
+                Some(MergeStrategy::Merge),
+                TOMLTree::TableArray(tables),
+                TOMLTree::TableArray(others),
+            ) => {
+                for (i, other) in others.iter().enumerate() {
+                    path.push(PathSegment::Index(i));
+
+                    if i < tables.len() {
+                        self.merge_table(&mut tables[i], other, path, None)?;
+                    } else {
+                        self.record_table(other, path);
+                        tables.push(other.clone());
+                    }
+
+                    path.pop();
+                }
+            }
+            (_, base, tree) => self.replace(base, tree, path),
+        }
+
+        return Ok(());
+    }
+
+    fn merge_table(
+        &mut self,
+        base: &mut TOMLTable,
+        table: &TOMLTable,
+        path: &mut Vec<PathSegment>,
+        inherited: Option<MergeStrategy>,
+    ) -> Result<(), MergeError> {
+        for (key, tree) in &table.entries {
+            path.push(PathSegment::Key(key.clone()));
+
+            match base.get_mut(key) {
+                Some(existing) => self.merge_tree(existing, tree, path, inherited)?,
+                None => {
+                    self.record(tree, path);
+                    base.insert(key, tree.clone());
+                }
+            }
+
+            path.pop();
+        }
+
+        return Ok(());
+    }
+}
+
+impl MergedDocument {
+    pub fn new() -> Self {
+        return MergedDocument {
+            document: TOMLTable::new(),
+            origins: BTreeMap::new(),
+        };
+    }
+
+    // Layers `document` on top, `source` names it in origins, usually the file path
+    pub fn merge(
+        &mut self,
+        source: &str,
+        document: &TOMLTable,
+        options: &MergeOptions,
+    ) -> Result<(), MergeError> {
+        let mut merger: Merger = Merger {
+            options,
+            source,
+            origins: &mut self.origins,
+        };
+
+        return merger.merge_table(&mut self.document, document, &mut vec![], None);
+    }
+
+    pub fn get_origins(&self, path: &str) -> Option<&Vec<Origin>> {
+        let segments: Vec<PathSegment> = parse_path(path).ok()?;
+
+        return self.origins.get(&format_path(&segments));
+    }
+}
+
+pub fn merge_documents(
+    documents: &[(&str, &TOMLTable)],
+    options: &MergeOptions,
+) -> Result<MergedDocument, MergeError> {
+    let mut merged: MergedDocument = MergedDocument::new();
+
+    for (source, document) in documents {
+        merged.merge(source, document, options)?;
+    }
+
+    return Ok(merged);
+}
@@ -1,11 +1,17 @@
-#[derive(Debug)]
+use crate::parser::token::Position;
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum AST {
-    ASTTagDefinition(String),
-    ASTVariableDefinition(String, Box<AST>),
+    ASTTagDefinition(String, Position),
+    ASTTagArrayDefinition(String, Position),
+    ASTVariableDefinition(String, Box<AST>, Position),
     ASTSeparator(),
     ASTBool(bool),
     ASTInt(i32),
     ASTString(String),
+    ASTDateTime(String),
     ASTArray(Vec<AST>),
     ASTCompound(Vec<AST>),
+    // A statement that failed to parse, only from `Parser::parse_recovering`
+    ASTError(String, Position),
 }
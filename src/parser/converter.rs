@@ -1,4 +1,7 @@
 use crate::parser::ast::AST;
+use crate::parser::error::ParseError;
+use crate::parser::parser::is_datetime;
+use crate::parser::token::Position;
 use std::any::Any;
 
 use std::collections::HashMap;
@@ -31,11 +34,34 @@ impl TOMLStringMap {
             panic!("[ERROR] Tag with name `{}`, doesn't exists", tag);
         }
     }
+
+    pub fn add_value_at(&mut self, tag: &str, n: &str, v: &str, position: Position) {
+        self.add_value(tag, n, v);
+
+        if let Some(tag) = self.tags.get_mut(tag) {
+            tag.positions.insert(String::from(n), position);
+        }
+    }
+
+    pub fn remove_tag(&mut self, tag: &str) -> bool {
+        return self.tags.remove(tag).is_some();
+    }
+
+    pub fn remove_value(&mut self, tag: &str, n: &str) -> bool {
+        return if let Some(tag) = self.tags.get_mut(tag) {
+            tag.positions.remove(n);
+            tag.values.remove(n).is_some()
+        } else {
+            false
+        };
+    }
 }
 
 pub struct TOMLStringTag {
     pub name: String,
     pub values: HashMap<String, String>,
+    pub position: Option<Position>,
+    pub positions: HashMap<String, Position>,
 }
 
 impl TOMLStringTag {
@@ -43,6 +69,8 @@ impl TOMLStringTag {
         return Self {
             name: String::from(n),
             values: HashMap::new(),
+            position: None,
+            positions: HashMap::new(),
         };
     }
 
@@ -51,7 +79,60 @@ impl TOMLStringTag {
     }
 }
 
-fn convert_ast_array_to_string(array: &Vec<AST>, typecheck: bool) -> String {
+pub fn get_value_type(value: &str) -> Option<&str> {
+    // Empty arrays are stored without a type
+    if value.is_empty() {
+        return Some("array");
+    }
+
+    // Values converted without typechecking start with their contents
+    if !value.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        return None;
+    }
+
+    return if let Some(pos) = value.find('#') {
+        Some(&value[0..pos])
+    } else {
+        None
+    };
+}
+
+// Types of empty arrays, or arrays of them, end with `array`
+fn is_empty_array_type(t: &str) -> bool {
+    return t == "array" || t.ends_with("_array");
+}
+
+// The more specific of two array element types, None if they don't match. Empty arrays
+// match any array type.
+pub(crate) fn get_common_type<'b>(a: &'b str, b: &'b str) -> Option<&'b str> {
+    let is_empty_version = |empty: &str, t: &str| {
+        is_empty_array_type(empty) && t.starts_with(empty) && t[empty.len()..].starts_with('_')
+    };
+
+    return if a == b || is_empty_version(b, a) {
+        Some(a)
+    } else if is_empty_version(a, b) {
+        Some(b)
+    } else {
+        None
+    };
+}
+
+// Quotes, separators and backslashes in strings are escaped with a backslash
+fn escape_string(value: &str) -> String {
+    let mut string: String = String::with_capacity(value.len());
+
+    for c in value.chars() {
+        if c == '\\' || c == '\'' || c == ';' || c == '|' {
+            string.push('\\');
+        }
+        string.push(c);
+    }
+
+    return string;
+}
+
+fn convert_ast_array_to_string(array: &Vec<AST>, typecheck: bool) -> Result<String, String> {
     // Return string
     let mut string: String = String::new();
 
@@ -59,9 +140,9 @@ fn convert_ast_array_to_string(array: &Vec<AST>, typecheck: bool) -> String {
     let mut first: bool = true;
     let mut current_type: String = String::new();
 
-    let mut check_type = |str: &mut String, t: &str| {
+    let mut check_type = |str: &mut String, t: &str| -> Result<(), String> {
         if !typecheck {
-            return;
+            return Ok(());
         }
 
         let real_t: String = t.to_owned() + "#";
@@ -73,47 +154,74 @@ fn convert_ast_array_to_string(array: &Vec<AST>, typecheck: bool) -> String {
             str.push_str(real_t.as_str());
         }
 
+        // Empty arrays match any array type, the more specific type is kept
         if real_t != current_type.as_str() {
-            panic!(
-                "[ERROR] Wrong type for array element, expected `{}` found `{}`.",
+            let current: String = current_type[0..current_type.len() - 1].to_owned();
+
+            match get_common_type(current.as_str(), t) {
+                Some(common) if common == t => {
+                    str.replace_range(0..current_type.len(), real_t.as_str());
+                    current_type = real_t;
+
+                    return Ok(());
+                }
+                Some(_) => return Ok(()),
+                None => {}
+            }
+
+            return Err(format!(
+                "Wrong type for array element, expected `{}` found `{}`.",
                 current_type[0..current_type.len() - 1].to_string(),
                 t
-            );
+            ));
         }
+
+        return Ok(());
     };
 
     // Parsing
     for ast in array.iter() {
         match ast {
             AST::ASTBool(v) => {
-                check_type(&mut string, "array_bool");
+                check_type(&mut string, "array_bool")?;
 
                 string += ";";
                 string += v.to_string().as_str();
             }
             AST::ASTInt(v) => {
-                check_type(&mut string, "array_int");
+                check_type(&mut string, "array_int")?;
 
                 string += ";";
                 string += v.to_string().as_str();
             }
             AST::ASTString(v) => {
-                check_type(&mut string, "array_string");
+                check_type(&mut string, "array_string")?;
 
                 string += ";";
                 string += "'";
-                string += v;
+                string += escape_string(v).as_str();
                 string += "'";
             }
+            AST::ASTDateTime(v) => {
+                check_type(&mut string, "array_datetime")?;
+
+                string += ";";
+                string += v;
+            }
             AST::ASTArray(vec) => {
-                let arr_string = convert_ast_array_to_string(vec, typecheck);
+                let mut arr_string = convert_ast_array_to_string(vec, typecheck)?;
 
                 if typecheck {
+                    // Nested empty arrays are typed so they can be read back next to their siblings
+                    if arr_string.is_empty() {
+                        arr_string = String::from("array#");
+                    }
+
                     let pos = arr_string.find('#');
                     if let Some(n) = pos {
                         let mut t: String = String::from("array_");
                         t += &arr_string[0..n];
-                        check_type(&mut string, t.as_str());
+                        check_type(&mut string, t.as_str())?;
                     }
                 }
 
@@ -122,79 +230,228 @@ fn convert_ast_array_to_string(array: &Vec<AST>, typecheck: bool) -> String {
                 string += "|";
             }
             _ => {
-                panic!("[ERROR] Invalid AST for conversion, found `{:?}`.", ast);
+                return Err(format!("Invalid AST for conversion, found `{:?}`.", ast));
             }
         }
     }
 
-    return string;
+    return Ok(string);
+}
+
+pub fn convert_ast_value_to_string(value: &AST, typecheck: bool) -> String {
+    return match try_convert_ast_value_to_string(value, typecheck) {
+        Ok(str) => str,
+        Err(e) => panic!("[ERROR] {}", e),
+    };
+}
+
+pub fn try_convert_ast_value_to_string(value: &AST, typecheck: bool) -> Result<String, String> {
+    return match value {
+        AST::ASTBool(v) => {
+            let mut str: String;
+
+            if typecheck {
+                str = String::from("bool#");
+            } else {
+                str = String::new();
+            }
+
+            str += v.to_string().as_str();
+            Ok(str)
+        }
+        AST::ASTInt(v) => {
+            let mut str: String;
+
+            if typecheck {
+                str = String::from("int#");
+            } else {
+                str = String::new();
+            }
+
+            str += v.to_string().as_str();
+            Ok(str)
+        }
+        AST::ASTString(v) => {
+            let mut str: String;
+
+            if typecheck {
+                str = String::from("string#'");
+            } else {
+                str = String::from("'");
+            }
+
+            str += escape_string(v).as_str();
+            str += "'";
+            Ok(str)
+        }
+        AST::ASTDateTime(v) => {
+            let mut str: String;
+
+            if typecheck {
+                str = String::from("datetime#");
+            } else {
+                str = String::new();
+            }
+
+            str += v.as_str();
+            Ok(str)
+        }
+        AST::ASTArray(v) => convert_ast_array_to_string(v, typecheck),
+        _ => Err(format!(
+            "Unknown type for variable value, type was `{:?}`.",
+            value
+        )),
+    };
+}
+
+pub(crate) fn resolve_tag_name(name: &str, tag_arrays: &HashMap<String, usize>, resolve_last: bool) -> String {
+    let mut resolved: String = String::new();
+
+    let parts: Vec<&str> = name.split('.').collect();
+
+    for (i, part) in parts.iter().enumerate() {
+        if !resolved.is_empty() {
+            resolved += ".";
+        }
+        resolved += part;
+
+        if i == parts.len() - 1 && !resolve_last {
+            break;
+        }
+
+        // Tags inside an array of tags belong to its last element
+        if let Some(count) = tag_arrays.get(&resolved) {
+            resolved += format!("[{}]", count - 1).as_str();
+        }
+    }
+
+    return resolved;
 }
 
 pub fn convert_ast_to_string(compound: &AST, typecheck: bool) -> TOMLStringMap {
+    return match try_convert_ast_to_string(compound, typecheck) {
+        Ok(map) => map,
+        Err(e) => panic!("[ERROR] {}", e),
+    };
+}
+
+pub fn try_convert_ast_to_string(
+    compound: &AST,
+    typecheck: bool,
+) -> Result<TOMLStringMap, ParseError> {
+    return convert_ast_to_string_with(compound, typecheck, &mut |e| Err(e));
+}
+
+// Converts what it can, a table that fails is left out with its keys. `ASTError` nodes are
+// skipped, they are already reported by the parser.
+pub fn convert_ast_to_string_recovering(
+    compound: &AST,
+    typecheck: bool,
+) -> (TOMLStringMap, Vec<ParseError>) {
+    let mut errors: Vec<ParseError> = vec![];
+
+    let map: TOMLStringMap = convert_ast_to_string_with(compound, typecheck, &mut |e| {
+        errors.push(e);
+        return Ok(());
+    })
+    .unwrap_or_else(|_| TOMLStringMap::new());
+
+    return (map, errors);
+}
+
+// `on_error` stops the conversion by returning the error, or lets it go on
+fn convert_ast_to_string_with(
+    compound: &AST,
+    typecheck: bool,
+    on_error: &mut dyn FnMut(ParseError) -> Result<(), ParseError>,
+) -> Result<TOMLStringMap, ParseError> {
     let mut map: TOMLStringMap = TOMLStringMap::new();
 
     let mut current_tag: String = String::from(".");
 
+    // Element count of every array of tags, elements are stored as `name[index]`
+    let mut tag_arrays: HashMap<String, usize> = HashMap::new();
+
+    let add_tag = |map: &mut TOMLStringMap, name: &str, position: &Position| {
+        if map.tags.contains_key(name) {
+            return Err(ParseError::new(
+                format!("Tag with name `{}`, already exists.", name).as_str(),
+                *position,
+            ));
+        }
+
+        map.add_tag(name, true);
+        map.tags.get_mut(name).unwrap().position = Some(*position);
+
+        return Ok(());
+    };
+
+    // Keys of a table that failed are dropped
+    let mut skipping: bool = false;
+
     if let AST::ASTCompound(asts) = &compound {
         for ast in asts.iter() {
             match ast {
-                AST::ASTTagDefinition(n) => {
-                    map.add_tag(n.as_str(), true);
-                    current_tag = n.clone();
+                AST::ASTTagDefinition(n, position) => {
+                    let name: String = resolve_tag_name(n, &tag_arrays, true);
+
+                    skipping = false;
+
+                    if let Err(e) = add_tag(&mut map, name.as_str(), position) {
+                        on_error(e)?;
+                        skipping = true;
+                    }
+
+                    current_tag = name;
                 }
-                AST::ASTVariableDefinition(n, v) => {
-                    let val: String = match v.as_ref() {
-                        AST::ASTBool(v) => {
-                            let mut str: String;
-
-                            if typecheck {
-                                str = String::from("bool#");
-                            } else {
-                                str = String::new();
-                            }
-
-                            str += v.to_string().as_str();
-                            str
-                        }
-                        AST::ASTInt(v) => {
-                            let mut str: String;
+                AST::ASTTagArrayDefinition(n, position) => {
+                    let array: String = resolve_tag_name(n, &tag_arrays, false);
 
-                            if typecheck {
-                                str = String::from("int#");
-                            } else {
-                                str = String::new();
-                            }
+                    let count: &mut usize = tag_arrays.entry(array.clone()).or_insert(0);
+                    let name: String = format!("{}[{}]", array, count);
+                    *count += 1;
 
-                            str += v.to_string().as_str();
-                            str
-                        }
-                        AST::ASTString(v) => {
-                            let mut str: String;
-
-                            if typecheck {
-                                str = String::from("string#'");
-                            } else {
-                                str = String::from("'");
-                            }
-
-                            str += v.as_str();
-                            str += "'";
-                            str
-                        }
-                        AST::ASTArray(v) => convert_ast_array_to_string(v, typecheck),
-                        _ => {
-                            panic!(
-                                "[ERROR] Unknown type for variable value, type was `{:?}`.",
-                                v
-                            );
+                    skipping = false;
+
+                    if let Err(e) = add_tag(&mut map, name.as_str(), position) {
+                        on_error(e)?;
+                        skipping = true;
+                    }
+
+                    current_tag = name;
+                }
+                AST::ASTVariableDefinition(_, _, _) if skipping => {}
+                AST::ASTVariableDefinition(n, v, position) => {
+                    let val: String = match try_convert_ast_value_to_string(v, typecheck) {
+                        Ok(val) => val,
+                        Err(e) => {
+                            on_error(ParseError::new(e.as_str(), *position))?;
+                            continue;
                         }
                     };
-                    map.add_value(current_tag.as_str(), n, val.as_str());
+
+                    // Values before the first tag are global
+                    if current_tag == "." {
+                        map.add_tag(".", false);
+                    }
+
+                    // Keys are only defined once per table
+                    if map.tags[current_tag.as_str()].values.contains_key(n) {
+                        on_error(ParseError::new(
+                            format!("Key with name `{}`, already exists.", n).as_str(),
+                            *position,
+                        ))?;
+                        continue;
+                    }
+
+                    map.add_value_at(current_tag.as_str(), n, val.as_str(), *position);
                 }
                 AST::ASTSeparator() => {
                     map.add_tag(".", false);
                     current_tag = String::from(".");
+                    skipping = false;
                 }
+                AST::ASTError(_, _) => {}
                 _ => {
                     panic!("[ERROR] Invalid AST for conversion, found `{:?}`.", ast);
                 }
@@ -204,5 +461,191 @@ pub fn convert_ast_to_string(compound: &AST, typecheck: bool) -> TOMLStringMap {
         panic!("[ERROR] Expected AST Compound found `{:?}`.", compound);
     }
 
-    return map;
+    return Ok(map);
+}
+
+struct ValueReader<'a> {
+    value: &'a str,
+    ptr: usize,
+}
+
+impl<'a> ValueReader<'a> {
+    fn current(&self) -> Option<char> {
+        return self.value[self.ptr..].chars().next();
+    }
+
+    fn read_type(&mut self) -> Option<&'a str> {
+        // Types are only present when the value is converted with typechecking
+        if !self.value[self.ptr..].starts_with(|c: char| c.is_ascii_alphabetic()) {
+            return None;
+        }
+
+        let rest: &'a str = &self.value[self.ptr..];
+        let end: usize = rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))?;
+
+        if !rest[end..].starts_with('#') {
+            return None;
+        }
+
+        self.ptr += end + 1;
+
+        return Some(&rest[0..end]);
+    }
+
+    fn read_scalar(&mut self) -> Option<AST> {
+        let rest: &str = &self.value[self.ptr..];
+
+        // Strings end with the first quote that isn't escaped
+        if rest.starts_with('\'') {
+            let mut string: String = String::new();
+            let mut chars = rest.char_indices().skip(1);
+
+            while let Some((i, c)) = chars.next() {
+                match c {
+                    '\\' => string.push(chars.next()?.1),
+                    '\'' => {
+                        self.ptr += i + 1;
+                        return Some(AST::ASTString(string));
+                    }
+                    _ => string.push(c),
+                }
+            }
+
+            return None;
+        }
+
+        let end: usize = rest
+            .find(|c: char| c == ';' || c == '|')
+            .unwrap_or(rest.len());
+        let scalar: &str = &rest[0..end];
+
+        self.ptr += end;
+
+        return match scalar {
+            "true" => Some(AST::ASTBool(true)),
+            "false" => Some(AST::ASTBool(false)),
+            _ if is_datetime(scalar) => Some(AST::ASTDateTime(scalar.to_owned())),
+            _ => scalar.parse::<i32>().ok().map(AST::ASTInt),
+        };
+    }
+
+    fn read_array(&mut self, array_type: Option<&str>) -> Option<AST> {
+        let mut array: Vec<AST> = vec![];
+
+        // Nested arrays are wrapped in `|`, other elements are prefixed with `;`
+        let mut nested: Option<bool> = array_type.map(|t| t.starts_with("array_array"));
+
+        loop {
+            match self.current() {
+                None => break,
+                Some(';') if nested != Some(true) => {
+                    self.ptr += 1;
+                    array.push(self.read_scalar()?);
+                    nested = Some(false);
+                }
+                Some('|') if nested != Some(false) => {
+                    // A separator that doesn't start a new array closes this one
+                    let next: Option<char> = self.value[self.ptr + 1..].chars().next();
+                    if !array.is_empty()
+                        && !matches!(next, Some(c) if c == ';' || c.is_ascii_alphabetic())
+                    {
+                        break;
+                    }
+
+                    self.ptr += 1;
+
+                    let element_type: Option<&str> = self.read_type();
+                    array.push(self.read_array(element_type)?);
+
+                    if self.current() != Some('|') {
+                        return None;
+                    }
+
+                    self.ptr += 1;
+                    nested = Some(true);
+                }
+                _ => break,
+            }
+        }
+
+        return Some(AST::ASTArray(array));
+    }
+}
+
+pub fn convert_string_to_ast(value: &str) -> Option<AST> {
+    // Empty arrays are stored as empty strings
+    if value.is_empty() {
+        return Some(AST::ASTArray(vec![]));
+    }
+
+    let mut reader: ValueReader = ValueReader { value, ptr: 0 };
+
+    let ast: AST = match reader.read_type() {
+        Some("bool") | Some("int") | Some("string") | Some("datetime") => reader.read_scalar()?,
+        Some(t) if t.starts_with("array") => reader.read_array(Some(t))?,
+        Some(_) => return None,
+        None => {
+            if value.starts_with(';') || value.starts_with('|') {
+                reader.read_array(None)?
+            } else {
+                reader.read_scalar()?
+            }
+        }
+    };
+
+    // The whole value must be consumed
+    return if reader.ptr == value.len() {
+        Some(ast)
+    } else {
+        None
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parser::Parser;
+
+    fn convert(content: &str) -> Result<TOMLStringMap, ParseError> {
+        let ast: AST = Parser::from_string(content).try_parse().unwrap();
+
+        return try_convert_ast_to_string(&ast, true);
+    }
+
+    #[test]
+    fn duplicate_keys() {
+        let error: ParseError = convert("a = 1\na = 2\n").err().unwrap();
+
+        assert_eq!(error.message, "Key with name `a`, already exists.");
+        assert_eq!((error.position.line, error.position.column), (2, 1));
+
+        let error: ParseError = convert("[t]\nb = 1\nc = 2\nb = \"x\"\n").err().unwrap();
+
+        assert_eq!(error.position.line, 4);
+    }
+
+    #[test]
+    fn same_key_in_other_tables() {
+        let map: TOMLStringMap =
+            convert("a = 1\n[t]\na = 2\n[[u]]\na = 4\n[[u]]\na = 5\n").unwrap();
+
+        assert_eq!(map.get_int(".", "a"), Some(1));
+        assert_eq!(map.get_int("t", "a"), Some(2));
+        assert_eq!(map.get_int("u[1]", "a"), Some(5));
+
+        // The empty line makes the last key global again
+        let error: ParseError = convert("a = 1\n[t]\na = 2\n\na = 3\n").err().unwrap();
+
+        assert_eq!(error.position.line, 5);
+    }
+
+    #[test]
+    fn duplicate_keys_recovering() {
+        let ast: AST = Parser::from_string("a = 1\na = 2\nb = 3\n").try_parse().unwrap();
+        let (map, errors) = convert_ast_to_string_recovering(&ast, true);
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(map.get_int(".", "a"), Some(1));
+        assert_eq!(map.get_int(".", "b"), Some(3));
+    }
 }
@@ -0,0 +1,29 @@
+use crate::parser::token::Position;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub message: String,
+    pub position: Position,
+}
+
+impl ParseError {
+    pub fn new(message: &str, position: Position) -> Self {
+        return ParseError {
+            message: String::from(message),
+            position,
+        };
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return write!(
+            f,
+            "{} At line {}, column {}.",
+            self.message, self.position.line, self.position.column
+        );
+    }
+}
+
+impl std::error::Error for ParseError {}
@@ -0,0 +1,109 @@
+use crate::parser::error::ParseError;
+use crate::parser::layout::{get_document_layout, DocumentLayout};
+use crate::parser::path::{format_path, PathSegment};
+use crate::parser::tree::{parse_document, TOMLNode, TOMLTable};
+use crate::parser::writer::convert_ast_value_to_toml;
+
+// Formats the source without losing comments. Keys are written as `key = value` without
+// indentation, headers without spaces, comments after one space and runs of empty lines
+// become one. Empty lines end tables, so none are added or removed entirely. Arrays that
+// span lines are kept as written since they can hold comments.
+
+fn push_line(lines: &mut Vec<String>, line: &mut String) {
+    let line: String = std::mem::take(line);
+
+    // Runs of empty lines become one, and none at the start
+    if line.is_empty() && lines.last().map_or(true, |l| l.is_empty()) {
+        return;
+    }
+
+    lines.push(line);
+}
+
+pub fn format_document(source: &str) -> Result<String, ParseError> {
+    let document: TOMLTable = parse_document(source)?;
+
+    let layout: DocumentLayout = get_document_layout(source);
+
+    // Start, end and the formatted text of every key and header
+    let mut items: Vec<(usize, usize, String)> = vec![];
+
+    for section in &layout.sections {
+        if let Some((start, end)) = section.header {
+            let header: String = source[start..end]
+                .chars()
+                .filter(|c| !c.is_whitespace())
+                .collect();
+
+            items.push((start, end, header));
+        }
+
+        for key in &section.keys {
+            let name: &str = match key.path.last() {
+                Some(PathSegment::Key(name)) => name.as_str(),
+                _ => continue,
+            };
+
+            let written: &str = &source[key.value_start..key.value_end];
+
+            let value: String = match document.get_node(&key.path) {
+                Some(TOMLNode::Value(value, _)) if !written.contains('\n') => {
+                    convert_ast_value_to_toml(value)
+                }
+                _ => String::from(written),
+            };
+
+            // Quotes are only kept on keys that need them
+            let name: String = format_path(&[PathSegment::Key(String::from(name))]);
+
+            items.push((key.start, key.value_end, format!("{} = {}", name, value)));
+        }
+    }
+
+    items.sort_by_key(|(start, _, _)| *start);
+
+    let mut lines: Vec<String> = vec![];
+    let mut line: String = String::new();
+    let mut cursor: usize = 0;
+
+    for (start, end, formatted) in
+        items
+            .iter()
+            .map(|(s, e, f)| (*s, *e, f.as_str()))
+            .chain([(source.len(), source.len(), "")])
+    {
+        // Whatever is between two items, the end of a line, empty lines, comments and
+        // indentation
+        let gap: Vec<&str> = source[cursor..start].split('\n').collect();
+
+        for (i, part) in gap.iter().enumerate() {
+            let part: &str = part.trim();
+
+            if i > 0 {
+                push_line(&mut lines, &mut line);
+            }
+
+            if part.starts_with('#') {
+                if !line.is_empty() {
+                    line.push(' ');
+                }
+
+                line += part;
+            }
+        }
+
+        line += formatted;
+        cursor = end;
+    }
+
+    push_line(&mut lines, &mut line);
+
+    while lines.last().map_or(false, |l| l.is_empty()) {
+        lines.pop();
+    }
+
+    let mut formatted: String = lines.join("\n");
+    formatted.push('\n');
+
+    return Ok(formatted);
+}
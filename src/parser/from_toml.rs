@@ -0,0 +1,327 @@
+use crate::parser::ast::AST;
+use crate::parser::path::{format_path, PathSegment};
+use crate::parser::token::Position;
+use crate::parser::tree::{parse_document, TOMLTable, TOMLTree};
+use std::fmt;
+use std::fs;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FromTomlError {
+    pub message: String,
+    pub field: Option<String>,
+    pub path: String,
+    pub position: Option<Position>,
+}
+
+impl FromTomlError {
+    pub fn new(message: &str, path: &[PathSegment], position: Option<Position>) -> Self {
+        return FromTomlError {
+            message: String::from(message),
+            field: None,
+            path: format_path(path),
+            position,
+        };
+    }
+}
+
+impl fmt::Display for FromTomlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+
+        if let Some(field) = &self.field {
+            write!(f, " for field `{}`", field)?;
+        }
+
+        if !self.path.is_empty() {
+            write!(f, " at `{}`", self.path)?;
+        }
+
+        if let Some(position) = &self.position {
+            write!(f, " (line {}, column {})", position.line, position.column)?;
+        }
+
+        return Ok(());
+    }
+}
+
+impl std::error::Error for FromTomlError {}
+
+pub trait FromToml: Sized {
+    fn from_toml(tree: &TOMLTree, path: &[PathSegment]) -> Result<Self, FromTomlError>;
+}
+
+pub fn from_toml_str<T: FromToml>(s: &str) -> Result<T, FromTomlError> {
+    let to_error = |message: String, position: Position| FromTomlError {
+        message,
+        field: None,
+        path: String::new(),
+        position: Some(position),
+    };
+
+    let table: TOMLTable = match parse_document(s) {
+        Ok(table) => table,
+        Err(e) => return Err(to_error(e.message, e.position)),
+    };
+
+    return T::from_toml(&TOMLTree::Table(table), &[]);
+}
+
+pub fn from_toml_file<T: FromToml>(filepath: &str) -> Result<T, FromTomlError> {
+    return match fs::read_to_string(filepath) {
+        Ok(content) => from_toml_str(content.as_str()),
+        Err(e) => Err(FromTomlError::new(
+            format!("Failed to read file `{}`: {}", filepath, e).as_str(),
+            &[],
+            None,
+        )),
+    };
+}
+
+// Used by `#[derive(FromToml)]`, `name` and `field` only show up in error messages
+pub fn get_table<'a>(
+    tree: &'a TOMLTree,
+    path: &[PathSegment],
+    name: &str,
+) -> Result<&'a TOMLTable, FromTomlError> {
+    return match tree {
+        TOMLTree::Table(table) => Ok(table),
+        _ => Err(FromTomlError::new(
+            format!("Expected a table for `{}`", name).as_str(),
+            path,
+            tree.get_position(),
+        )),
+    };
+}
+
+pub fn get_field<T: FromToml>(
+    table: &TOMLTable,
+    path: &[PathSegment],
+    key: &str,
+    field: &str,
+) -> Result<T, FromTomlError> {
+    let mut field_path: Vec<PathSegment> = path.to_vec();
+    field_path.push(PathSegment::Key(String::from(key)));
+
+    return match table.get(key) {
+        // The innermost field is the most precise, keep it
+        Some(tree) => T::from_toml(tree, &field_path).map_err(|mut e| {
+            e.field.get_or_insert_with(|| String::from(field));
+            e
+        }),
+        None => {
+            let mut e: FromTomlError =
+                FromTomlError::new("Missing value", &field_path, table.position);
+            e.field = Some(String::from(field));
+
+            Err(e)
+        }
+    };
+}
+
+pub fn get_field_or<T: FromToml>(
+    table: &TOMLTable,
+    path: &[PathSegment],
+    key: &str,
+    field: &str,
+    default: impl FnOnce() -> T,
+) -> Result<T, FromTomlError> {
+    if table.get(key).is_none() {
+        return Ok(default());
+    }
+
+    return get_field(table, path, key, field);
+}
+
+fn get_value<'a>(
+    tree: &'a TOMLTree,
+    path: &[PathSegment],
+    expected: &str,
+) -> Result<&'a AST, FromTomlError> {
+    return match tree {
+        TOMLTree::Value(ast, _) => Ok(ast),
+        _ => Err(FromTomlError::new(
+            format!("Expected {}, found a table", expected).as_str(),
+            path,
+            tree.get_position(),
+        )),
+    };
+}
+
+fn wrong_type(tree: &TOMLTree, path: &[PathSegment], expected: &str) -> FromTomlError {
+    return FromTomlError::new(
+        format!("Expected {}", expected).as_str(),
+        path,
+        tree.get_position(),
+    );
+}
+
+impl FromToml for bool {
+    fn from_toml(tree: &TOMLTree, path: &[PathSegment]) -> Result<Self, FromTomlError> {
+        return match get_value(tree, path, "a bool")? {
+            AST::ASTBool(v) => Ok(*v),
+            _ => Err(wrong_type(tree, path, "a bool")),
+        };
+    }
+}
+
+impl FromToml for String {
+    fn from_toml(tree: &TOMLTree, path: &[PathSegment]) -> Result<Self, FromTomlError> {
+        // Dates and times are read as strings
+        return match get_value(tree, path, "a string")? {
+            AST::ASTString(v) | AST::ASTDateTime(v) => Ok(v.clone()),
+            _ => Err(wrong_type(tree, path, "a string")),
+        };
+    }
+}
+
+macro_rules! impl_from_toml_for_int {
+    ($($t:ty),*) => {
+        $(
+            impl FromToml for $t {
+                fn from_toml(tree: &TOMLTree, path: &[PathSegment]) -> Result<Self, FromTomlError> {
+                    let v: i32 = match get_value(tree, path, "an integer")? {
+                        AST::ASTInt(v) => *v,
+                        _ => return Err(wrong_type(tree, path, "an integer")),
+                    };
+
+                    return <$t>::try_from(v).map_err(|_| {
+                        wrong_type(tree, path, format!("an integer fitting in `{}`", stringify!($t)).as_str())
+                    });
+                }
+            }
+        )*
+    };
+}
+
+impl_from_toml_for_int!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+impl<T: FromToml> FromToml for Option<T> {
+    fn from_toml(tree: &TOMLTree, path: &[PathSegment]) -> Result<Self, FromTomlError> {
+        // Missing keys are handled by `get_field_or`
+        return T::from_toml(tree, path).map(Some);
+    }
+}
+
+impl<T: FromToml> FromToml for Vec<T> {
+    fn from_toml(tree: &TOMLTree, path: &[PathSegment]) -> Result<Self, FromTomlError> {
+        let items: Vec<TOMLTree> = match tree {
+            TOMLTree::Value(AST::ASTArray(values), position) => values
+                .iter()
+                .map(|v| TOMLTree::Value(v.clone(), *position))
+                .collect(),
+            TOMLTree::TableArray(tables) => tables.iter().cloned().map(TOMLTree::Table).collect(),
+            _ => return Err(wrong_type(tree, path, "an array")),
+        };
+
+        let mut result: Vec<T> = Vec::with_capacity(items.len());
+
+        for (i, item) in items.iter().enumerate() {
+            let mut item_path: Vec<PathSegment> = path.to_vec();
+            item_path.push(PathSegment::Index(i));
+
+            result.push(T::from_toml(item, &item_path)?);
+        }
+
+        return Ok(result);
+    }
+}
+
+impl FromToml for TOMLTable {
+    fn from_toml(tree: &TOMLTree, path: &[PathSegment]) -> Result<Self, FromTomlError> {
+        return get_table(tree, path, "table").cloned();
+    }
+}
+
+impl FromToml for TOMLTree {
+    fn from_toml(tree: &TOMLTree, _path: &[PathSegment]) -> Result<Self, FromTomlError> {
+        return Ok(tree.clone());
+    }
+}
+
+#[cfg(all(test, feature = "derive"))]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, crate::FromToml)]
+    struct Server {
+        host: String,
+        #[toml(rename = "listen-port")]
+        port: u16,
+    }
+
+    #[derive(Debug, PartialEq, crate::FromToml)]
+    struct Config {
+        name: String,
+        timeout: Option<i32>,
+        #[toml(default)]
+        tags: Vec<String>,
+        server: Server,
+        replicas: Vec<Server>,
+    }
+
+    #[test]
+    fn nested_structs() {
+        let text: &str = "name = \"app\"\ntimeout = 30\n\n[server]\nhost = \"a\"\nlisten-port = 80\n\n[[replicas]]\nhost = \"b\"\nlisten-port = 81\n";
+        let config: Config = from_toml_str(text).unwrap();
+
+        assert_eq!(
+            config,
+            Config {
+                name: String::from("app"),
+                timeout: Some(30),
+                tags: vec![],
+                server: Server {
+                    host: String::from("a"),
+                    port: 80,
+                },
+                replicas: vec![Server {
+                    host: String::from("b"),
+                    port: 81,
+                }],
+            }
+        );
+    }
+
+    #[test]
+    fn missing_option_is_none() {
+        let text: &str =
+            "name = \"app\"\nreplicas = []\n\n[server]\nhost = \"a\"\nlisten-port = 80\n";
+        let config: Config = from_toml_str(text).unwrap();
+
+        assert_eq!(config.timeout, None);
+        assert!(config.replicas.is_empty());
+    }
+
+    #[test]
+    fn renamed_field_uses_key() {
+        // The field name isn't a key in the document
+        let error: FromTomlError =
+            from_toml_str::<Server>("host = \"a\"\nport = 80\n").unwrap_err();
+
+        assert_eq!(error.message, "Missing value");
+        assert_eq!(error.field.as_deref(), Some("Server.port"));
+        assert_eq!(error.path, "listen-port");
+    }
+
+    #[test]
+    fn missing_nested_field() {
+        let text: &str = "name = \"app\"\nreplicas = []\n\n[server]\nlisten-port = 80\n";
+        let error: FromTomlError = from_toml_str::<Config>(text).unwrap_err();
+
+        assert_eq!(error.message, "Missing value");
+        assert_eq!(error.field.as_deref(), Some("Server.host"));
+        assert_eq!(error.path, "server.host");
+        assert_eq!(error.position.map(|p| p.line), Some(4));
+    }
+
+    #[test]
+    fn wrong_type_in_array_of_tables() {
+        let text: &str = "name = \"app\"\n\n[server]\nhost = \"a\"\nlisten-port = 80\n\n[[replicas]]\nhost = \"b\"\nlisten-port = 100000\n";
+        let error: FromTomlError = from_toml_str::<Config>(text).unwrap_err();
+
+        assert_eq!(error.message, "Expected an integer fitting in `u16`");
+        assert_eq!(error.field.as_deref(), Some("Server.port"));
+        assert_eq!(error.path, "replicas[0].listen-port");
+        assert_eq!(error.position.map(|p| p.line), Some(9));
+    }
+}
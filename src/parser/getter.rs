@@ -0,0 +1,115 @@
+use crate::parser::ast::AST;
+use crate::parser::converter::{convert_string_to_ast, TOMLStringMap};
+
+// The getters return `None` if the tag or name doesn't exist, or the value has another type.
+impl TOMLStringMap {
+    pub fn get_value(&self, tag: &str, name: &str) -> Option<AST> {
+        let value: &String = self.tags.get(tag)?.values.get(name)?;
+
+        return convert_string_to_ast(value);
+    }
+
+    pub fn get_int(&self, tag: &str, name: &str) -> Option<i32> {
+        return match self.get_value(tag, name)? {
+            AST::ASTInt(v) => Some(v),
+            _ => None,
+        };
+    }
+
+    pub fn get_string(&self, tag: &str, name: &str) -> Option<String> {
+        return match self.get_value(tag, name)? {
+            AST::ASTString(v) => Some(v),
+            _ => None,
+        };
+    }
+
+    pub fn get_bool(&self, tag: &str, name: &str) -> Option<bool> {
+        return match self.get_value(tag, name)? {
+            AST::ASTBool(v) => Some(v),
+            _ => None,
+        };
+    }
+
+    pub fn get_datetime(&self, tag: &str, name: &str) -> Option<String> {
+        return match self.get_value(tag, name)? {
+            AST::ASTDateTime(v) => Some(v),
+            _ => None,
+        };
+    }
+
+    pub fn get_array(&self, tag: &str, name: &str) -> Option<Vec<AST>> {
+        return match self.get_value(tag, name)? {
+            AST::ASTArray(v) => Some(v),
+            _ => None,
+        };
+    }
+
+    pub fn get_int_array(&self, tag: &str, name: &str) -> Option<Vec<i32>> {
+        return self
+            .get_array(tag, name)?
+            .into_iter()
+            .map(|v| match v {
+                AST::ASTInt(v) => Some(v),
+                _ => None,
+            })
+            .collect();
+    }
+
+    pub fn get_string_array(&self, tag: &str, name: &str) -> Option<Vec<String>> {
+        return self
+            .get_array(tag, name)?
+            .into_iter()
+            .map(|v| match v {
+                AST::ASTString(v) => Some(v),
+                _ => None,
+            })
+            .collect();
+    }
+
+    pub fn get_bool_array(&self, tag: &str, name: &str) -> Option<Vec<bool>> {
+        return self
+            .get_array(tag, name)?
+            .into_iter()
+            .map(|v| match v {
+                AST::ASTBool(v) => Some(v),
+                _ => None,
+            })
+            .collect();
+    }
+
+    pub fn get_int_or(&self, tag: &str, name: &str, default: i32) -> i32 {
+        return self.get_int(tag, name).unwrap_or(default);
+    }
+
+    pub fn get_string_or(&self, tag: &str, name: &str, default: &str) -> String {
+        return self
+            .get_string(tag, name)
+            .unwrap_or_else(|| String::from(default));
+    }
+
+    pub fn get_bool_or(&self, tag: &str, name: &str, default: bool) -> bool {
+        return self.get_bool(tag, name).unwrap_or(default);
+    }
+
+    pub fn get_int_array_or(&self, tag: &str, name: &str, default: &[i32]) -> Vec<i32> {
+        return self
+            .get_int_array(tag, name)
+            .unwrap_or_else(|| default.to_vec());
+    }
+
+    pub fn get_string_array_or(&self, tag: &str, name: &str, default: &[&str]) -> Vec<String> {
+        return self
+            .get_string_array(tag, name)
+            .unwrap_or_else(|| default.iter().map(|s| String::from(*s)).collect());
+    }
+
+    pub fn get_bool_array_or(&self, tag: &str, name: &str, default: &[bool]) -> Vec<bool> {
+        return self
+            .get_bool_array(tag, name)
+            .unwrap_or_else(|| default.to_vec());
+    }
+
+    pub fn get_path_or(&self, path: &str, default: AST) -> AST {
+        return self.get_path(path).unwrap_or(default);
+    }
+}
@@ -0,0 +1,255 @@
+use crate::merge::{MergeOptions, MergedDocument};
+use crate::parser::ast::AST;
+use crate::parser::token::Position;
+use crate::parser::tree::{parse_document, TOMLTable, TOMLTree};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Opt-in includes. A global `include = "common.toml"` or `include = ["a.toml", "b.toml"]`
+// loads those files relative to the including file. Included files are layered in order
+// below the document that includes them, so its own values win.
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IncludeOptions {
+    pub key: String,
+    pub max_depth: usize,
+    // Includes outside this directory are refused
+    pub root: Option<PathBuf>,
+    pub merge: MergeOptions,
+}
+
+impl IncludeOptions {
+    pub fn new() -> Self {
+        return IncludeOptions {
+            key: String::from("include"),
+            max_depth: 16,
+            root: None,
+            merge: MergeOptions::new(),
+        };
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IncludeError {
+    pub file: String,
+    pub message: String,
+    pub position: Option<Position>,
+    // The include chain, innermost first, as file and position of the `include` key
+    pub included_from: Vec<(String, Position)>,
+}
+
+impl fmt::Display for IncludeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.file, self.message)?;
+
+        if let Some(position) = &self.position {
+            write!(f, " At line {}, column {}.", position.line, position.column)?;
+        }
+
+        for (file, position) in &self.included_from {
+            write!(
+                f,
+                "\n  included from {}:{}:{}",
+                file, position.line, position.column
+            )?;
+        }
+
+        return Ok(());
+    }
+}
+
+impl std::error::Error for IncludeError {}
+
+struct IncludeResolver<'a> {
+    options: &'a IncludeOptions,
+    root: Option<PathBuf>,
+    // Files being resolved, to find cycles
+    stack: Vec<PathBuf>,
+    chain: Vec<(String, Position)>,
+    merged: MergedDocument,
+}
+
+impl<'a> IncludeResolver<'a> {
+    fn error(&self, file: &str, message: &str, position: Option<Position>) -> IncludeError {
+        return IncludeError {
+            file: String::from(file),
+            message: String::from(message),
+            position,
+            included_from: self.chain.iter().rev().cloned().collect(),
+        };
+    }
+
+    fn get_includes(
+        &self,
+        file: &str,
+        document: &mut TOMLTable,
+    ) -> Result<(Vec<String>, Position), IncludeError> {
+        let (value, position) = match document.remove(self.options.key.as_str()) {
+            None => return Ok((vec![], Position::new())),
+            Some(TOMLTree::Value(value, position)) => (value, position.unwrap_or(Position::new())),
+            Some(tree) => {
+                return Err(self.error(
+                    file,
+                    format!(
+                        "`{}` must be a string or an array of strings.",
+                        self.options.key
+                    )
+                    .as_str(),
+                    tree.get_position(),
+                ));
+            }
+        };
+
+        let includes: Option<Vec<String>> = match value {
+            AST::ASTString(v) => Some(vec![v]),
+            AST::ASTArray(values) => values
+                .into_iter()
+                .map(|v| match v {
+                    AST::ASTString(v) => Some(v),
+                    _ => None,
+                })
+                .collect(),
+            _ => None,
+        };
+
+        return match includes {
+            Some(includes) => Ok((includes, position)),
+            None => Err(self.error(
+                file,
+                format!(
+                    "`{}` must be a string or an array of strings.",
+                    self.options.key
+                )
+                .as_str(),
+                Some(position),
+            )),
+        };
+    }
+
+    // Problems with an include are reported at the `include` key of the file including it
+    fn include_error(&self, file: &str, message: &str) -> IncludeError {
+        return match self.chain.split_last() {
+            Some(((parent, position), chain)) => IncludeError {
+                file: parent.clone(),
+                message: String::from(message),
+                position: Some(*position),
+                included_from: chain.iter().rev().cloned().collect(),
+            },
+            None => self.error(file, message, None),
+        };
+    }
+
+    fn resolve(&mut self, path: &Path) -> Result<(), IncludeError> {
+        let file: String = path.display().to_string();
+
+        let canonical: PathBuf = match fs::canonicalize(path) {
+            Ok(canonical) => canonical,
+            Err(e) => {
+                return Err(self.include_error(
+                    file.as_str(),
+                    format!("Failed to read `{}`: {}.", file, e).as_str(),
+                ));
+            }
+        };
+
+        if let Some(root) = &self.root {
+            if !canonical.starts_with(root) {
+                return Err(self.include_error(
+                    file.as_str(),
+                    format!("`{}` is outside of `{}`.", file, root.display()).as_str(),
+                ));
+            }
+        }
+
+        if self.stack.contains(&canonical) {
+            return Err(self.include_error(
+                file.as_str(),
+                format!("Including `{}` creates a cycle.", file).as_str(),
+            ));
+        }
+
+        if self.stack.len() > self.options.max_depth {
+            return Err(self.include_error(
+                file.as_str(),
+                format!(
+                    "Including `{}` goes over the maximum depth of {}.",
+                    file, self.options.max_depth
+                )
+                .as_str(),
+            ));
+        }
+
+        let content: String = match fs::read_to_string(&canonical) {
+            Ok(content) => content,
+            Err(e) => {
+                return Err(self.include_error(
+                    file.as_str(),
+                    format!("Failed to read `{}`: {}.", file, e).as_str(),
+                ));
+            }
+        };
+
+        let mut document: TOMLTable = match parse_document(content.as_str()) {
+            Ok(document) => document,
+            Err(e) => {
+                return Err(self.error(file.as_str(), e.message.as_str(), Some(e.position)));
+            }
+        };
+
+        let (includes, include_position) = self.get_includes(file.as_str(), &mut document)?;
+
+        self.stack.push(canonical);
+        self.chain.push((file.clone(), include_position));
+
+        // Relative to the including file
+        let directory: PathBuf = path.parent().map(Path::to_path_buf).unwrap_or_default();
+
+        for include in includes {
+            self.resolve(&directory.join(include))?;
+        }
+
+        self.chain.pop();
+        self.stack.pop();
+
+        let options: &MergeOptions = &self.options.merge;
+
+        if let Err(e) = self.merged.merge(file.as_str(), &document, options) {
+            return Err(self.error(file.as_str(), e.to_string().as_str(), None));
+        }
+
+        return Ok(());
+    }
+}
+
+pub fn parse_file_with_includes<P: AsRef<Path>>(
+    path: P,
+    options: &IncludeOptions,
+) -> Result<MergedDocument, IncludeError> {
+    let root: Option<PathBuf> = match &options.root {
+        Some(root) => match fs::canonicalize(root) {
+            Ok(root) => Some(root),
+            Err(e) => {
+                return Err(IncludeError {
+                    file: root.display().to_string(),
+                    message: format!("Invalid root: {}", e),
+                    position: None,
+                    included_from: vec![],
+                });
+            }
+        },
+        None => None,
+    };
+
+    let mut resolver: IncludeResolver = IncludeResolver {
+        options,
+        root,
+        stack: vec![],
+        chain: vec![],
+        merged: MergedDocument::new(),
+    };
+
+    resolver.resolve(path.as_ref())?;
+
+    return Ok(resolver.merged);
+}
@@ -0,0 +1,375 @@
+use crate::parser::ast::AST;
+use crate::parser::converter::{convert_ast_to_string_recovering, TOMLStringMap};
+use crate::parser::error::ParseError;
+use crate::parser::parser::Parser;
+use crate::parser::token::Position;
+use crate::parser::tree::TOMLTable;
+
+// Replaces the bytes from `start` to `end` with `text`
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextEdit {
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+}
+
+impl TextEdit {
+    pub fn new(start: usize, end: usize, text: &str) -> Self {
+        return TextEdit {
+            start,
+            end,
+            text: String::from(text),
+        };
+    }
+}
+
+// The statements from a table header or an empty line up to the next one. The first
+// section starts at the start of the document.
+#[derive(Debug, Clone)]
+struct Section {
+    // Start of the line of the first statement
+    start: Position,
+    statements: Vec<AST>,
+    errors: Vec<ParseError>,
+}
+
+// A recovering parse kept up to date with edits. An edit reparses the sections it touches
+// and the ones after it until the parse reaches a section that starts where it did before,
+// the sections from there on are kept and only moved. The result is always the same as
+// `Parser::parse_recovering` on the whole text.
+pub struct IncrementalParse {
+    text: String,
+    sections: Vec<Section>,
+    // Sections the last edit parsed again
+    reparsed: usize,
+}
+
+impl IncrementalParse {
+    pub fn new(text: &str) -> Self {
+        let sections: Vec<Section> = parse_sections(text, Position::new(), None).0;
+
+        return IncrementalParse {
+            text: String::from(text),
+            reparsed: sections.len(),
+            sections,
+        };
+    }
+
+    pub fn get_text(&self) -> &str {
+        return self.text.as_str();
+    }
+
+    pub fn get_ast(&self) -> AST {
+        return AST::ASTCompound(
+            self.sections
+                .iter()
+                .flat_map(|s| s.statements.iter().cloned())
+                .collect(),
+        );
+    }
+
+    pub fn get_errors(&self) -> Vec<ParseError> {
+        return self
+            .sections
+            .iter()
+            .flat_map(|s| s.errors.iter().cloned())
+            .collect();
+    }
+
+    // The partial document and every error, like `parse_document_recovering`
+    pub fn get_document(&self) -> (TOMLTable, Vec<ParseError>) {
+        let mut errors: Vec<ParseError> = self.get_errors();
+
+        let (map, conversion_errors): (TOMLStringMap, Vec<ParseError>) =
+            convert_ast_to_string_recovering(&self.get_ast(), true);
+        let (document, tree_errors): (TOMLTable, Vec<ParseError>) = map.to_tree_recovering();
+
+        errors.extend(conversion_errors);
+        errors.extend(tree_errors);
+
+        errors.sort_by_key(|e| e.position.offset);
+
+        return (document, errors);
+    }
+
+    pub fn get_section_count(&self) -> usize {
+        return self.sections.len();
+    }
+
+    pub fn get_reparsed_count(&self) -> usize {
+        return self.reparsed;
+    }
+
+    pub fn apply_edit(&mut self, edit: &TextEdit) -> Result<(), String> {
+        if edit.start > edit.end
+            || edit.end > self.text.len()
+            || !self.text.is_char_boundary(edit.start)
+            || !self.text.is_char_boundary(edit.end)
+        {
+            return Err(format!(
+                "Invalid edit range {}..{} for a text of {} bytes.",
+                edit.start,
+                edit.end,
+                self.text.len()
+            ));
+        }
+
+        // The section before the edited one is parsed again too, a changed line at the start
+        // of a section can decide where the statement before it ends
+        let edited: usize = self
+            .sections
+            .partition_point(|s| s.start.offset <= edit.start)
+            .saturating_sub(1);
+        let first: usize = edited.saturating_sub(1);
+
+        let offset_delta: isize = edit.text.len() as isize - (edit.end - edit.start) as isize;
+        let line_delta: isize = edit.text.matches('\n').count() as isize
+            - self.text[edit.start..edit.end].matches('\n').count() as isize;
+
+        self.text
+            .replace_range(edit.start..edit.end, edit.text.as_str());
+
+        // Sections after the edit that can be kept, by their start in the new text
+        let resume: Resume = Resume {
+            sections: &self.sections[first + 1..],
+            edit_end: edit.end,
+            new_edit_end: edit.start + edit.text.len(),
+            offset_delta,
+        };
+
+        let (mut parsed, kept) =
+            parse_sections(self.text.as_str(), self.sections[first].start, Some(resume));
+
+        self.reparsed = parsed.len();
+
+        let mut rest: Vec<Section> = match kept {
+            Some(index) => self.sections.split_off(first + 1 + index),
+            None => vec![],
+        };
+
+        for section in rest.iter_mut() {
+            shift_section(section, offset_delta, line_delta);
+        }
+
+        self.sections.truncate(first);
+        self.sections.append(&mut parsed);
+        self.sections.append(&mut rest);
+
+        return Ok(());
+    }
+}
+
+// Where a reparse can stop and go on with the old sections
+struct Resume<'a> {
+    sections: &'a [Section],
+    // End of the edit in the old text and in the new one
+    edit_end: usize,
+    new_edit_end: usize,
+    offset_delta: isize,
+}
+
+impl Resume<'_> {
+    // The old section that starts at `start` of the new text. Its whole first line has to
+    // be after the edit, so the statements in it are the same apart from where they are.
+    fn find(&self, start: usize) -> Option<usize> {
+        if start < self.new_edit_end {
+            return None;
+        }
+
+        let old: usize = (start as isize - self.offset_delta) as usize;
+
+        if old < self.edit_end {
+            return None;
+        }
+
+        return self
+            .sections
+            .binary_search_by_key(&old, |s| s.start.offset)
+            .ok();
+    }
+}
+
+// Start of the line of `position`
+fn get_line_start(text: &str, position: Position) -> Position {
+    return Position {
+        line: position.line,
+        column: 1,
+        offset: text[..position.offset].rfind('\n').map_or(0, |i| i + 1),
+    };
+}
+
+fn is_section_start(statement: &AST) -> bool {
+    return matches!(
+        statement,
+        AST::ASTTagDefinition(_, _) | AST::ASTTagArrayDefinition(_, _) | AST::ASTSeparator()
+    );
+}
+
+// Sections of `text` from `start`, up to the end or the first one `resume` has. The index
+// of that one in `resume.sections` is returned with them.
+fn parse_sections(
+    text: &str,
+    start: Position,
+    resume: Option<Resume>,
+) -> (Vec<Section>, Option<usize>) {
+    let mut parser: Parser = Parser::from_string_at(text, start);
+    let mut sections: Vec<Section> = vec![Section {
+        start,
+        statements: vec![],
+        errors: vec![],
+    }];
+
+    while let Some((statement, position, error)) = parser.parse_next_recovering() {
+        let current: &Section = &sections[sections.len() - 1];
+
+        if is_section_start(&statement) && !current.statements.is_empty() {
+            let line_start: Position = get_line_start(text, position);
+
+            if let Some(index) = resume.as_ref().and_then(|r| r.find(line_start.offset)) {
+                return (sections, Some(index));
+            }
+
+            sections.push(Section {
+                start: line_start,
+                statements: vec![],
+                errors: vec![],
+            });
+        }
+
+        let current: &mut Section = sections.last_mut().unwrap();
+
+        current.statements.push(statement);
+        current.errors.extend(error);
+    }
+
+    return (sections, None);
+}
+
+fn shift_position(position: &mut Position, offset_delta: isize, line_delta: isize) {
+    position.offset = (position.offset as isize + offset_delta) as usize;
+    position.line = (position.line as isize + line_delta) as usize;
+}
+
+// Moves a kept section to where it is after an edit before it
+fn shift_section(section: &mut Section, offset_delta: isize, line_delta: isize) {
+    shift_position(&mut section.start, offset_delta, line_delta);
+
+    for statement in section.statements.iter_mut() {
+        match statement {
+            AST::ASTTagDefinition(_, position)
+            | AST::ASTTagArrayDefinition(_, position)
+            | AST::ASTVariableDefinition(_, _, position)
+            | AST::ASTError(_, position) => shift_position(position, offset_delta, line_delta),
+            _ => {}
+        }
+    }
+
+    for error in section.errors.iter_mut() {
+        shift_position(&mut error.position, offset_delta, line_delta);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parser::parse_document_recovering;
+
+    const DOCUMENT: &str = "a = 1\n\n[b]\nx = 1\ny = \"two\"\n\n[c]\nz = [1, 2]\n\n[d]\nw = true\n";
+
+    fn assert_full_parse(parse: &IncrementalParse) {
+        let (ast, errors) = Parser::from_string(parse.get_text()).parse_recovering();
+
+        assert_eq!(parse.get_ast(), ast);
+        assert_eq!(parse.get_errors(), errors);
+        assert_eq!(
+            parse.get_document().1,
+            parse_document_recovering(parse.get_text()).1
+        );
+    }
+
+    fn apply(parse: &mut IncrementalParse, search: &str, text: &str) {
+        let start: usize = parse.get_text().find(search).unwrap();
+        let edit: TextEdit = TextEdit::new(start, start + search.len(), text);
+
+        parse.apply_edit(&edit).unwrap();
+        assert_full_parse(parse);
+    }
+
+    #[test]
+    fn insert_key() {
+        let mut parse: IncrementalParse = IncrementalParse::new(DOCUMENT);
+        assert_full_parse(&parse);
+
+        apply(&mut parse, "x = 1\n", "x = 1\nnew = [true]\n");
+
+        assert!(parse.get_reparsed_count() < parse.get_section_count());
+        assert!(parse.get_text().contains("new = [true]"));
+    }
+
+    #[test]
+    fn insert_lines_and_sections() {
+        let mut parse: IncrementalParse = IncrementalParse::new(DOCUMENT);
+
+        apply(&mut parse, "\n[c]", "\n[e]\nv = 1\n\n[c]");
+        apply(&mut parse, "a = 1\n", "a = 1\n\n\n");
+
+        assert_eq!(
+            parse.get_section_count(),
+            IncrementalParse::new(parse.get_text()).get_section_count()
+        );
+    }
+
+    #[test]
+    fn delete_lines() {
+        let mut parse: IncrementalParse = IncrementalParse::new(DOCUMENT);
+
+        apply(&mut parse, "y = \"two\"\n", "");
+        apply(&mut parse, "\n[d]\nw = true\n", "");
+        apply(&mut parse, "\n\n[b]", "");
+    }
+
+    #[test]
+    fn change_headers() {
+        let mut parse: IncrementalParse = IncrementalParse::new(DOCUMENT);
+
+        apply(&mut parse, "[c]", "[[c]]");
+        apply(&mut parse, "[[c]]", "c = 1");
+        apply(&mut parse, "[b]", "[c]");
+        apply(&mut parse, "[d]", "[b]");
+    }
+
+    #[test]
+    fn break_and_fix() {
+        let mut parse: IncrementalParse = IncrementalParse::new(DOCUMENT);
+
+        apply(&mut parse, "[b]", "[b");
+        assert_eq!(parse.get_errors().len(), 1);
+
+        apply(&mut parse, "z = [1, 2]", "z = [1, ");
+        assert_eq!(parse.get_errors().len(), 2);
+
+        apply(&mut parse, "[b", "[b]");
+        apply(&mut parse, "z = [1, ", "z = [1, 2]");
+        assert!(parse.get_errors().is_empty());
+        assert_eq!(parse.get_text(), DOCUMENT);
+    }
+
+
+    #[test]
+    fn duplicate_keys() {
+        let mut parse: IncrementalParse = IncrementalParse::new(DOCUMENT);
+
+        apply(&mut parse, "y = \"two\"", "x = \"two\"");
+        assert_eq!(parse.get_document().1.len(), 1);
+    }
+
+    #[test]
+    fn invalid_edit() {
+        let mut parse: IncrementalParse = IncrementalParse::new("k = \"é\"\n");
+
+        assert!(parse.apply_edit(&TextEdit::new(6, 7, "")).is_err());
+        assert!(parse.apply_edit(&TextEdit::new(2, 1, "")).is_err());
+        assert!(parse.apply_edit(&TextEdit::new(0, 100, "")).is_err());
+        assert_eq!(parse.get_text(), "k = \"é\"\n");
+    }
+}
@@ -0,0 +1,279 @@
+use crate::parser::ast::AST;
+use crate::parser::path::{format_path, parse_path, PathSegment};
+use crate::parser::token::Position;
+use crate::parser::tree::{TOMLNode, TOMLTable, TOMLTree};
+use std::collections::HashMap;
+use std::fmt;
+
+// Opt-in interpolation of string values after parsing. `${server.host}` is replaced by
+// the value at that path, `${env:HOME}` by an environment variable and `${env:PORT:-8080}`
+// falls back to `8080` when it isn't set, defaults work for paths too. `$${` is a literal
+// `${`. Strings inside arrays are interpolated, other values are left as they are.
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct InterpolationOptions {
+    // Allows `${env:NAME}`
+    pub env: bool,
+    // Looked up before the process environment
+    pub variables: HashMap<String, String>,
+}
+
+impl InterpolationOptions {
+    pub fn new() -> Self {
+        return InterpolationOptions {
+            env: true,
+            variables: HashMap::new(),
+        };
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct InterpolationError {
+    pub path: String,
+    pub message: String,
+    // Position of the key holding the string
+    pub position: Option<Position>,
+    // Start and end of the reference in characters of the string
+    pub span: (usize, usize),
+}
+
+impl fmt::Display for InterpolationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "`{}` (characters {}..{}): {}",
+            self.path, self.span.0, self.span.1, self.message
+        )?;
+
+        if let Some(position) = &self.position {
+            write!(f, " At line {}, column {}.", position.line, position.column)?;
+        }
+
+        return Ok(());
+    }
+}
+
+impl std::error::Error for InterpolationError {}
+
+struct Interpolator<'a> {
+    options: &'a InterpolationOptions,
+    document: &'a TOMLTable,
+    resolved: HashMap<String, String>,
+    // Paths being resolved, to find cycles
+    stack: Vec<String>,
+}
+
+impl<'a> Interpolator<'a> {
+    fn lookup_env(&self, name: &str) -> Option<String> {
+        if let Some(value) = self.options.variables.get(name) {
+            return Some(value.clone());
+        }
+
+        return std::env::var(name).ok();
+    }
+
+    fn lookup_path(
+        &mut self,
+        reference: &str,
+        error: &dyn Fn(&str) -> InterpolationError,
+    ) -> Result<Option<String>, InterpolationError> {
+        let segments: Vec<PathSegment> = match parse_path(reference) {
+            Ok(segments) => segments,
+            Err(e) => return Err(error(e.to_string().as_str())),
+        };
+
+        let document: &TOMLTable = self.document;
+
+        return match document.get_node(&segments) {
+            None | Some(TOMLNode::Table(_) | TOMLNode::TableArray(_)) => Ok(None),
+            Some(TOMLNode::Value(AST::ASTString(value), position)) => {
+                Ok(Some(self.resolve(&segments, value, position)?))
+            }
+            Some(TOMLNode::Value(AST::ASTInt(value), _)) => Ok(Some(value.to_string())),
+            Some(TOMLNode::Value(AST::ASTBool(value), _)) => Ok(Some(value.to_string())),
+            Some(TOMLNode::Value(AST::ASTDateTime(value), _)) => Ok(Some(value.clone())),
+            Some(_) => Err(error(
+                format!("`{}` can't be interpolated into a string.", reference).as_str(),
+            )),
+        };
+    }
+
+    // Interpolates the string at `path`, each string is resolved once
+    fn resolve(
+        &mut self,
+        path: &[PathSegment],
+        value: &str,
+        position: Option<Position>,
+    ) -> Result<String, InterpolationError> {
+        let name: String = format_path(path);
+
+        if let Some(resolved) = self.resolved.get(&name) {
+            return Ok(resolved.clone());
+        }
+
+        if let Some(start) = self.stack.iter().position(|p| p == &name) {
+            let mut cycle: Vec<String> = self.stack[start..].to_vec();
+            cycle.push(name.clone());
+
+            return Err(InterpolationError {
+                path: name,
+                message: format!("Interpolation cycle `{}`.", cycle.join("` -> `")),
+                position,
+                span: (0, value.chars().count()),
+            });
+        }
+
+        self.stack.push(name.clone());
+
+        let chars: Vec<char> = value.chars().collect();
+        let mut result: String = String::new();
+        let mut i: usize = 0;
+
+        while i < chars.len() {
+            if chars[i] == '$' && chars.get(i + 1) == Some(&'$') && chars.get(i + 2) == Some(&'{') {
+                result += "${";
+                i += 3;
+                continue;
+            }
+
+            if chars[i] != '$' || chars.get(i + 1) != Some(&'{') {
+                result.push(chars[i]);
+                i += 1;
+                continue;
+            }
+
+            let end: usize = match chars[i + 2..].iter().position(|c| *c == '}') {
+                Some(end) => i + 2 + end,
+                None => {
+                    return Err(InterpolationError {
+                        path: name,
+                        message: String::from("Unclosed `${`."),
+                        position,
+                        span: (i, chars.len()),
+                    });
+                }
+            };
+
+            let span: (usize, usize) = (i, end + 1);
+            let inner: String = chars[i + 2..end].iter().collect();
+
+            let error = |message: &str| InterpolationError {
+                path: name.clone(),
+                message: String::from(message),
+                position,
+                span,
+            };
+
+            let (reference, default) = match inner.split_once(":-") {
+                Some((reference, default)) => (reference, Some(default)),
+                None => (inner.as_str(), None),
+            };
+
+            let found: Option<String> = if let Some(variable) = reference.strip_prefix("env:") {
+                if !self.options.env {
+                    return Err(error("Environment variables aren't allowed."));
+                }
+
+                self.lookup_env(variable)
+            } else if reference.is_empty() {
+                return Err(error("Empty `${}`."));
+            } else {
+                self.lookup_path(reference, &error)?
+            };
+
+            result += match (&found, default) {
+                (Some(found), _) => found.as_str(),
+                (None, Some(default)) => default,
+                (None, None) if reference.starts_with("env:") => {
+                    return Err(error(
+                        format!("Environment variable `{}` isn't set.", &reference[4..]).as_str(),
+                    ));
+                }
+                (None, None) => {
+                    return Err(error(
+                        format!("`{}` doesn't name a value.", reference).as_str(),
+                    ));
+                }
+            };
+
+            i = end + 1;
+        }
+
+        self.stack.pop();
+        self.resolved.insert(name, result.clone());
+
+        return Ok(result);
+    }
+
+    fn interpolate_table(
+        &mut self,
+        table: &TOMLTable,
+        path: &mut Vec<PathSegment>,
+    ) -> Result<TOMLTable, InterpolationError> {
+        let mut interpolated: TOMLTable = TOMLTable {
+            entries: vec![],
+            position: table.position,
+        };
+
+        for (key, tree) in &table.entries {
+            path.push(PathSegment::Key(key.clone()));
+
+            let tree: TOMLTree = match tree {
+                TOMLTree::Value(AST::ASTString(value), position) => TOMLTree::Value(
+                    AST::ASTString(self.resolve(path, value, *position)?),
+                    *position,
+                ),
+                TOMLTree::Value(AST::ASTArray(values), position) => {
+                    let mut array: Vec<AST> = vec![];
+
+                    for (i, value) in values.iter().enumerate() {
+                        array.push(match value {
+                            AST::ASTString(value) => {
+                                path.push(PathSegment::Index(i));
+                                let value: String = self.resolve(path, value, *position)?;
+                                path.pop();
+
+                                AST::ASTString(value)
+                            }
+                            value => value.clone(),
+                        });
+                    }
+
+                    TOMLTree::Value(AST::ASTArray(array), *position)
+                }
+                TOMLTree::Value(value, position) => TOMLTree::Value(value.clone(), *position),
+                TOMLTree::Table(table) => TOMLTree::Table(self.interpolate_table(table, path)?),
+                TOMLTree::TableArray(tables) => {
+                    let mut array: Vec<TOMLTable> = vec![];
+
+                    for (i, table) in tables.iter().enumerate() {
+                        path.push(PathSegment::Index(i));
+                        array.push(self.interpolate_table(table, path)?);
+                        path.pop();
+                    }
+
+                    TOMLTree::TableArray(array)
+                }
+            };
+
+            interpolated.entries.push((key.clone(), tree));
+            path.pop();
+        }
+
+        return Ok(interpolated);
+    }
+}
+
+pub fn interpolate_document(
+    document: &TOMLTable,
+    options: &InterpolationOptions,
+) -> Result<TOMLTable, InterpolationError> {
+    let mut interpolator: Interpolator = Interpolator {
+        options,
+        document,
+        resolved: HashMap::new(),
+        stack: vec![],
+    };
+
+    return interpolator.interpolate_table(document, &mut vec![]);
+}
@@ -0,0 +1,204 @@
+use crate::parser::converter::resolve_tag_name;
+use crate::parser::lexer::Lexer;
+use crate::parser::path::PathSegment;
+use crate::parser::token::{Token, TokenType};
+use crate::parser::tree::split_tag_name;
+use std::collections::HashMap;
+
+// Where keys and tables are in the source, as byte offsets, so the text can be edited
+// without rewriting the parts that don't change. It follows the lexer and the converter,
+// an empty line ends a table and the keys after it are global again.
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyLayout {
+    pub path: Vec<PathSegment>,
+    // Start of the key
+    pub start: usize,
+    pub value_start: usize,
+    pub value_end: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SectionLayout {
+    // Empty for global keys
+    pub path: Vec<PathSegment>,
+    // Start and end of `[name]` or `[[name]]`, global keys have no header
+    pub header: Option<(usize, usize)>,
+    // End of the header or of the last value
+    pub end: usize,
+    pub keys: Vec<KeyLayout>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocumentLayout {
+    pub sections: Vec<SectionLayout>,
+}
+
+impl DocumentLayout {
+    pub fn get_key(&self, path: &[PathSegment]) -> Option<&KeyLayout> {
+        return self
+            .sections
+            .iter()
+            .flat_map(|s| s.keys.iter())
+            .find(|k| k.path == path);
+    }
+
+    // The section defining the table at `path`
+    pub fn get_section(&self, path: &[PathSegment]) -> Option<&SectionLayout> {
+        return self
+            .sections
+            .iter()
+            .find(|s| s.header.is_some() && s.path == path);
+    }
+}
+
+pub(crate) fn convert_tag_name_to_path(name: &str) -> Vec<PathSegment> {
+    let mut path: Vec<PathSegment> = vec![];
+
+    for (key, index) in split_tag_name(name) {
+        path.push(PathSegment::Key(String::from(key)));
+
+        if let Some(index) = index {
+            path.push(PathSegment::Index(index));
+        }
+    }
+
+    return path;
+}
+
+// Token values are the source between their start and end, without the quotes of strings
+pub(crate) fn get_token_end(token: &Token) -> usize {
+    let length: usize = token.value.len();
+
+    return match token.token_type {
+        TokenType::TokenString => token.position.offset + length + 2,
+        _ => token.position.offset + length,
+    };
+}
+
+// Expects a document that parses
+pub fn get_document_layout(source: &str) -> DocumentLayout {
+    let mut lexer: Lexer = Lexer::from_string(source);
+    let mut tokens: Vec<Token> = vec![];
+
+    while let Some(token) = lexer.get_next_token() {
+        tokens.push(token);
+    }
+
+    let mut sections: Vec<SectionLayout> = vec![SectionLayout {
+        path: vec![],
+        header: None,
+        end: 0,
+        keys: vec![],
+    }];
+
+    let mut tag_arrays: HashMap<String, usize> = HashMap::new();
+    let mut at_line_start: bool = true;
+    let mut i: usize = 0;
+
+    while i < tokens.len() {
+        let token: &Token = &tokens[i];
+
+        match token.token_type {
+            TokenType::TokenNewLine => {
+                // An empty line
+                if at_line_start && i > 0 {
+                    sections.push(SectionLayout {
+                        path: vec![],
+                        header: None,
+                        end: token.position.offset,
+                        keys: vec![],
+                    });
+                }
+
+                at_line_start = true;
+                i += 1;
+            }
+            TokenType::TokenLBracket => {
+                let is_array: bool = tokens
+                    .get(i + 1)
+                    .map_or(false, |t| t.token_type == TokenType::TokenLBracket);
+
+                let name_index: usize = if is_array { i + 2 } else { i + 1 };
+                let end_index: usize = if is_array { i + 4 } else { i + 2 };
+
+                let (name, end) = match (tokens.get(name_index), tokens.get(end_index)) {
+                    (Some(name), Some(end)) => (name.value, end.position.offset + 1),
+                    _ => break,
+                };
+
+                let tag: String = if is_array {
+                    let array: String = resolve_tag_name(name, &tag_arrays, false);
+                    let count: &mut usize = tag_arrays.entry(array.clone()).or_insert(0);
+                    let tag: String = format!("{}[{}]", array, count);
+                    *count += 1;
+                    tag
+                } else {
+                    resolve_tag_name(name, &tag_arrays, true)
+                };
+
+                sections.push(SectionLayout {
+                    path: convert_tag_name_to_path(tag.as_str()),
+                    header: Some((token.position.offset, end)),
+                    end,
+                    keys: vec![],
+                });
+
+                at_line_start = false;
+                i = end_index + 1;
+            }
+            // A key, bare or quoted
+            TokenType::TokenID | TokenType::TokenString => {
+                let start: usize = token.position.offset;
+                let key: String = token.value.to_string();
+
+                // Skip the key and the equals sign
+                i += 2;
+
+                let value_start: usize = match tokens.get(i) {
+                    Some(value) => value.position.offset,
+                    None => break,
+                };
+
+                let mut depth: usize = 0;
+                let mut value_end: usize = value_start;
+
+                // Arrays run to their closing bracket and can span lines
+                while let Some(value) = tokens.get(i) {
+                    match value.token_type {
+                        TokenType::TokenLBracket => depth += 1,
+                        TokenType::TokenRBracket => depth -= 1,
+                        _ => {}
+                    }
+
+                    value_end = get_token_end(value);
+                    i += 1;
+
+                    if depth == 0 {
+                        break;
+                    }
+                }
+
+                let section: &mut SectionLayout = sections.last_mut().unwrap();
+                let mut path: Vec<PathSegment> = section.path.clone();
+                path.push(PathSegment::Key(key));
+
+                section.end = value_end;
+                section.keys.push(KeyLayout {
+                    path,
+                    start,
+                    value_start,
+                    value_end,
+                });
+
+                at_line_start = false;
+            }
+            _ => {
+                at_line_start = false;
+                i += 1;
+            }
+        }
+    }
+
+    return DocumentLayout { sections };
+}
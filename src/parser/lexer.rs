@@ -1,91 +1,157 @@
 use super::token;
-use crate::parser::token::{Token, TokenType};
+use crate::parser::token::{Position, Token, TokenType};
 
-use std::fs;
-
-pub struct Lexer {
+pub struct Lexer<'a> {
     current: char,
     ptr: usize,
-    content: Vec<u8>,
-    pub tokens: Vec<Token>,
+    position: Position,
+    // Tokens are slices of it
+    content: &'a str,
+    pub tokens: Vec<Token<'a>>,
 }
 
-impl Lexer {
-    pub fn new(filepath: &str) -> Self {
-        let mut lexer = Lexer {
-            current: '\0',
+impl<'a> Lexer<'a> {
+    pub fn from_string(content: &'a str) -> Self {
+        return Lexer {
+            current: content.chars().next().unwrap_or('\0'),
             ptr: 0,
-            content: fs::read(filepath).expect("[ERROR] Failed to read file."),
+            position: Position::new(),
+            content,
             tokens: vec![],
         };
+    }
 
-        lexer.current = lexer.content[0] as char;
+    // Lexes `content` from `position`, which has to be the start of a line
+    pub fn from_string_at(content: &'a str, position: Position) -> Self {
+        let mut lexer: Lexer = Lexer::from_string(content);
+
+        lexer.ptr = position.offset.min(content.len());
+        lexer.current = content[lexer.ptr..].chars().next().unwrap_or('\0');
+        lexer.position = position;
 
         return lexer;
     }
 
+    // Where the next token starts, the end of the content once it's all read
+    pub fn get_position(&self) -> Position {
+        return self.position;
+    }
+
     fn advance(&mut self) {
-        if self.ptr < self.content.len() - 1 {
-            self.ptr += 1;
-            self.current = char::from(self.content[self.ptr]);
+        if self.current == '\0' {
+            return;
+        }
+
+        // Track the position of the current character, columns count characters and
+        // offsets bytes
+        if self.current == '\n' {
+            self.position.line += 1;
+            self.position.column = 1;
         } else {
-            self.current = '\0'
+            self.position.column += 1;
         }
+
+        self.ptr += self.current.len_utf8();
+        self.position.offset = self.ptr;
+        self.current = self.content[self.ptr..].chars().next().unwrap_or('\0');
     }
 
-    fn push_token(&mut self, t: TokenType, v: &str) {
+    fn push_token(&mut self, t: TokenType, v: &'a str) {
         self.tokens.push(Token::new(t, v));
     }
 
     fn skip_whitespace(&mut self) {
-        while (self.current == ' ' || self.current == '\t') && self.ptr != self.content.len() {
+        while (self.current == ' ' || self.current == '\t' || self.current == '\r')
+            && self.ptr != self.content.len()
+        {
             self.advance();
         }
     }
 
-    fn get_string(&mut self) -> Token {
-        self.advance();
+    // Only whitespace before the current character on its line
+    fn is_line_start(&self) -> bool {
+        return self.content.as_bytes()[..self.ptr]
+            .iter()
+            .rev()
+            .take_while(|c| **c != b'\n')
+            .all(|c| *c == b' ' || *c == b'\t' || *c == b'\r');
+    }
 
-        let mut string: String = String::new();
+    fn get_string(&mut self) -> Token<'a> {
+        let start: Position = self.position;
 
-        while self.current != '"' && self.ptr != self.content.len() {
-            string.push(self.current);
+        self.advance();
+
+        while self.current != '"' && self.current != '\0' {
             self.advance()
         }
 
+        // Unterminated string
+        if self.current == '\0' {
+            return Token::new_at(
+                TokenType::TokenNone,
+                &self.content[start.offset..self.ptr],
+                start,
+            );
+        }
+
+        let string: &'a str = &self.content[start.offset + 1..self.ptr];
+
         self.advance();
 
-        return Token::new(TokenType::TokenString, string.as_str());
+        return Token::new_at(TokenType::TokenString, string, start);
     }
 
-    fn get_id(&mut self) -> Token {
-        let mut string: String = String::new();
+    fn get_id(&mut self) -> Token<'a> {
+        let start: Position = self.position;
 
-        while (self.current != ' ' && self.current != '\t' && self.current != '\0')
+        while (self.current != ' '
+            && self.current != '\t'
+            && self.current != '\r'
+            && self.current != '\0')
             && self.ptr < self.content.len()
         {
-            if Token::is_single_token(self.current) {
-                return Token::new(TokenType::TokenID, string.as_str());
+            if Token::is_single_token(self.current) || self.current == '#' {
+                break;
             }
 
-            string.push(self.current);
             self.advance();
         }
 
-        return Token::new(TokenType::TokenID, string.as_str());
+        return Token::new_at(
+            TokenType::TokenID,
+            &self.content[start.offset..self.ptr],
+            start,
+        );
     }
 
-    pub fn get_next_token(&mut self) -> Option<Token> {
+    pub fn get_next_token(&mut self) -> Option<Token<'a>> {
         // Collect tokens
         loop {
             // Advance
-            if self.current == '\0' && self.ptr < self.content.len() {
+            if self.current == '\0' {
                 break;
             }
 
             // Skip whitespace
             self.skip_whitespace();
 
+            // Comments run to the end of the line, lines with only a comment are skipped
+            // whole so they don't end a table like an empty line does
+            if self.current == '#' {
+                let whole_line: bool = self.is_line_start();
+
+                while self.current != '\n' && self.current != '\0' {
+                    self.advance();
+                }
+
+                if whole_line {
+                    self.advance();
+                }
+
+                continue;
+            }
+
             // Collect string
             if self.current == '"' {
                 return Some(self.get_string());
@@ -96,29 +162,40 @@ impl Lexer {
                 return Some(self.get_id());
             }
 
+            let start: Position = self.position;
+
             // Collect token
             match self.current {
                 '=' => {
                     self.advance();
-                    return Some(Token::new(TokenType::TokenEqual, "="));
+                    return Some(Token::new_at(TokenType::TokenEqual, "=", start));
                 }
                 '[' => {
                     self.advance();
-                    return Some(Token::new(TokenType::TokenLBracket, "["));
+                    return Some(Token::new_at(TokenType::TokenLBracket, "[", start));
                 }
                 ']' => {
                     self.advance();
-                    return Some(Token::new(TokenType::TokenRBracket, "]"));
+                    return Some(Token::new_at(TokenType::TokenRBracket, "]", start));
                 }
                 ',' => {
                     self.advance();
-                    return Some(Token::new(TokenType::TokenComma, ","));
+                    return Some(Token::new_at(TokenType::TokenComma, ",", start));
                 }
                 '\n' => {
                     self.advance();
-                    return Some(Token::new(TokenType::TokenNewLine, "\n"));
+                    return Some(Token::new_at(TokenType::TokenNewLine, "\n", start));
+                }
+                '\0' => {}
+                _ => {
+                    // Unknown character
+                    self.advance();
+                    return Some(Token::new_at(
+                        TokenType::TokenNone,
+                        &self.content[start.offset..self.ptr],
+                        start,
+                    ));
                 }
-                _ => {}
             };
         }
 
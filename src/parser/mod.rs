@@ -1,5 +1,16 @@
 pub mod ast;
 pub mod converter;
+pub mod error;
+pub mod format;
+pub mod from_toml;
+pub mod getter;
+pub mod include;
+pub mod incremental;
+pub mod interpolate;
+pub mod layout;
 pub mod lexer;
 pub mod parser;
+pub mod path;
 pub mod token;
+pub mod tree;
+pub mod writer;
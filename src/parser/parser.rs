@@ -1,61 +1,266 @@
 use crate::parser::ast::AST;
+use crate::parser::converter::{convert_ast_to_string_recovering, TOMLStringMap};
+use crate::parser::error::ParseError;
 use crate::parser::lexer::Lexer;
-use crate::parser::token::{Token, TokenType};
+use crate::parser::token::{Position, Token, TokenType};
+use crate::parser::tree::TOMLTable;
+use std::collections::VecDeque;
+use std::fs;
+
+pub struct Parser<'a> {
+    lexer: Lexer<'a>,
+    token: Option<Token<'a>>,
+    // Tokens read ahead of `token`
+    peeked: VecDeque<Token<'a>>,
+    // What `get_token` gives once every token is read, at the end of the document so errors
+    // there point at it
+    end: Token<'a>,
+    // Arrays being parsed, left as it was when an error returns from inside an array
+    depth: usize,
+}
+
+// Owns the content of a file, the parser and its tokens borrow from it
+pub struct OwnedParser {
+    content: String,
+}
+
+impl OwnedParser {
+    pub fn get_content(&self) -> &str {
+        return self.content.as_str();
+    }
 
-pub struct Parser {
-    lexer: Lexer,
-    token: Option<Token>,
+    pub fn get_parser(&self) -> Parser<'_> {
+        return Parser::from_string(self.content.as_str());
+    }
 }
 
-impl Parser {
-    pub fn new(filepath: &str) -> Self {
+impl<'a> Parser<'a> {
+    // Reads the whole file, `get_parser` lends a parser of it
+    pub fn new(filepath: &str) -> OwnedParser {
+        let content: String = fs::read_to_string(filepath).expect("[ERROR] Failed to read file.");
+
+        return OwnedParser { content };
+    }
+
+    pub fn from_string(content: &'a str) -> Self {
+        return Parser::from_lexer(Lexer::from_string(content));
+    }
+
+    // Positioned on the first token at `position`, a line start, for `parse_next_recovering`
+    pub(crate) fn from_string_at(content: &'a str, position: Position) -> Self {
+        let mut parser: Parser = Parser::from_lexer(Lexer::from_string_at(content, position));
+
+        parser.token = parser.next_token();
+
+        return parser;
+    }
+
+    fn from_lexer(lexer: Lexer<'a>) -> Self {
         return Parser {
-            lexer: Lexer::new(filepath),
+            lexer,
             token: None,
+            peeked: VecDeque::new(),
+            end: Token::new(TokenType::TokenNone, "\0"),
+            depth: 0,
         };
     }
 
-    pub fn get_token(&self) -> Token {
-        return if let Some(t) = &self.token {
-            Token::new(t.token_type.clone(), t.value.as_str())
-        } else {
-            Token::new(TokenType::TokenNone, "\0")
+    pub fn get_token(&self) -> &Token<'a> {
+        return self.token.as_ref().unwrap_or(&self.end);
+    }
+
+    fn unexpected_end(&self) -> ParseError {
+        return ParseError::new("Unexpected end of input.", self.end.position);
+    }
+
+    fn unexpected_token(&self, token: &Token) -> ParseError {
+        // Past the last token there is nothing to print
+        if self.token.is_none() {
+            return self.unexpected_end();
+        }
+
+        return ParseError::new(
+            format!(
+                "Unexpected token {:?}, with value '{}'.",
+                token.token_type,
+                token.value.escape_debug()
+            )
+            .as_str(),
+            token.position,
+        );
+    }
+
+    pub fn consume(&mut self, token_type: TokenType) -> Result<(), ParseError> {
+        self.take_token(token_type)?;
+
+        return Ok(());
+    }
+
+    // Moves the current token out if it has the type and reads the next one
+    fn take_token(&mut self, token_type: TokenType) -> Result<Token<'a>, ParseError> {
+        match &self.token {
+            Some(t) if t.token_type == token_type => {}
+            Some(t) => return Err(self.unexpected_token(t)),
+            None => return Err(self.unexpected_end()),
+        }
+
+        let next: Option<Token<'a>> = self.next_token();
+
+        return Ok(std::mem::replace(&mut self.token, next).unwrap());
+    }
+
+    pub fn parse(&mut self) -> AST {
+        return match self.try_parse() {
+            Ok(ast) => ast,
+            Err(e) => panic!("[ERROR] {}", e),
         };
     }
 
-    pub fn consume(&mut self, token_type: TokenType) {
-        if let Some(t) = &self.token {
-            if t.token_type == token_type {
-                self.token = self.lexer.get_next_token();
-            } else {
-                panic!(
-                    "[ERROR] Unexpected token {:?}, with value '{}'.",
-                    t.token_type, t.value
-                );
+    fn next_token(&mut self) -> Option<Token<'a>> {
+        let token: Option<Token<'a>> = match self.peeked.pop_front() {
+            Some(token) => Some(token),
+            None => self.lexer.get_next_token(),
+        };
+
+        if token.is_none() {
+            self.end.position = self.lexer.get_position();
+        }
+
+        return token;
+    }
+
+    // Types of the current token and the `count - 1` after it
+    fn peek_types(&mut self, count: usize) -> Vec<TokenType> {
+        while self.peeked.len() + 1 < count {
+            match self.lexer.get_next_token() {
+                Some(token) => self.peeked.push_back(token),
+                None => break,
             }
-        } else {
-            println!("[WARNING] Token is None.");
         }
+
+        return self
+            .token
+            .iter()
+            .chain(self.peeked.iter())
+            .take(count)
+            .map(|t| t.token_type.clone())
+            .collect();
     }
 
-    pub fn parse(&mut self) -> AST {
-        self.token = self.lexer.get_next_token();
+    // `key =`, `"key" =`, `[name]`, `[[name]]`, an empty line or the end
+    fn is_statement_start(&mut self) -> bool {
+        use TokenType::*;
+
+        return match self.peek_types(4).as_slice() {
+            [] | [TokenNewLine, ..] => true,
+            [TokenID | TokenString, TokenEqual, ..] => true,
+            [TokenLBracket, TokenID, TokenRBracket, ..] => true,
+            [TokenLBracket, TokenLBracket, TokenID, TokenRBracket] => true,
+            _ => false,
+        };
+    }
+
+    // Skips the rest of the statement that failed. Inside an array that spans lines that
+    // is up to its closing bracket, or the next line that starts a statement.
+    fn synchronize(&mut self) {
+        let mut depth: usize = self.depth;
+        self.depth = 0;
+
+        while let Some(token) = &self.token {
+            match token.token_type {
+                TokenType::TokenLBracket if depth > 0 => depth += 1,
+                TokenType::TokenRBracket if depth > 0 => depth -= 1,
+                TokenType::TokenNewLine => {
+                    self.token = self.next_token();
+
+                    if depth == 0 || self.is_statement_start() {
+                        return;
+                    }
+
+                    continue;
+                }
+                _ => {}
+            }
+
+            self.token = self.next_token();
+        }
+    }
+
+    // Parses the whole input even when it has errors. Statements that fail become
+    // `ASTError` nodes and parsing resumes at the next statement, so every error is found
+    // in one pass.
+    pub fn parse_recovering(&mut self) -> (AST, Vec<ParseError>) {
+        let mut statements: Vec<AST> = vec![];
+        let mut errors: Vec<ParseError> = vec![];
+
+        self.token = self.next_token();
+
+        while let Some((statement, _, error)) = self.parse_next_recovering() {
+            statements.push(statement);
+            errors.extend(error);
+        }
+
+        return (AST::ASTCompound(statements), errors);
+    }
+
+    // One statement of `parse_recovering` with the position of its first token, None at the
+    // end. The parser holds nothing from earlier statements at that point, so parsing from
+    // a statement start always gives the same statements after it.
+    pub(crate) fn parse_next_recovering(&mut self) -> Option<(AST, Position, Option<ParseError>)> {
+        let position: Position = self.token.as_ref()?.position;
+
+        let result: Result<AST, ParseError> = self.parse_statement().and_then(|statement| {
+            // A statement ends its line
+            match &self.token {
+                None => {}
+                Some(t) if t.token_type == TokenType::TokenNewLine => {
+                    self.consume(TokenType::TokenNewLine)?
+                }
+                Some(t) => return Err(self.unexpected_token(t)),
+            }
+
+            return Ok(statement);
+        });
+
+        return match result {
+            Ok(statement) => Some((statement, position, None)),
+            Err(e) => {
+                self.synchronize();
+                Some((
+                    AST::ASTError(e.message.clone(), e.position),
+                    position,
+                    Some(e),
+                ))
+            }
+        };
+    }
+
+    pub fn try_parse(&mut self) -> Result<AST, ParseError> {
+        self.token = self.next_token();
+
+        // Empty file
+        if self.token.is_none() {
+            return Ok(AST::ASTCompound(vec![]));
+        }
 
         return self.parse_multiple_statement();
     }
 
-    pub fn parse_statement(&mut self) -> AST {
-        if let Some(token) = &mut self.token {
+    pub fn parse_statement(&mut self) -> Result<AST, ParseError> {
+        if let Some(token) = &self.token {
             match token.token_type {
                 TokenType::TokenNone => {}
                 TokenType::TokenID => {
                     return self.parse_variable();
                 }
-                TokenType::TokenString => {}
+                // Quoted key
+                TokenType::TokenString => {
+                    return self.parse_variable();
+                }
                 TokenType::TokenEqual => {}
                 TokenType::TokenComma => {}
                 TokenType::TokenNewLine => {
-                    return AST::ASTSeparator();
+                    return Ok(AST::ASTSeparator());
                 }
                 TokenType::TokenLBracket => {
                     return self.parse_tag();
@@ -63,37 +268,38 @@ impl Parser {
                 TokenType::TokenRBracket => {}
             }
 
-            panic!(
-                "[ERROR] Unexpected token {:?}, with value '{}'.",
-                token.token_type, token.value
-            );
+            return Err(self.unexpected_token(token));
         } else {
-            panic!("[ERROR] Token is None.");
+            return Err(self.unexpected_end());
         }
     }
 
-    pub fn parse_multiple_statement(&mut self) -> AST {
+    pub fn parse_multiple_statement(&mut self) -> Result<AST, ParseError> {
         let mut compound = AST::ASTCompound(vec![]);
 
-        let statement = self.parse_statement();
+        let statement = self.parse_statement()?;
 
-        let mut token = self.get_token();
+        let mut token_type: TokenType = self.get_token().token_type.clone();
 
         if let AST::ASTCompound(v) = &mut compound {
             v.push(statement);
         }
 
-        while token.token_type == TokenType::TokenNewLine {
-            if let Some(t) = &mut self.token {
-                token.token_type = t.token_type.clone();
-                token.value = t.value.clone();
+        while token_type == TokenType::TokenNewLine {
+            if let Some(t) = &self.token {
+                token_type = t.token_type.clone();
 
-                self.consume(TokenType::TokenNewLine);
+                self.consume(TokenType::TokenNewLine)?;
             } else {
                 break;
             }
 
-            let ast_statement = self.parse_statement();
+            // Trailing new line at the end of the file
+            if self.token.is_none() {
+                break;
+            }
+
+            let ast_statement = self.parse_statement()?;
 
             if let AST::ASTCompound(ast) = &mut compound {
                 ast.push(ast_statement);
@@ -102,40 +308,62 @@ impl Parser {
             }
         }
 
-        return compound;
+        // Anything left after the last statement
+        if let Some(t) = &self.token {
+            return Err(self.unexpected_token(t));
+        }
+
+        return Ok(compound);
     }
 
-    pub fn parse_tag(&mut self) -> AST {
-        self.consume(TokenType::TokenLBracket); // Consume the left braces
+    pub fn parse_tag(&mut self) -> Result<AST, ParseError> {
+        let position: Position = self.get_token().position;
 
-        let tag: String = self.get_token().value; // Save the tag name
+        self.consume(TokenType::TokenLBracket)?; // Consume the left braces
 
-        self.consume(TokenType::TokenID);
+        if self.get_token().token_type == TokenType::TokenLBracket {
+            return self.parse_tag_array(position);
+        }
 
-        self.consume(TokenType::TokenRBracket);
+        let tag: Token = self.take_token(TokenType::TokenID)?; // Save the tag name
 
-        return AST::ASTTagDefinition(tag);
-    }
+        self.consume(TokenType::TokenRBracket)?;
 
-    pub fn parse_variable(&mut self) -> AST {
-        let var_name: String = self.get_token().value;
+        return Ok(AST::ASTTagDefinition(String::from(tag.value), position));
+    }
 
-        self.consume(TokenType::TokenID); // Consume the variable name
+    pub fn parse_tag_array(&mut self, position: Position) -> Result<AST, ParseError> {
+        self.consume(TokenType::TokenLBracket)?; // Consume the second left braces
 
-        self.consume(TokenType::TokenEqual); // Consume the equals sign
+        let tag: Token = self.take_token(TokenType::TokenID)?; // Save the tag name
 
-        let ast_value = self.parse_value();
+        self.consume(TokenType::TokenRBracket)?;
+        self.consume(TokenType::TokenRBracket)?;
 
-        return AST::ASTVariableDefinition(var_name, Box::new(ast_value));
+        return Ok(AST::ASTTagArrayDefinition(
+            String::from(tag.value),
+            position,
+        ));
     }
 
-    pub fn parse_value(&mut self) -> AST {
-        let token: Token = self.get_token();
-        let var_value: String;
+    pub fn parse_variable(&mut self) -> Result<AST, ParseError> {
+        // Take the variable name, bare or quoted
+        let token: Token = self.take_token(self.get_token().token_type.clone())?;
+
+        self.consume(TokenType::TokenEqual)?; // Consume the equals sign
 
-        match &token.token_type {
+        let ast_value = self.parse_value()?;
+
+        return Ok(AST::ASTVariableDefinition(
+            String::from(token.value),
+            Box::new(ast_value),
+            token.position,
+        ));
+    }
+
+    pub fn parse_value(&mut self) -> Result<AST, ParseError> {
+        match self.get_token().token_type {
             TokenType::TokenID => {
-                //var_value = token.value;
                 return self.parse_value_from_id();
             }
             TokenType::TokenString => {
@@ -145,53 +373,54 @@ impl Parser {
                 return self.parse_array();
             }
             _ => {
-                panic!(
-                    "[ERROR] Unexpected token {:?}, with value '{}'.",
-                    token.token_type, token.value
-                );
+                return Err(self.unexpected_token(self.get_token()));
             }
         }
     }
 
-    pub fn parse_value_from_id(&mut self) -> AST {
-        let token: Token = self.get_token();
-
-        self.consume(TokenType::TokenID);
+    pub fn parse_value_from_id(&mut self) -> Result<AST, ParseError> {
+        let token: Token = self.take_token(TokenType::TokenID)?;
 
         return if token.value == "false" || token.value == "true" {
             if token.value == "true" {
-                AST::ASTBool(true)
+                Ok(AST::ASTBool(true))
             } else {
-                AST::ASTBool(false)
+                Ok(AST::ASTBool(false))
             }
+        } else if is_datetime(&token.value) {
+            Ok(AST::ASTDateTime(String::from(token.value)))
+        } else if let Ok(v) = token.value.parse::<i32>() {
+            Ok(AST::ASTInt(v))
         } else {
-            AST::ASTInt(token.value.parse::<i32>().unwrap())
+            Err(ParseError::new(
+                format!("Invalid value '{}'.", token.value).as_str(),
+                token.position,
+            ))
         };
     }
 
-    pub fn parse_string(&mut self) -> AST {
-        let token = self.get_token();
-        self.consume(TokenType::TokenString);
+    pub fn parse_string(&mut self) -> Result<AST, ParseError> {
+        let token: Token = self.take_token(TokenType::TokenString)?;
 
-        return AST::ASTString(token.value);
+        return Ok(AST::ASTString(String::from(token.value)));
     }
 
-    pub fn parse_array(&mut self) -> AST {
-        self.consume(TokenType::TokenLBracket); // Consume the bracket
+    pub fn parse_array(&mut self) -> Result<AST, ParseError> {
+        self.consume(TokenType::TokenLBracket)?; // Consume the bracket
+        self.depth += 1;
 
         let mut array: Vec<AST> = vec![];
         let mut expected_value: bool = true;
 
-        while let token = self.get_token() {
-            match token.token_type {
+        loop {
+            let token_type: TokenType = self.get_token().token_type.clone();
+
+            match token_type {
                 TokenType::TokenID => {
                     if !expected_value {
-                        panic!(
-                            "[ERROR] Unexpected token {:?}, with value '{}'.",
-                            token.token_type, token.value
-                        );
+                        return Err(self.unexpected_token(self.get_token()));
                     } else {
-                        let val = self.parse_value_from_id();
+                        let val = self.parse_value_from_id()?;
 
                         array.push(val);
                         expected_value = false;
@@ -199,12 +428,9 @@ impl Parser {
                 }
                 TokenType::TokenString => {
                     if !expected_value {
-                        panic!(
-                            "[ERROR] Unexpected token {:?}, with value '{}'.",
-                            token.token_type, token.value
-                        );
+                        return Err(self.unexpected_token(self.get_token()));
                     } else {
-                        let val = self.parse_string();
+                        let val = self.parse_string()?;
 
                         array.push(val);
                         expected_value = false;
@@ -212,50 +438,130 @@ impl Parser {
                 }
                 TokenType::TokenComma => {
                     if expected_value {
-                        panic!(
-                            "[ERROR] Unexpected token {:?}, with value '{}'.",
-                            token.token_type, token.value
-                        );
+                        return Err(self.unexpected_token(self.get_token()));
                     }
                     expected_value = true;
 
-                    self.consume(TokenType::TokenComma);
+                    self.consume(TokenType::TokenComma)?;
                 }
                 TokenType::TokenRBracket => {
-                    if expected_value {
-                        panic!(
-                            "[ERROR] Unexpected token {:?}, with value '{}'.",
-                            token.token_type, token.value
-                        );
+                    // Only empty arrays can close right after the bracket
+                    if expected_value && !array.is_empty() {
+                        return Err(self.unexpected_token(self.get_token()));
                     }
 
-                    self.consume(TokenType::TokenRBracket);
+                    self.consume(TokenType::TokenRBracket)?;
                     break;
                 }
                 TokenType::TokenNewLine => {
-                    self.consume(TokenType::TokenNewLine);
+                    self.consume(TokenType::TokenNewLine)?;
                 }
                 TokenType::TokenLBracket => {
                     if !expected_value {
-                        panic!(
-                            "[ERROR] Unexpected token {:?}, with value '{}'.",
-                            token.token_type, token.value
-                        );
+                        return Err(self.unexpected_token(self.get_token()));
                     }
 
-                    array.push(self.parse_array());
+                    array.push(self.parse_array()?);
 
                     expected_value = false;
                 }
                 _ => {
-                    panic!(
-                        "[ERROR] Unexpected token {:?}, with value '{}'.",
-                        token.token_type, token.value
-                    );
+                    return Err(self.unexpected_token(self.get_token()));
                 }
             }
         }
 
-        return AST::ASTArray(array);
+        self.depth -= 1;
+
+        return Ok(AST::ASTArray(array));
     }
 }
+
+fn is_digits(s: &str, count: usize) -> bool {
+    return s.len() == count && s.chars().all(|c| c.is_ascii_digit());
+}
+
+fn is_time(s: &str) -> bool {
+    // `hh:mm:ss` with optional fractional seconds
+    let (time, fraction) = match s.split_once('.') {
+        Some((time, fraction)) => (time, Some(fraction)),
+        None => (s, None),
+    };
+
+    let parts: Vec<&str> = time.split(':').collect();
+
+    return parts.len() == 3
+        && parts.iter().all(|p| is_digits(p, 2))
+        && fraction.map_or(true, |f| {
+            !f.is_empty() && f.chars().all(|c| c.is_ascii_digit())
+        });
+}
+
+pub fn is_datetime(s: &str) -> bool {
+    if !s.is_ascii() {
+        return false;
+    }
+
+    // Local time
+    if is_time(s) {
+        return true;
+    }
+
+    // Local date
+    let date: &str = s.get(0..10).unwrap_or("");
+    let parts: Vec<&str> = date.split('-').collect();
+
+    if !(parts.len() == 3
+        && is_digits(parts[0], 4)
+        && is_digits(parts[1], 2)
+        && is_digits(parts[2], 2))
+    {
+        return false;
+    }
+
+    if s.len() == 10 {
+        return true;
+    }
+
+    // Date and time, with an optional offset
+    if !(s[10..].starts_with('T') || s[10..].starts_with('t')) {
+        return false;
+    }
+
+    let mut time: &str = &s[11..];
+
+    if time.ends_with('Z') || time.ends_with('z') {
+        time = &time[0..time.len() - 1];
+    } else if time.len() > 6
+        && (time[time.len() - 6..].starts_with('+') || time[time.len() - 6..].starts_with('-'))
+    {
+        let offset: &str = &time[time.len() - 5..];
+        let offset_parts: Vec<&str> = offset.split(':').collect();
+
+        if !(offset_parts.len() == 2 && offset_parts.iter().all(|p| is_digits(p, 2))) {
+            return false;
+        }
+
+        time = &time[0..time.len() - 6];
+    }
+
+    return is_time(time);
+}
+
+// The partial document and every error of the parser, the converter and the tree, sorted by
+// their offset in the source
+pub fn parse_document_recovering(content: &str) -> (TOMLTable, Vec<ParseError>) {
+    let (ast, mut errors) = Parser::from_string(content).parse_recovering();
+
+    let (map, conversion_errors): (TOMLStringMap, Vec<ParseError>) =
+        convert_ast_to_string_recovering(&ast, true);
+    let (document, tree_errors): (TOMLTable, Vec<ParseError>) = map.to_tree_recovering();
+
+    errors.extend(conversion_errors);
+    errors.extend(tree_errors);
+
+    // In document order, not grouped by stage
+    errors.sort_by_key(|e| e.position.offset);
+
+    return (document, errors);
+}
@@ -0,0 +1,356 @@
+use crate::parser::ast::AST;
+use crate::parser::converter::{
+    convert_ast_value_to_string, convert_string_to_ast, get_value_type, TOMLStringMap,
+    TOMLStringTag,
+};
+use crate::parser::token::Token;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PathErrorKind {
+    InvalidSyntax(String),
+    NotFound,
+    IndexOutOfBounds(usize, usize),
+    NotAnArray(String),
+    NotATable(String),
+    NotAValue,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathError {
+    pub path: String,
+    pub segment: usize,
+    pub kind: PathErrorKind,
+}
+
+impl fmt::Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let segment: String = match parse_path(&self.path) {
+            Ok(segments) if self.segment < segments.len() => {
+                format_path(&segments[self.segment..self.segment + 1])
+            }
+            _ => String::new(),
+        };
+
+        return match &self.kind {
+            PathErrorKind::InvalidSyntax(message) => {
+                write!(f, "Invalid path `{}`: {}", self.path, message)
+            }
+            PathErrorKind::NotFound => {
+                write!(f, "Path `{}`: `{}` doesn't exists.", self.path, segment)
+            }
+            PathErrorKind::IndexOutOfBounds(index, len) => write!(
+                f,
+                "Path `{}`: index {} is out of bounds for array of length {}.",
+                self.path, index, len
+            ),
+            PathErrorKind::NotAnArray(t) => write!(
+                f,
+                "Path `{}`: `{}` can't index a value of type `{}`.",
+                self.path, segment, t
+            ),
+            PathErrorKind::NotATable(t) => write!(
+                f,
+                "Path `{}`: `{}` can't be looked up in a value of type `{}`.",
+                self.path, segment, t
+            ),
+            PathErrorKind::NotAValue => {
+                write!(f, "Path `{}` refers to a table, not a value.", self.path)
+            }
+        };
+    }
+}
+
+impl std::error::Error for PathError {}
+
+fn is_bare_key_char(c: char) -> bool {
+    return !(c == '.' || c == '[' || c == ']' || c == '"' || c == '\\' || c.is_whitespace());
+}
+
+pub fn parse_path(path: &str) -> Result<Vec<PathSegment>, PathError> {
+    let mut segments: Vec<PathSegment> = vec![];
+
+    let chars: Vec<char> = path.chars().collect();
+    let mut ptr: usize = 0;
+
+    let error = |segments: &Vec<PathSegment>, message: String| PathError {
+        path: path.to_owned(),
+        segment: segments.len(),
+        kind: PathErrorKind::InvalidSyntax(message),
+    };
+
+    // A key is expected at the start and after every `.`
+    let mut expect_key: bool = true;
+
+    while ptr < chars.len() || expect_key {
+        if expect_key {
+            let mut key: String = String::new();
+
+            if ptr < chars.len() && chars[ptr] == '"' {
+                // Quoted key, `\"` and `\\` are escapes
+                ptr += 1;
+
+                loop {
+                    if ptr >= chars.len() {
+                        return Err(error(&segments, String::from("unterminated quoted key")));
+                    }
+
+                    match chars[ptr] {
+                        '"' => break,
+                        '\\' if ptr + 1 < chars.len() => {
+                            key.push(chars[ptr + 1]);
+                            ptr += 2;
+                        }
+                        c => {
+                            key.push(c);
+                            ptr += 1;
+                        }
+                    }
+                }
+
+                ptr += 1;
+            } else {
+                while ptr < chars.len() && is_bare_key_char(chars[ptr]) {
+                    key.push(chars[ptr]);
+                    ptr += 1;
+                }
+
+                if key.is_empty() {
+                    return Err(error(
+                        &segments,
+                        format!("expected a key at column {}", ptr + 1),
+                    ));
+                }
+            }
+
+            segments.push(PathSegment::Key(key));
+            expect_key = false;
+
+            continue;
+        }
+
+        match chars[ptr] {
+            '.' => {
+                expect_key = true;
+                ptr += 1;
+            }
+            '[' => {
+                let start: usize = ptr + 1;
+                let mut end: usize = start;
+
+                while end < chars.len() && chars[end].is_ascii_digit() {
+                    end += 1;
+                }
+
+                if end == start || end >= chars.len() || chars[end] != ']' {
+                    return Err(error(
+                        &segments,
+                        format!("expected an index at column {}", start + 1),
+                    ));
+                }
+
+                let index: String = chars[start..end].iter().collect();
+
+                segments.push(PathSegment::Index(index.parse::<usize>().map_err(
+                    |_| error(&segments, format!("index `{}` is too large", index)),
+                )?));
+
+                ptr = end + 1;
+            }
+            c => {
+                return Err(error(
+                    &segments,
+                    format!("unexpected `{}` at column {}", c, ptr + 1),
+                ));
+            }
+        }
+    }
+
+    return Ok(segments);
+}
+
+pub fn format_path(segments: &[PathSegment]) -> String {
+    let mut path: String = String::new();
+
+    for segment in segments {
+        match segment {
+            PathSegment::Key(key) => {
+                if !path.is_empty() {
+                    path.push('.');
+                }
+
+                // Bare keys are read back by the lexer as a single id
+                if key.chars().all(is_bare_key_char)
+                    && Token::is_valid_id(key)
+                    && !key.contains('#')
+                {
+                    path += key;
+                } else {
+                    path.push('"');
+                    path += key.replace('\\', "\\\\").replace('"', "\\\"").as_str();
+                    path.push('"');
+                }
+            }
+            PathSegment::Index(index) => {
+                path += format!("[{}]", index).as_str();
+            }
+        }
+    }
+
+    return path;
+}
+
+// Tag names are stored flat, keys are joined with `.` and indices appended as `[index]`
+fn format_tag_name(segments: &[PathSegment]) -> String {
+    let mut name: String = String::new();
+
+    for segment in segments {
+        match segment {
+            PathSegment::Key(key) => {
+                if !name.is_empty() {
+                    name.push('.');
+                }
+                name += key;
+            }
+            PathSegment::Index(index) => {
+                name += format!("[{}]", index).as_str();
+            }
+        }
+    }
+
+    return name;
+}
+
+fn count_tag_array(map: &TOMLStringMap, array: &str) -> usize {
+    let mut count: usize = 0;
+
+    while map
+        .tags
+        .contains_key(format!("{}[{}]", array, count).as_str())
+    {
+        count += 1;
+    }
+
+    return count;
+}
+
+impl TOMLStringMap {
+    pub fn get_path(&self, path: &str) -> Result<AST, PathError> {
+        let segments: Vec<PathSegment> = parse_path(path)?;
+
+        let error = |segment: usize, kind: PathErrorKind| PathError {
+            path: path.to_owned(),
+            segment,
+            kind,
+        };
+
+        // Find the longest prefix naming a tag, the rest addresses a value inside it
+        let mut tag_len: usize = 0;
+        let mut tag: Option<&TOMLStringTag> = None;
+        let mut out_of_bounds: Option<PathError> = None;
+
+        for len in (1..=segments.len()).rev() {
+            if let Some(t) = self.tags.get(format_tag_name(&segments[0..len]).as_str()) {
+                tag_len = len;
+                tag = Some(t);
+                break;
+            }
+
+            // Elements of arrays of tags that don't exist
+            if let PathSegment::Index(index) = segments[len - 1] {
+                let count: usize = count_tag_array(self, &format_tag_name(&segments[0..len - 1]));
+
+                if count > 0 && out_of_bounds.is_none() {
+                    out_of_bounds = Some(error(
+                        len - 1,
+                        PathErrorKind::IndexOutOfBounds(index, count),
+                    ));
+                }
+            }
+        }
+
+        if let Some(e) = out_of_bounds {
+            return Err(e);
+        }
+
+        // Global values
+        let tag: &TOMLStringTag = if let Some(t) = tag {
+            t
+        } else if count_tag_array(self, &format_tag_name(&segments)) > 0 {
+            return Err(error(segments.len() - 1, PathErrorKind::NotAValue));
+        } else if let Some(t) = self.tags.get(".") {
+            t
+        } else {
+            return Err(error(0, PathErrorKind::NotFound));
+        };
+
+        if tag_len == segments.len() {
+            return Err(error(tag_len - 1, PathErrorKind::NotAValue));
+        }
+
+        // Dotted keys are stored as a single name
+        let key_count: usize = segments[tag_len..]
+            .iter()
+            .take_while(|s| matches!(s, PathSegment::Key(_)))
+            .count();
+
+        let mut value: Option<(usize, &String)> = None;
+
+        for len in (1..=key_count).rev() {
+            let name: String = format_tag_name(&segments[tag_len..tag_len + len]);
+
+            if let Some(v) = tag.values.get(name.as_str()) {
+                value = Some((tag_len + len, v));
+                break;
+            }
+        }
+
+        let (mut ptr, value): (usize, &String) = if let Some(v) = value {
+            v
+        } else {
+            return Err(error(tag_len, PathErrorKind::NotFound));
+        };
+
+        let mut ast: AST = if let Some(ast) = convert_string_to_ast(value) {
+            ast
+        } else {
+            return Err(error(ptr - 1, PathErrorKind::NotFound));
+        };
+
+        // Index into arrays
+        while ptr < segments.len() {
+            let type_str: String = get_value_type(&convert_ast_value_to_string(&ast, true))
+                .unwrap_or("")
+                .to_owned();
+
+            match (&segments[ptr], ast) {
+                (PathSegment::Index(index), AST::ASTArray(mut array)) => {
+                    if *index >= array.len() {
+                        return Err(error(
+                            ptr,
+                            PathErrorKind::IndexOutOfBounds(*index, array.len()),
+                        ));
+                    }
+
+                    ast = array.swap_remove(*index);
+                }
+                (PathSegment::Index(_), _) => {
+                    return Err(error(ptr, PathErrorKind::NotAnArray(type_str)));
+                }
+                (PathSegment::Key(_), _) => {
+                    return Err(error(ptr, PathErrorKind::NotATable(type_str)));
+                }
+            }
+
+            ptr += 1;
+        }
+
+        return Ok(ast);
+    }
+}
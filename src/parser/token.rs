@@ -10,16 +10,45 @@ pub enum TokenType {
     TokenRBracket,
 }
 
-pub struct Token {
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+    pub offset: usize,
+}
+
+impl Position {
+    pub fn new() -> Self {
+        return Position {
+            line: 1,
+            column: 1,
+            offset: 0,
+        };
+    }
+}
+
+// Values are slices of the source
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token<'a> {
     pub token_type: TokenType,
-    pub value: String,
+    pub value: &'a str,
+    pub position: Position,
 }
 
-impl Token {
-    pub fn new(t: TokenType, v: &str) -> Self {
+impl<'a> Token<'a> {
+    pub fn new(t: TokenType, v: &'a str) -> Self {
         return Token {
             token_type: t,
-            value: String::from(v),
+            value: v,
+            position: Position::new(),
+        };
+    }
+
+    pub fn new_at(t: TokenType, v: &'a str, position: Position) -> Self {
+        return Token {
+            token_type: t,
+            value: v,
+            position,
         };
     }
 
@@ -29,4 +58,12 @@ impl Token {
             _ => false,
         };
     }
+
+    pub fn is_valid_id(s: &str) -> bool {
+        // Ids start like the lexer expects and run until whitespace or a single token
+        return s.starts_with(|c: char| c.is_alphanumeric() || c == '-' || c == '+')
+            && !s.chars().any(|c| {
+                Token::is_single_token(c) || c == ' ' || c == '\t' || c == '"' || c == '\0'
+            });
+    }
 }
@@ -0,0 +1,280 @@
+use crate::parser::ast::AST;
+use crate::parser::converter::{
+    convert_string_to_ast, try_convert_ast_to_string, TOMLStringMap, TOMLStringTag,
+};
+use crate::parser::error::ParseError;
+use crate::parser::parser::Parser;
+use crate::parser::path::PathSegment;
+use crate::parser::token::Position;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TOMLTree {
+    Value(AST, Option<Position>),
+    Table(TOMLTable),
+    TableArray(Vec<TOMLTable>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TOMLTable {
+    pub entries: Vec<(String, TOMLTree)>,
+    pub position: Option<Position>,
+}
+
+impl TOMLTable {
+    pub fn new() -> Self {
+        return TOMLTable {
+            entries: vec![],
+            position: None,
+        };
+    }
+
+    pub fn get(&self, key: &str) -> Option<&TOMLTree> {
+        return self.entries.iter().find(|(k, _)| k == key).map(|(_, v)| v);
+    }
+
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut TOMLTree> {
+        return self
+            .entries
+            .iter_mut()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v);
+    }
+
+    pub fn insert(&mut self, key: &str, tree: TOMLTree) {
+        if let Some(existing) = self.get_mut(key) {
+            *existing = tree;
+        } else {
+            self.entries.push((String::from(key), tree));
+        }
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<TOMLTree> {
+        let index: usize = self.entries.iter().position(|(k, _)| k == key)?;
+
+        return Some(self.entries.remove(index).1);
+    }
+}
+
+impl TOMLTree {
+    pub fn get_position(&self) -> Option<Position> {
+        return match self {
+            TOMLTree::Value(_, position) => *position,
+            TOMLTree::Table(table) => table.position,
+            TOMLTree::TableArray(tables) => tables.first().and_then(|t| t.position),
+        };
+    }
+}
+
+// What a path points to, indices go into arrays of tables and arrays of values
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TOMLNode<'a> {
+    Table(&'a TOMLTable),
+    TableArray(&'a Vec<TOMLTable>),
+    Value(&'a AST, Option<Position>),
+}
+
+impl<'a> TOMLNode<'a> {
+    pub fn to_tree(&self) -> TOMLTree {
+        return match self {
+            TOMLNode::Table(table) => TOMLTree::Table((*table).clone()),
+            TOMLNode::TableArray(tables) => TOMLTree::TableArray((*tables).clone()),
+            TOMLNode::Value(value, position) => TOMLTree::Value((*value).clone(), *position),
+        };
+    }
+}
+
+impl TOMLTable {
+    pub fn get_node(&self, path: &[PathSegment]) -> Option<TOMLNode<'_>> {
+        let mut node: TOMLNode = TOMLNode::Table(self);
+
+        for segment in path {
+            node = match (node, segment) {
+                (TOMLNode::Table(table), PathSegment::Key(key)) => match table.get(key)? {
+                    TOMLTree::Value(value, position) => TOMLNode::Value(value, *position),
+                    TOMLTree::Table(table) => TOMLNode::Table(table),
+                    TOMLTree::TableArray(tables) => TOMLNode::TableArray(tables),
+                },
+                (TOMLNode::TableArray(tables), PathSegment::Index(index)) => {
+                    TOMLNode::Table(tables.get(*index)?)
+                }
+                (TOMLNode::Value(AST::ASTArray(values), position), PathSegment::Index(index)) => {
+                    TOMLNode::Value(values.get(*index)?, position)
+                }
+                _ => return None,
+            };
+        }
+
+        return Some(node);
+    }
+}
+
+// Tag names like `servers[1].items[0]` split into `(key, index)` parts
+pub(crate) fn split_tag_name(name: &str) -> Vec<(&str, Option<usize>)> {
+    return name
+        .split('.')
+        .map(|part| {
+            if let (Some(start), true) = (part.find('['), part.ends_with(']')) {
+                if let Ok(index) = part[start + 1..part.len() - 1].parse::<usize>() {
+                    return (&part[0..start], Some(index));
+                }
+            }
+
+            (part, None)
+        })
+        .collect();
+}
+
+fn conflict(name: &str, position: Option<Position>) -> ParseError {
+    return ParseError::new(
+        format!("`{}` is defined both as a value and a table.", name).as_str(),
+        position.unwrap_or(Position::new()),
+    );
+}
+
+fn get_tag_table<'a>(
+    root: &'a mut TOMLTable,
+    tag: &TOMLStringTag,
+) -> Result<&'a mut TOMLTable, ParseError> {
+    let mut table: &mut TOMLTable = root;
+
+    for (key, index) in split_tag_name(tag.name.as_str()) {
+        if table.get(key).is_none() {
+            let tree: TOMLTree = if index.is_some() {
+                TOMLTree::TableArray(vec![])
+            } else {
+                TOMLTree::Table(TOMLTable::new())
+            };
+
+            table.insert(key, tree);
+        }
+
+        table = match (table.get_mut(key).unwrap(), index) {
+            (TOMLTree::Table(t), None) => t,
+            (TOMLTree::TableArray(tables), Some(index)) => {
+                while tables.len() <= index {
+                    tables.push(TOMLTable::new());
+                }
+
+                &mut tables[index]
+            }
+            _ => return Err(conflict(tag.name.as_str(), tag.position)),
+        };
+    }
+
+    return Ok(table);
+}
+
+fn get_sorted_names<'a>(
+    names: impl Iterator<Item = &'a String>,
+    position: impl Fn(&str) -> Option<Position>,
+) -> Vec<&'a String> {
+    let mut names: Vec<&String> = names.collect();
+
+    // Source order when known, names without a position go last
+    names.sort_by(|a, b| {
+        let key = |n: &str| {
+            (
+                position(n).map_or(usize::MAX, |p| p.offset),
+                String::from(n),
+            )
+        };
+        key(a).cmp(&key(b))
+    });
+
+    return names;
+}
+
+// Parses a document into its tables, keyed by tag name
+pub fn parse_string_map(source: &str) -> Result<TOMLStringMap, ParseError> {
+    let ast: AST = Parser::from_string(source).try_parse()?;
+
+    return try_convert_ast_to_string(&ast, true);
+}
+
+// Parses, typechecks and builds the tree of a document, stopping at the first error
+pub fn parse_document(source: &str) -> Result<TOMLTable, ParseError> {
+    return parse_string_map(source)?.to_tree();
+}
+
+impl TOMLStringMap {
+    pub fn to_tree(&self) -> Result<TOMLTable, ParseError> {
+        return self.to_tree_with(&mut |e| Err(e));
+    }
+
+    // Builds what it can, tables and keys that conflict are left out
+    pub fn to_tree_recovering(&self) -> (TOMLTable, Vec<ParseError>) {
+        let mut errors: Vec<ParseError> = vec![];
+
+        let tree: TOMLTable = self
+            .to_tree_with(&mut |e| {
+                errors.push(e);
+                return Ok(());
+            })
+            .unwrap_or_else(|_| TOMLTable::new());
+
+        return (tree, errors);
+    }
+
+    // `on_error` stops by returning the error, or lets it go on
+    fn to_tree_with(
+        &self,
+        on_error: &mut dyn FnMut(ParseError) -> Result<(), ParseError>,
+    ) -> Result<TOMLTable, ParseError> {
+        let mut root: TOMLTable = TOMLTable::new();
+
+        // Global keys come first, like in the source
+        let names: Vec<&String> = get_sorted_names(self.tags.keys(), |n| {
+            if n == "." {
+                Some(Position::new())
+            } else {
+                self.tags[n].position
+            }
+        });
+
+        for name in names {
+            let tag: &TOMLStringTag = &self.tags[name];
+
+            let table: &mut TOMLTable = if name == "." {
+                &mut root
+            } else {
+                match get_tag_table(&mut root, tag) {
+                    Ok(table) => table,
+                    Err(e) => {
+                        on_error(e)?;
+                        continue;
+                    }
+                }
+            };
+
+            if name != "." {
+                table.position = tag.position;
+            }
+
+            let keys: Vec<&String> =
+                get_sorted_names(tag.values.keys(), |n| tag.positions.get(n).copied());
+
+            for key in keys {
+                let position: Option<Position> = tag.positions.get(key).copied();
+
+                let ast: AST = if let Some(ast) = convert_string_to_ast(&tag.values[key]) {
+                    ast
+                } else {
+                    on_error(ParseError::new(
+                        format!("Failed to read value of `{}`.", key).as_str(),
+                        position.unwrap_or(Position::new()),
+                    ))?;
+                    continue;
+                };
+
+                if table.get(key).is_some() {
+                    on_error(conflict(key, position))?;
+                    continue;
+                }
+
+                table.insert(key, TOMLTree::Value(ast, position));
+            }
+        }
+
+        return Ok(root);
+    }
+}
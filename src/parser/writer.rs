@@ -0,0 +1,268 @@
+use crate::parser::ast::AST;
+use crate::parser::converter::{convert_string_to_ast, TOMLStringMap, TOMLStringTag};
+use crate::parser::path::{format_path, PathSegment};
+use crate::parser::tree::{TOMLTable, TOMLTree};
+
+pub fn convert_ast_value_to_toml(value: &AST) -> String {
+    return match value {
+        AST::ASTBool(v) => v.to_string(),
+        AST::ASTInt(v) => v.to_string(),
+        AST::ASTDateTime(v) => v.clone(),
+        AST::ASTString(v) => {
+            let mut str: String = String::from("\"");
+            str += v.as_str();
+            str += "\"";
+            str
+        }
+        AST::ASTArray(v) => {
+            let elements: Vec<String> = v.iter().map(convert_ast_value_to_toml).collect();
+
+            let mut str: String = String::from("[");
+            str += elements.join(", ").as_str();
+            str += "]";
+            str
+        }
+        _ => {
+            panic!("[ERROR] Invalid AST for writing, found `{:?}`.", value);
+        }
+    };
+}
+
+fn convert_tag_to_toml(tag: &TOMLStringTag, string: &mut String) {
+    let mut names: Vec<&String> = tag.values.keys().collect();
+    names.sort();
+
+    for name in names {
+        let value: &String = &tag.values[name];
+
+        let ast: AST = if let Some(ast) = convert_string_to_ast(value) {
+            ast
+        } else {
+            panic!("[ERROR] Failed to read value `{}` of `{}`.", value, name);
+        };
+
+        *string += format_path(&[PathSegment::Key(name.clone())]).as_str();
+        *string += " = ";
+        *string += convert_ast_value_to_toml(&ast).as_str();
+        *string += "\n";
+    }
+}
+
+fn remove_tag_indices(name: &str) -> String {
+    let mut header: String = String::new();
+    let mut in_index: bool = false;
+
+    for c in name.chars() {
+        match c {
+            '[' => in_index = true,
+            ']' => in_index = false,
+            _ if !in_index => header.push(c),
+            _ => {}
+        }
+    }
+
+    return header;
+}
+
+fn get_tag_sort_key(name: &str) -> String {
+    let mut key: String = String::new();
+    let mut index: String = String::new();
+    let mut in_index: bool = false;
+
+    // Pad indices so `a[10]` sorts after `a[9]`
+    for c in name.chars() {
+        match c {
+            '[' => {
+                in_index = true;
+                key.push(c);
+            }
+            ']' if in_index => {
+                in_index = false;
+                key += format!("{:0>10}", index).as_str();
+                key.push(c);
+                index.clear();
+            }
+            _ if in_index => index.push(c),
+            _ => key.push(c),
+        }
+    }
+
+    return key;
+}
+
+pub fn convert_string_map_to_toml(map: &TOMLStringMap) -> String {
+    let mut string: String = String::new();
+
+    // Global values come first, before any tag is opened
+    if let Some(tag) = map.tags.get(".") {
+        convert_tag_to_toml(tag, &mut string);
+    }
+
+    let mut names: Vec<&String> = map.tags.keys().filter(|n| n.as_str() != ".").collect();
+    names.sort_by_key(|n| get_tag_sort_key(n));
+
+    for name in names {
+        // A blank line closes the previous tag
+        if !string.is_empty() {
+            string += "\n";
+        }
+
+        // Elements of arrays of tags are stored as `name[index]`
+        let header: String = remove_tag_indices(name);
+
+        if name.ends_with(']') {
+            string += "[[";
+            string += header.as_str();
+            string += "]]\n";
+        } else {
+            string += "[";
+            string += header.as_str();
+            string += "]\n";
+        }
+
+        convert_tag_to_toml(&map.tags[name], &mut string);
+    }
+
+    return string;
+}
+
+fn convert_array_to_pretty_toml(values: &[AST]) -> String {
+    if values.is_empty() {
+        return String::from("[]");
+    }
+
+    // One element per line, the parser doesn't allow a trailing comma
+    let elements: Vec<String> = values
+        .iter()
+        .map(|v| String::from("    ") + convert_ast_value_to_toml(v).as_str())
+        .collect();
+
+    let mut str: String = String::from("[\n");
+    str += elements.join(",\n").as_str();
+    str += "\n]";
+
+    return str;
+}
+
+fn convert_table_to_toml(
+    table: &TOMLTable,
+    name: &str,
+    is_array_element: bool,
+    pretty: bool,
+    path: &mut Vec<PathSegment>,
+    annotate: &dyn Fn(&[PathSegment]) -> Option<String>,
+    string: &mut String,
+) {
+    let has_values: bool = table
+        .entries
+        .iter()
+        .any(|(_, tree)| matches!(tree, TOMLTree::Value(_, _)));
+
+    // Tables holding only sub tables are implied by their headers
+    if !name.is_empty() && (is_array_element || has_values || table.entries.is_empty()) {
+        if pretty && !string.is_empty() {
+            *string += "\n";
+        }
+
+        if is_array_element {
+            *string += format!("[[{}]]\n", name).as_str();
+        } else {
+            *string += format!("[{}]\n", name).as_str();
+        }
+    }
+
+    // Plain keys have to come before any sub table is opened
+    for (key, tree) in &table.entries {
+        if let TOMLTree::Value(ast, _) = tree {
+            let value: String = match ast {
+                AST::ASTArray(values) if pretty => convert_array_to_pretty_toml(values),
+                _ => convert_ast_value_to_toml(ast),
+            };
+
+            // Keys that can't be bare are quoted
+            let name: String = format_path(&[PathSegment::Key(key.clone())]);
+            *string += format!("{} = {}", name, value).as_str();
+
+            path.push(PathSegment::Key(key.clone()));
+
+            if let Some(annotation) = annotate(path) {
+                *string += format!("  # {}", annotation).as_str();
+            }
+
+            path.pop();
+            *string += "\n";
+        }
+    }
+
+    for (key, tree) in &table.entries {
+        let sub_name: String = if name.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", name, key)
+        };
+
+        path.push(PathSegment::Key(key.clone()));
+
+        match tree {
+            TOMLTree::Table(sub_table) => {
+                convert_table_to_toml(
+                    sub_table,
+                    sub_name.as_str(),
+                    false,
+                    pretty,
+                    path,
+                    annotate,
+                    string,
+                );
+            }
+            TOMLTree::TableArray(tables) => {
+                for (i, sub_table) in tables.iter().enumerate() {
+                    path.push(PathSegment::Index(i));
+                    convert_table_to_toml(
+                        sub_table,
+                        sub_name.as_str(),
+                        true,
+                        pretty,
+                        path,
+                        annotate,
+                        string,
+                    );
+                    path.pop();
+                }
+            }
+            TOMLTree::Value(_, _) => {}
+        }
+
+        path.pop();
+    }
+}
+
+// Pretty output puts a blank line before headers and array elements on their own lines
+pub fn convert_tree_to_toml(table: &TOMLTable, pretty: bool) -> String {
+    let mut string: String = String::new();
+
+    convert_table_to_toml(
+        table,
+        "",
+        false,
+        pretty,
+        &mut vec![],
+        &|_| None,
+        &mut string,
+    );
+
+    return string;
+}
+
+// Adds `# annotation` after values, the parser skips comments so this is for display
+pub fn convert_tree_to_annotated_toml(
+    table: &TOMLTable,
+    pretty: bool,
+    annotate: &dyn Fn(&[PathSegment]) -> Option<String>,
+) -> String {
+    let mut string: String = String::new();
+
+    convert_table_to_toml(table, "", false, pretty, &mut vec![], annotate, &mut string);
+
+    return string;
+}
@@ -0,0 +1,891 @@
+use crate::json::convert::convert_json_to_subtree;
+use crate::json::JSONValue;
+use crate::merge::is_same_tree;
+use crate::parser::ast::AST;
+use crate::parser::layout::{get_document_layout, DocumentLayout, KeyLayout};
+use crate::parser::path::{format_path, parse_path, PathSegment};
+use crate::parser::token::Token;
+use crate::parser::tree::{parse_document, TOMLNode, TOMLTable, TOMLTree};
+use crate::parser::writer::convert_ast_value_to_toml;
+use std::fmt;
+
+// RFC 6902 style operations applied to the source text. Only the lines an operation
+// touches are rewritten, comments and layout elsewhere are kept. Paths are TOML paths like
+// `servers[0].port` or JSON Pointers like `/servers/0/port`, `-` appends to an array.
+// A patch is applied as a whole or not at all.
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatchOperation {
+    Add(Vec<PathSegment>, TOMLTree),
+    Remove(Vec<PathSegment>),
+    Replace(Vec<PathSegment>, TOMLTree),
+    // From, to
+    Move(Vec<PathSegment>, Vec<PathSegment>),
+    Copy(Vec<PathSegment>, Vec<PathSegment>),
+    Test(Vec<PathSegment>, TOMLTree),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatchError {
+    // Index of the failing operation, None if the patch or the document is malformed
+    pub operation: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self.operation {
+            Some(operation) => write!(f, "Operation {}: {}", operation + 1, self.message),
+            None => write!(f, "{}", self.message),
+        };
+    }
+}
+
+impl std::error::Error for PatchError {}
+
+// `/a/0/b`, numbers are indices until the path is resolved against a document
+fn parse_pointer(pointer: &str) -> Vec<PathSegment> {
+    return pointer
+        .split('/')
+        .skip(1)
+        .map(|part| match part.parse::<usize>() {
+            Ok(index) => PathSegment::Index(index),
+            Err(_) => PathSegment::Key(part.replace("~1", "/").replace("~0", "~")),
+        })
+        .collect();
+}
+
+fn parse_patch_path(path: &str) -> Result<Vec<PathSegment>, String> {
+    if path.is_empty() || path.starts_with('/') {
+        return Ok(parse_pointer(path));
+    }
+
+    // `ports[-]` appends
+    if let Some(array) = path.strip_suffix("[-]") {
+        let mut segments: Vec<PathSegment> = parse_path(array).map_err(|e| e.to_string())?;
+        segments.push(PathSegment::Key(String::from("-")));
+
+        return Ok(segments);
+    }
+
+    return parse_path(path).map_err(|e| e.to_string());
+}
+
+fn parse_operation(
+    op: &str,
+    path: Option<&str>,
+    from: Option<&str>,
+    value: Option<TOMLTree>,
+) -> Result<PatchOperation, String> {
+    let path: Vec<PathSegment> = parse_patch_path(path.ok_or("Missing `path`.")?)?;
+
+    let get_from = || -> Result<Vec<PathSegment>, String> {
+        return parse_patch_path(from.ok_or("Missing `from`.")?);
+    };
+
+    let get_value = || value.clone().ok_or(String::from("Missing `value`."));
+
+    return match op {
+        "add" => Ok(PatchOperation::Add(path, get_value()?)),
+        "remove" => Ok(PatchOperation::Remove(path)),
+        "replace" => Ok(PatchOperation::Replace(path, get_value()?)),
+        "move" => Ok(PatchOperation::Move(get_from()?, path)),
+        "copy" => Ok(PatchOperation::Copy(get_from()?, path)),
+        "test" => Ok(PatchOperation::Test(path, get_value()?)),
+        _ => Err(format!("Unknown operation `{}`.", op)),
+    };
+}
+
+// `[{"op": "replace", "path": "service.replicas", "value": 5}]`, values can be typed
+pub fn parse_json_patch(patch: &JSONValue) -> Result<Vec<PatchOperation>, PatchError> {
+    let operations: &Vec<JSONValue> = match patch {
+        JSONValue::Array(operations) => operations,
+        _ => {
+            return Err(PatchError {
+                operation: None,
+                message: String::from("Invalid patch, expected an array of operations."),
+            });
+        }
+    };
+
+    let mut patch: Vec<PatchOperation> = vec![];
+
+    for (i, operation) in operations.iter().enumerate() {
+        let error = |message: String| PatchError {
+            operation: Some(i),
+            message,
+        };
+
+        let value: Option<TOMLTree> = match operation.get("value") {
+            Some(value) => Some(
+                convert_json_to_subtree(value, true, &mut vec![])
+                    .map_err(|e| error(e.to_string()))?,
+            ),
+            None => None,
+        };
+
+        let op: &str = operation
+            .get("op")
+            .and_then(|op| op.as_str())
+            .ok_or(error(String::from("Missing `op`.")))?;
+
+        patch.push(
+            parse_operation(
+                op,
+                operation.get("path").and_then(|p| p.as_str()),
+                operation.get("from").and_then(|p| p.as_str()),
+                value,
+            )
+            .map_err(error)?,
+        );
+    }
+
+    return Ok(patch);
+}
+
+// An `[[operations]]` array of tables with `op`, `path`, `from` and `value` keys
+pub fn parse_toml_patch(patch: &TOMLTable) -> Result<Vec<PatchOperation>, PatchError> {
+    let operations: &Vec<TOMLTable> = match patch.get("operations") {
+        Some(TOMLTree::TableArray(operations)) => operations,
+        _ => {
+            return Err(PatchError {
+                operation: None,
+                message: String::from(
+                    "Invalid patch, expected an `[[operations]]` array of tables.",
+                ),
+            });
+        }
+    };
+
+    let get_string = |operation: &TOMLTable, key: &str| match operation.get(key) {
+        Some(TOMLTree::Value(AST::ASTString(value), _)) => Some(value.clone()),
+        _ => None,
+    };
+
+    let mut patch: Vec<PatchOperation> = vec![];
+
+    for (i, operation) in operations.iter().enumerate() {
+        let op: String = get_string(operation, "op").ok_or(PatchError {
+            operation: Some(i),
+            message: String::from("Missing `op`."),
+        })?;
+
+        patch.push(
+            parse_operation(
+                op.as_str(),
+                get_string(operation, "path").as_deref(),
+                get_string(operation, "from").as_deref(),
+                operation.get("value").cloned(),
+            )
+            .map_err(|message| PatchError {
+                operation: Some(i),
+                message,
+            })?,
+        );
+    }
+
+    return Ok(patch);
+}
+
+// Pointers can't tell keys from indices, and `-` is the end of an array
+fn resolve_path(document: &TOMLTable, path: &[PathSegment]) -> Vec<PathSegment> {
+    let mut resolved: Vec<PathSegment> = vec![];
+
+    for segment in path {
+        let node: Option<TOMLNode> = document.get_node(&resolved);
+
+        let length: Option<usize> = match node {
+            Some(TOMLNode::TableArray(tables)) => Some(tables.len()),
+            Some(TOMLNode::Value(AST::ASTArray(values), _)) => Some(values.len()),
+            _ => None,
+        };
+
+        let is_table: bool = matches!(node, Some(TOMLNode::Table(_)));
+
+        resolved.push(match (segment, length) {
+            (PathSegment::Key(key), Some(length)) if key == "-" => PathSegment::Index(length),
+            (PathSegment::Key(key), Some(_)) => match key.parse::<usize>() {
+                Ok(index) => PathSegment::Index(index),
+                Err(_) => segment.clone(),
+            },
+            (PathSegment::Index(index), None) if is_table => PathSegment::Key(index.to_string()),
+            _ => segment.clone(),
+        });
+    }
+
+    return resolved;
+}
+
+fn get_line_start(text: &str, offset: usize) -> usize {
+    return text[..offset].rfind('\n').map_or(0, |i| i + 1);
+}
+
+// After the newline, a comment at the end of the line goes with it
+fn get_line_end(text: &str, offset: usize) -> usize {
+    return text[offset..]
+        .find('\n')
+        .map_or(text.len(), |i| offset + i + 1);
+}
+
+// Empty lines before a table header, they go with the table
+fn get_blank_lines_start(text: &str, offset: usize) -> usize {
+    let mut start: usize = get_line_start(text, offset);
+
+    while start > 0 {
+        let previous: usize = get_line_start(text, start - 1);
+
+        if !text[previous..start].trim().is_empty() {
+            break;
+        }
+
+        start = previous;
+    }
+
+    return start;
+}
+
+// Comments right above a header describe its table, they go with it
+fn get_comments_start(text: &str, offset: usize) -> usize {
+    let mut start: usize = get_line_start(text, offset);
+
+    while start > 0 {
+        let previous: usize = get_line_start(text, start - 1);
+
+        if !text[previous..start].trim_start().starts_with('#') {
+            break;
+        }
+
+        start = previous;
+    }
+
+    return start;
+}
+
+fn check_key(key: &str) -> Result<(), String> {
+    // Keys are bare, dots would be read as nested tables
+    if !Token::is_valid_id(key) || key.contains('.') {
+        return Err(format!("Key `{}` can't be written.", key));
+    }
+
+    return Ok(());
+}
+
+fn render_value(value: &AST) -> Result<String, String> {
+    return match value {
+        // Strings have no escapes
+        AST::ASTString(v) if v.contains(['"', '\n']) => {
+            Err(format!("String `{}` can't be written.", v))
+        }
+        AST::ASTArray(values) => {
+            for value in values {
+                render_value(value)?;
+            }
+
+            Ok(convert_ast_value_to_toml(value))
+        }
+        _ => Ok(convert_ast_value_to_toml(value)),
+    };
+}
+
+// `[name]` or `[[name]]` with its values, followed by the tables inside it
+fn render_section(
+    path: &[PathSegment],
+    table: &TOMLTable,
+    is_array: bool,
+) -> Result<String, String> {
+    let name: Vec<&str> = path
+        .iter()
+        .filter_map(|segment| match segment {
+            PathSegment::Key(key) => Some(key.as_str()),
+            PathSegment::Index(_) => None,
+        })
+        .collect();
+
+    for key in &name {
+        check_key(key)?;
+    }
+
+    let mut section: String = if is_array {
+        format!("\n[[{}]]\n", name.join("."))
+    } else {
+        format!("\n[{}]\n", name.join("."))
+    };
+
+    for (key, tree) in &table.entries {
+        if let TOMLTree::Value(value, _) = tree {
+            check_key(key)?;
+            section += format!("{} = {}\n", key, render_value(value)?).as_str();
+        }
+    }
+
+    for (key, tree) in &table.entries {
+        let mut path: Vec<PathSegment> = path.to_vec();
+        path.push(PathSegment::Key(key.clone()));
+
+        match tree {
+            TOMLTree::Table(table) => section += render_section(&path, table, false)?.as_str(),
+            TOMLTree::TableArray(tables) => {
+                for table in tables {
+                    section += render_section(&path, table, true)?.as_str();
+                }
+            }
+            TOMLTree::Value(_, _) => {}
+        }
+    }
+
+    return Ok(section);
+}
+
+enum ArrayEdit {
+    Insert(AST),
+    Replace(AST),
+    Remove,
+}
+
+fn edit_array(value: &mut AST, indices: &[PathSegment], edit: ArrayEdit) -> Result<(), String> {
+    let (values, index) = match (value, indices.first()) {
+        (AST::ASTArray(values), Some(PathSegment::Index(index))) => (values, *index),
+        _ => return Err(String::from("Expected an index into an array.")),
+    };
+
+    if indices.len() > 1 {
+        return match values.get_mut(index) {
+            Some(value) => edit_array(value, &indices[1..], edit),
+            None => Err(format!("Index {} is out of bounds.", index)),
+        };
+    }
+
+    match edit {
+        ArrayEdit::Insert(value) if index <= values.len() => values.insert(index, value),
+        ArrayEdit::Replace(value) if index < values.len() => values[index] = value,
+        ArrayEdit::Remove if index < values.len() => {
+            values.remove(index);
+        }
+        _ => return Err(format!("Index {} is out of bounds.", index)),
+    }
+
+    return Ok(());
+}
+
+struct Patcher {
+    text: String,
+    document: TOMLTable,
+    layout: DocumentLayout,
+}
+
+impl Patcher {
+    fn splice(&mut self, start: usize, end: usize, replacement: &str) -> Result<(), String> {
+        let mut text: String = String::from(&self.text[..start]);
+        text += replacement;
+        text += &self.text[end..];
+
+        // Every step has to leave a document that parses
+        self.document = parse_document(text.as_str())
+            .map_err(|e| format!("The patched document doesn't parse: {}", e))?;
+        self.layout = get_document_layout(text.as_str());
+        self.text = text;
+
+        return Ok(());
+    }
+
+    fn remove_lines(&mut self, mut spans: Vec<(usize, usize)>) -> Result<(), String> {
+        spans.sort();
+
+        let mut text: String = String::new();
+        let mut last: usize = 0;
+
+        for (start, end) in spans {
+            let start: usize = get_line_start(&self.text, start).max(last);
+            text += &self.text[last..start];
+            last = get_line_end(&self.text, end).max(last);
+        }
+
+        text += &self.text[last..];
+
+        return self.splice(0, self.text.len(), text.as_str());
+    }
+
+    fn get_node(&self, path: &[PathSegment]) -> Result<TOMLNode<'_>, String> {
+        return self
+            .document
+            .get_node(path)
+            .ok_or(format!("`{}` doesn't exist.", format_path(path)));
+    }
+
+    // The key whose value holds `path`, for paths into arrays of values
+    fn get_value_key(&self, path: &[PathSegment]) -> Option<KeyLayout> {
+        return (1..path.len())
+            .rev()
+            .find_map(|len| self.layout.get_key(&path[..len]))
+            .cloned();
+    }
+
+    fn edit_value(&mut self, path: &[PathSegment], edit: ArrayEdit) -> Result<(), String> {
+        let key: KeyLayout = self
+            .get_value_key(path)
+            .ok_or(format!("`{}` isn't in an array.", format_path(path)))?;
+
+        let mut value: AST = match self.get_node(&key.path)? {
+            TOMLNode::Value(value, _) => value.clone(),
+            _ => return Err(format!("`{}` isn't an array.", format_path(&key.path))),
+        };
+
+        edit_array(&mut value, &path[key.path.len()..], edit)?;
+
+        return self.splice(
+            key.value_start,
+            key.value_end,
+            render_value(&value)?.as_str(),
+        );
+    }
+
+    fn remove(&mut self, path: &[PathSegment]) -> Result<(), String> {
+        if path.is_empty() {
+            return Err(String::from("The document can't be removed."));
+        }
+
+        let node: TOMLNode = self.get_node(path)?;
+
+        if let Some(key) = self.layout.get_key(path) {
+            let span: (usize, usize) = (key.start, key.value_end);
+            return self.remove_lines(vec![span]);
+        }
+
+        if let TOMLNode::Value(_, _) = node {
+            return self.edit_value(path, ArrayEdit::Remove);
+        }
+
+        // Tables go with the tables inside them
+        let spans: Vec<(usize, usize)> = self
+            .layout
+            .sections
+            .iter()
+            .filter(|s| s.path.starts_with(path))
+            .filter_map(|s| {
+                s.header
+                    .map(|(start, _)| (get_blank_lines_start(&self.text, start), s.end))
+            })
+            .collect();
+
+        return self.remove_lines(spans);
+    }
+
+    // New tables go after the array element holding them, or at the end
+    fn get_section_insertion(&self, path: &[PathSegment]) -> usize {
+        let parent: &[PathSegment] = &path[..path.len() - 1];
+
+        let element: Option<usize> = parent
+            .iter()
+            .rposition(|s| matches!(s, PathSegment::Index(_)));
+
+        return match element {
+            Some(i) => self
+                .layout
+                .sections
+                .iter()
+                .filter(|s| s.header.is_some() && s.path.starts_with(&parent[..=i]))
+                .last()
+                .map_or(self.text.len(), |s| get_line_end(&self.text, s.end)),
+            None => self.text.len(),
+        };
+    }
+
+    fn insert_sections(&mut self, offset: usize, sections: &str) -> Result<(), String> {
+        let mut sections: String = String::from(sections);
+
+        if offset > 0 && !self.text[..offset].ends_with('\n') {
+            sections.insert(0, '\n');
+        }
+
+        return self.splice(offset, offset, sections.as_str());
+    }
+
+    fn add_key(&mut self, parent: &[PathSegment], key: &str, value: &AST) -> Result<(), String> {
+        check_key(key)?;
+
+        let line: String = format!("{} = {}\n", key, render_value(value)?);
+
+        if parent.is_empty() {
+            let global_end: Option<usize> =
+                self.layout.sections[0].keys.last().map(|k| k.value_end);
+            let first_header: Option<usize> = self
+                .layout
+                .sections
+                .iter()
+                .find_map(|s| s.header.map(|(start, _)| start));
+
+            return match (global_end, first_header) {
+                (Some(end), _) => {
+                    let offset: usize = get_line_end(&self.text, end);
+                    self.insert_sections(offset, line.as_str())
+                }
+                (None, Some(start)) => {
+                    let offset: usize = get_comments_start(&self.text, start);
+                    self.splice(offset, offset, format!("{}\n", line).as_str())
+                }
+                (None, None) => self.insert_sections(self.text.len(), line.as_str()),
+            };
+        }
+
+        return match self.layout.get_section(parent) {
+            Some(section) => {
+                let offset: usize = get_line_end(&self.text, section.end);
+                self.insert_sections(offset, line.as_str())
+            }
+            // Tables that only exist through the tables inside them get a header
+            None => {
+                let mut table: TOMLTable = TOMLTable::new();
+                table.insert(key, TOMLTree::Value(value.clone(), None));
+
+                let offset: usize = self.get_section_insertion(parent);
+                self.insert_sections(offset, render_section(parent, &table, false)?.as_str())
+            }
+        };
+    }
+
+    fn add(&mut self, path: &[PathSegment], value: &TOMLTree) -> Result<(), String> {
+        let (last, parent) = match path.split_last() {
+            Some(split) => split,
+            None => return Err(String::from("The document can't be replaced.")),
+        };
+
+        let parent_node: TOMLNode = self.get_node(parent)?;
+
+        return match (parent_node, last, value) {
+            (TOMLNode::Table(table), PathSegment::Key(key), value) => {
+                if table.get(key).is_some() {
+                    return self.replace(path, value);
+                }
+
+                match value {
+                    TOMLTree::Value(value, _) => self.add_key(parent, key, value),
+                    TOMLTree::Table(table) => {
+                        let offset: usize = self.get_section_insertion(path);
+                        self.insert_sections(offset, render_section(path, table, false)?.as_str())
+                    }
+                    TOMLTree::TableArray(tables) => {
+                        let mut sections: String = String::new();
+
+                        for table in tables {
+                            sections += render_section(path, table, true)?.as_str();
+                        }
+
+                        let offset: usize = self.get_section_insertion(path);
+                        self.insert_sections(offset, sections.as_str())
+                    }
+                }
+            }
+            (TOMLNode::TableArray(tables), PathSegment::Index(index), TOMLTree::Table(table)) => {
+                if *index != tables.len() {
+                    return Err(String::from(
+                        "Tables can only be added to the end of an array of tables.",
+                    ));
+                }
+
+                let offset: usize = self.get_section_insertion(path);
+                self.insert_sections(offset, render_section(path, table, true)?.as_str())
+            }
+            (
+                TOMLNode::Value(AST::ASTArray(_), _),
+                PathSegment::Index(_),
+                TOMLTree::Value(value, _),
+            ) => self.edit_value(path, ArrayEdit::Insert(value.clone())),
+            _ => Err(format!(
+                "`{}` can't be added to `{}`.",
+                format_path(&path[path.len() - 1..]),
+                format_path(parent)
+            )),
+        };
+    }
+
+    fn replace(&mut self, path: &[PathSegment], value: &TOMLTree) -> Result<(), String> {
+        let node: TOMLNode = self.get_node(path)?;
+
+        if let TOMLTree::Value(value, _) = value {
+            if let Some(key) = self.layout.get_key(path) {
+                let (start, end) = (key.value_start, key.value_end);
+                return self.splice(start, end, render_value(value)?.as_str());
+            }
+
+            if let TOMLNode::Value(_, _) = node {
+                return self.edit_value(path, ArrayEdit::Replace(value.clone()));
+            }
+        }
+
+        // Anything else is removed and added again
+        let is_element: bool = matches!(path.last(), Some(PathSegment::Index(_)));
+
+        if is_element && !matches!(node, TOMLNode::Value(_, _)) {
+            return Err(String::from(
+                "Tables in an array of tables can't be replaced, only removed or added at the end.",
+            ));
+        }
+
+        self.remove(path)?;
+
+        return self.add(path, value);
+    }
+
+    fn apply(&mut self, operation: &PatchOperation) -> Result<(), String> {
+        let resolve =
+            |patcher: &Patcher, path: &[PathSegment]| resolve_path(&patcher.document, path);
+
+        return match operation {
+            PatchOperation::Add(path, value) => self.add(&resolve(self, path), value),
+            PatchOperation::Remove(path) => self.remove(&resolve(self, path)),
+            PatchOperation::Replace(path, value) => self.replace(&resolve(self, path), value),
+            PatchOperation::Move(from, path) => {
+                let from: Vec<PathSegment> = resolve(self, from);
+                let value: TOMLTree = self.get_node(&from)?.to_tree();
+                let to: Vec<PathSegment> = resolve(self, path);
+
+                if to.starts_with(&from) && to.len() > from.len() {
+                    return Err(String::from("A value can't be moved into itself."));
+                }
+
+                self.remove(&from)?;
+                self.add(&resolve(self, path), &value)
+            }
+            PatchOperation::Copy(from, path) => {
+                let value: TOMLTree = self.get_node(&resolve(self, from))?.to_tree();
+                self.add(&resolve(self, path), &value)
+            }
+            PatchOperation::Test(path, value) => {
+                let path: Vec<PathSegment> = resolve(self, path);
+
+                if is_same_tree(&self.get_node(&path)?.to_tree(), value) {
+                    Ok(())
+                } else {
+                    Err(format!(
+                        "`{}` doesn't have the expected value.",
+                        format_path(&path)
+                    ))
+                }
+            }
+        };
+    }
+}
+
+// Returns the patched source, nothing is changed when an operation fails
+pub fn apply_patch(source: &str, patch: &[PatchOperation]) -> Result<String, PatchError> {
+    let document: TOMLTable = parse_document(source).map_err(|e| PatchError {
+        operation: None,
+        message: format!("The document doesn't parse: {}", e),
+    })?;
+
+    let mut patcher: Patcher = Patcher {
+        text: String::from(source),
+        document,
+        layout: get_document_layout(source),
+    };
+
+    for (i, operation) in patch.iter().enumerate() {
+        patcher.apply(operation).map_err(|message| PatchError {
+            operation: Some(i),
+            message,
+        })?;
+    }
+
+    return Ok(patcher.text);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json::parse_json;
+
+    const DOCUMENT: &str = "name = \"a\"\n\n# Server settings\n[server]\nport = 80 # default\nhosts = [\"a\", \"b\"]\n\n[[workers]]\nid = 1\n";
+
+    fn patch(source: &str, patch: &str) -> Result<String, String> {
+        let operations: Vec<PatchOperation> =
+            parse_json_patch(&parse_json(patch).unwrap()).map_err(|e| e.to_string())?;
+
+        return apply_patch(source, &operations).map_err(|e| e.to_string());
+    }
+
+    #[test]
+    fn add() {
+        assert_eq!(
+            patch(
+                DOCUMENT,
+                r#"[{"op": "add", "path": "version", "value": 2}]"#
+            ),
+            Ok(DOCUMENT.replace("name = \"a\"\n", "name = \"a\"\nversion = 2\n"))
+        );
+        assert_eq!(
+            patch(
+                DOCUMENT,
+                r#"[{"op": "add", "path": "server.hosts[-]", "value": "c"}]"#
+            ),
+            Ok(DOCUMENT.replace("[\"a\", \"b\"]", "[\"a\", \"b\", \"c\"]"))
+        );
+        assert_eq!(
+            patch(
+                DOCUMENT,
+                r#"[{"op": "add", "path": "/workers/-", "value": {"id": 2}}]"#
+            ),
+            Ok(format!("{}\n[[workers]]\nid = 2\n", DOCUMENT))
+        );
+        assert_eq!(
+            patch(
+                DOCUMENT,
+                r#"[{"op": "add", "path": "/workers/0/name", "value": "w"}]"#
+            ),
+            Ok(DOCUMENT.replace("id = 1\n", "id = 1\nname = \"w\"\n"))
+        );
+    }
+
+    #[test]
+    fn add_root_key_before_header_comments() {
+        let source: &str = "# Server settings\n[server]\nport = 80\n";
+
+        assert_eq!(
+            patch(source, r#"[{"op": "add", "path": "name", "value": "a"}]"#),
+            Ok(format!("name = \"a\"\n\n{}", source))
+        );
+    }
+
+    #[test]
+    fn remove() {
+        assert_eq!(
+            patch(DOCUMENT, r#"[{"op": "remove", "path": "server.hosts[0]"}]"#),
+            Ok(DOCUMENT.replace("[\"a\", \"b\"]", "[\"b\"]"))
+        );
+        assert_eq!(
+            patch(DOCUMENT, r#"[{"op": "remove", "path": "/workers"}]"#),
+            Ok(DOCUMENT.replace("\n[[workers]]\nid = 1\n", ""))
+        );
+    }
+
+    #[test]
+    fn replace_keeps_comments() {
+        assert_eq!(
+            patch(
+                DOCUMENT,
+                r#"[{"op": "replace", "path": "server.port", "value": 8080}]"#
+            ),
+            Ok(DOCUMENT.replace("port = 80", "port = 8080"))
+        );
+    }
+
+    #[test]
+    fn move_and_copy() {
+        assert_eq!(
+            patch(
+                DOCUMENT,
+                r#"[{"op": "copy", "from": "name", "path": "server.name"}]"#
+            ),
+            Ok(DOCUMENT.replace(
+                "hosts = [\"a\", \"b\"]\n",
+                "hosts = [\"a\", \"b\"]\nname = \"a\"\n"
+            ))
+        );
+        assert_eq!(
+            patch(
+                DOCUMENT,
+                r#"[{"op": "move", "from": "name", "path": "server.name"}]"#
+            ),
+            Ok(DOCUMENT.replace("name = \"a\"\n\n", "\n").replace(
+                "hosts = [\"a\", \"b\"]\n",
+                "hosts = [\"a\", \"b\"]\nname = \"a\"\n"
+            ))
+        );
+        assert_eq!(
+            patch(
+                DOCUMENT,
+                r#"[{"op": "move", "from": "server", "path": "server.inner"}]"#
+            ),
+            Err(String::from(
+                "Operation 1: A value can't be moved into itself."
+            ))
+        );
+    }
+
+    #[test]
+    fn test_operation() {
+        assert_eq!(
+            patch(
+                DOCUMENT,
+                r#"[{"op": "test", "path": "server.port", "value": 80}]"#
+            ),
+            Ok(String::from(DOCUMENT))
+        );
+        assert_eq!(
+            patch(
+                DOCUMENT,
+                r#"[{"op": "test", "path": "server.port", "value": 81}]"#
+            ),
+            Err(String::from(
+                "Operation 1: `server.port` doesn't have the expected value."
+            ))
+        );
+    }
+
+    #[test]
+    fn failed_patch_changes_nothing() {
+        // The first operations succeed, the document is only returned when all of them do
+        let operations: &str = r#"[
+            {"op": "replace", "path": "server.port", "value": 8080},
+            {"op": "remove", "path": "name"},
+            {"op": "remove", "path": "missing"}
+        ]"#;
+
+        assert_eq!(
+            patch(DOCUMENT, operations),
+            Err(String::from("Operation 3: `missing` doesn't exist."))
+        );
+    }
+
+    #[test]
+    fn malformed_patches() {
+        assert_eq!(
+            patch(DOCUMENT, r#"{"op": "remove", "path": "name"}"#),
+            Err(String::from(
+                "Invalid patch, expected an array of operations."
+            ))
+        );
+        assert_eq!(
+            patch(
+                DOCUMENT,
+                r#"[{"op": "remove", "path": "name"}, {"path": "name"}]"#
+            ),
+            Err(String::from("Operation 2: Missing `op`."))
+        );
+        assert_eq!(
+            patch(DOCUMENT, r#"[{"op": "rename", "path": "name"}]"#),
+            Err(String::from("Operation 1: Unknown operation `rename`."))
+        );
+
+        let toml: TOMLTable = parse_document("[operation]\nop = \"remove\"\n").unwrap();
+        assert_eq!(
+            parse_toml_patch(&toml).map_err(|e| e.to_string()),
+            Err(String::from(
+                "Invalid patch, expected an `[[operations]]` array of tables."
+            ))
+        );
+    }
+
+    #[test]
+    fn invalid_document() {
+        assert_eq!(
+            patch("a = = 1\n", "[]"),
+            Err(String::from(
+                "The document doesn't parse: Unexpected token TokenEqual, with value '='. At line 1, column 5."
+            ))
+        );
+    }
+
+    #[test]
+    fn toml_patch() {
+        let toml: TOMLTable = parse_document(
+            "[[operations]]\nop = \"replace\"\npath = \"server.port\"\nvalue = 8080\n",
+        )
+        .unwrap();
+        let operations: Vec<PatchOperation> = parse_toml_patch(&toml).unwrap();
+
+        assert_eq!(
+            apply_patch(DOCUMENT, &operations),
+            Ok(DOCUMENT.replace("port = 80", "port = 8080"))
+        );
+    }
+}
@@ -0,0 +1,393 @@
+use crate::parser::ast::AST;
+use crate::parser::converter::{convert_string_to_ast, get_value_type, TOMLStringMap};
+use crate::parser::tree::split_tag_name;
+use crate::schema::{Schema, SchemaRule, SchemaSegment, SchemaType};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+// Builds a draft schema from sample documents. Types come from the converter's tags
+// (`int`, `array_string`, ...), a key is required when every instance of its table has
+// it, and integers get the observed range as `min` and `max`.
+
+#[derive(Debug, Clone, Default)]
+struct InferredKey {
+    present: usize,
+    types: BTreeSet<String>,
+    min: Option<i32>,
+    max: Option<i32>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct InferredTable {
+    is_array: bool,
+    // Number of parent instances holding this table
+    present: usize,
+    // Number of tables seen, one per element for arrays of tables
+    instances: usize,
+    keys: BTreeMap<String, InferredKey>,
+    tables: BTreeMap<String, InferredTable>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SchemaInference {
+    root: InferredTable,
+}
+
+fn get_schema_type(value_type: &str) -> Option<SchemaType> {
+    if value_type.starts_with("array") {
+        return Some(SchemaType::Array);
+    }
+
+    return SchemaType::from_name(value_type);
+}
+
+// `array_array_int` holds `array_int`, an empty `array` holds nothing known
+fn get_element_type(value_type: &str) -> Option<&str> {
+    return value_type.strip_prefix("array_");
+}
+
+fn collect_ints(value: &AST, min: &mut Option<i32>, max: &mut Option<i32>) {
+    match value {
+        AST::ASTInt(v) => {
+            *min = Some(min.map_or(*v, |min| min.min(*v)));
+            *max = Some(max.map_or(*v, |max| max.max(*v)));
+        }
+        AST::ASTArray(values) => {
+            for v in values {
+                collect_ints(v, min, max);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn add_value_rules(
+    rules: &mut Vec<SchemaRule>,
+    path: Vec<SchemaSegment>,
+    types: &BTreeSet<&str>,
+    required: bool,
+    range: (Option<i32>, Option<i32>),
+) {
+    let schema_types: BTreeSet<String> = types
+        .iter()
+        .filter_map(|t| get_schema_type(t))
+        .map(|t| String::from(t.get_name()))
+        .collect();
+
+    let mut rule: SchemaRule = SchemaRule::new(path.clone());
+    rule.required = required;
+
+    // Mixed types are left open
+    if schema_types.len() == 1 {
+        rule.value_type = get_schema_type(types.iter().next().unwrap());
+    }
+
+    if rule.value_type == Some(SchemaType::Int) {
+        rule.min = range.0.map(|v| v as i64);
+        rule.max = range.1.map(|v| v as i64);
+    }
+
+    let is_array: bool = rule.value_type == Some(SchemaType::Array);
+
+    rules.push(rule);
+
+    if is_array {
+        let elements: BTreeSet<&str> = types.iter().filter_map(|t| get_element_type(t)).collect();
+
+        if !elements.is_empty() {
+            let mut element_path: Vec<SchemaSegment> = path;
+            element_path.push(SchemaSegment::Any);
+
+            add_value_rules(rules, element_path, &elements, false, range);
+        }
+    }
+}
+
+fn add_table_rules(rules: &mut Vec<SchemaRule>, table: &InferredTable, path: &[SchemaSegment]) {
+    for (name, key) in &table.keys {
+        let mut key_path: Vec<SchemaSegment> = path.to_vec();
+        key_path.push(SchemaSegment::Key(name.clone()));
+
+        let types: BTreeSet<&str> = key.types.iter().map(|t| t.as_str()).collect();
+
+        add_value_rules(
+            rules,
+            key_path,
+            &types,
+            key.present == table.instances,
+            (key.min, key.max),
+        );
+    }
+
+    for (name, child) in &table.tables {
+        let mut child_path: Vec<SchemaSegment> = path.to_vec();
+        child_path.push(SchemaSegment::Key(name.clone()));
+
+        let mut rule: SchemaRule = SchemaRule::new(child_path.clone());
+        rule.required = child.present == table.instances;
+
+        if child.is_array {
+            rule.value_type = Some(SchemaType::Array);
+            rules.push(rule);
+
+            child_path.push(SchemaSegment::Any);
+
+            let mut element: SchemaRule = SchemaRule::new(child_path.clone());
+            element.value_type = Some(SchemaType::Table);
+            rules.push(element);
+        } else {
+            rule.value_type = Some(SchemaType::Table);
+            rules.push(rule);
+        }
+
+        add_table_rules(rules, child, &child_path);
+    }
+}
+
+const RUST_KEYWORDS: [&str; 35] = [
+    "as", "async", "await", "break", "const", "continue", "dyn", "else", "enum", "extern", "false",
+    "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref",
+    "return", "static", "struct", "trait", "true", "type", "unsafe", "use", "where", "while",
+    "yield",
+];
+
+fn get_struct_name(path: &[&str]) -> String {
+    let mut name: String = String::new();
+
+    for key in path {
+        for word in key.split(|c: char| !c.is_ascii_alphanumeric()) {
+            let mut chars = word.chars();
+
+            if let Some(first) = chars.next() {
+                name.push(first.to_ascii_uppercase());
+                name.push_str(chars.as_str());
+            }
+        }
+    }
+
+    // Identifiers can't start with a digit
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        name.insert(0, 'T');
+    }
+
+    return name;
+}
+
+fn get_field_name(key: &str) -> String {
+    let mut name: String = key
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        name.insert(0, '_');
+    }
+
+    // These can't be raw identifiers
+    if name == "self" || name == "crate" || name == "super" {
+        name.push('_');
+    } else if RUST_KEYWORDS.contains(&name.as_str()) {
+        name.insert_str(0, "r#");
+    }
+
+    return name;
+}
+
+// `None` when the observed types don't agree
+fn get_rust_type(types: &BTreeSet<&str>) -> Option<String> {
+    // Empty arrays agree with any other array
+    let mut types: BTreeSet<&str> = types.clone();
+
+    if types.len() > 1 && types.iter().any(|t| t.starts_with("array_")) {
+        types.remove("array");
+    }
+
+    if types.len() != 1 {
+        return None;
+    }
+
+    let value_type: &str = types.iter().next().unwrap();
+
+    return match value_type {
+        "bool" => Some(String::from("bool")),
+        "int" => Some(String::from("i32")),
+        "string" | "datetime" => Some(String::from("String")),
+        "array" => Some(String::from("Vec<TOMLTree>")),
+        _ => {
+            let element: &str = get_element_type(value_type)?;
+            let element: String = get_rust_type(&BTreeSet::from([element]))?;
+
+            Some(format!("Vec<{}>", element))
+        }
+    };
+}
+
+fn write_struct(
+    table: &InferredTable,
+    name: &str,
+    path: &mut Vec<String>,
+    output: &mut Vec<String>,
+) {
+    let keys: Vec<&str> = path.iter().map(|k| k.as_str()).collect();
+
+    let mut string: String = String::from("#[derive(Debug, FromToml)]\n");
+    string += format!("pub struct {} {{\n", name).as_str();
+
+    let mut add_field = |key: &str, rust_type: String, required: bool| {
+        let field: String = get_field_name(key);
+
+        if field.trim_start_matches("r#") != key {
+            string += format!("    #[toml(rename = \"{}\")]\n", key).as_str();
+        }
+
+        if required {
+            string += format!("    pub {}: {},\n", field, rust_type).as_str();
+        } else {
+            string += format!("    pub {}: Option<{}>,\n", field, rust_type).as_str();
+        }
+    };
+
+    for (key, inferred) in &table.keys {
+        let types: BTreeSet<&str> = inferred.types.iter().map(|t| t.as_str()).collect();
+        let rust_type: String = get_rust_type(&types).unwrap_or(String::from("TOMLTree"));
+
+        add_field(key, rust_type, inferred.present == table.instances);
+    }
+
+    for (key, child) in &table.tables {
+        let mut child_keys: Vec<&str> = keys.clone();
+        child_keys.push(key.as_str());
+
+        let child_name: String = get_struct_name(&child_keys);
+        let rust_type: String = if child.is_array {
+            format!("Vec<{}>", child_name)
+        } else {
+            child_name
+        };
+
+        add_field(key, rust_type, child.present == table.instances);
+    }
+
+    string += "}\n";
+    output.push(string);
+
+    for (key, child) in &table.tables {
+        path.push(key.clone());
+
+        let keys: Vec<&str> = path.iter().map(|k| k.as_str()).collect();
+        let child_name: String = get_struct_name(&keys);
+
+        write_struct(child, child_name.as_str(), path, output);
+        path.pop();
+    }
+}
+
+impl SchemaInference {
+    pub fn new() -> Self {
+        return SchemaInference::default();
+    }
+
+    pub fn get_document_count(&self) -> usize {
+        return self.root.instances;
+    }
+
+    pub fn add_document(&mut self, map: &TOMLStringMap) {
+        self.root.instances += 1;
+
+        // Table instances already counted in this document, by their concrete path
+        let mut seen: HashSet<String> = HashSet::new();
+
+        let mut names: Vec<&String> = map.tags.keys().collect();
+        names.sort();
+
+        for name in names {
+            let tag = &map.tags[name];
+            let mut table: &mut InferredTable = &mut self.root;
+
+            if name != "." {
+                let mut concrete: String = String::new();
+
+                for (key, index) in split_tag_name(name.as_str()) {
+                    let child: &mut InferredTable =
+                        table.tables.entry(String::from(key)).or_default();
+                    child.is_array |= index.is_some();
+
+                    concrete += "/";
+                    concrete += key;
+
+                    if seen.insert(concrete.clone()) {
+                        child.present += 1;
+                    }
+
+                    if let Some(index) = index {
+                        concrete += format!("[{}]", index).as_str();
+
+                        if seen.insert(concrete.clone()) {
+                            child.instances += 1;
+                        }
+                    } else if !child.is_array {
+                        child.instances = child.present;
+                    }
+
+                    table = child;
+                }
+            }
+
+            for (key, value) in &tag.values {
+                let inferred: &mut InferredKey = table.keys.entry(key.clone()).or_default();
+                inferred.present += 1;
+
+                if let Some(value_type) = get_value_type(value) {
+                    inferred.types.insert(String::from(value_type));
+                }
+
+                if let Some(ast) = convert_string_to_ast(value) {
+                    collect_ints(&ast, &mut inferred.min, &mut inferred.max);
+                }
+            }
+        }
+    }
+
+    pub fn to_schema(&self) -> Schema {
+        let mut rules: Vec<SchemaRule> = vec![];
+
+        add_table_rules(&mut rules, &self.root, &[]);
+
+        return Schema { rules };
+    }
+
+    // A skeleton for `#[derive(FromToml)]`, optional keys become `Option`
+    pub fn to_rust_structs(&self, name: &str) -> String {
+        let mut output: Vec<String> = vec![];
+
+        write_struct(&self.root, name, &mut vec![], &mut output);
+
+        let mut string: String = String::from("use toml_parser::FromToml;\n");
+
+        if output.iter().any(|s| s.contains("TOMLTree")) {
+            string += "use toml_parser::parser::tree::TOMLTree;\n";
+        }
+
+        for s in output {
+            string += "\n";
+            string += s.as_str();
+        }
+
+        return string;
+    }
+}
+
+pub fn infer_schema(documents: &[TOMLStringMap]) -> SchemaInference {
+    let mut inference: SchemaInference = SchemaInference::new();
+
+    for document in documents {
+        inference.add_document(document);
+    }
+
+    return inference;
+}
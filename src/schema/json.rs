@@ -0,0 +1,575 @@
+use crate::json::{convert_ast_to_json, convert_json_to_ast, JSONValue};
+use crate::parser::ast::AST;
+use crate::parser::converter::{try_convert_ast_value_to_string, TOMLStringMap};
+use crate::parser::token::Token;
+use crate::parser::writer::convert_string_map_to_toml;
+use crate::schema::regex::Regex;
+use crate::schema::{Schema, SchemaRule, SchemaSegment, SchemaType};
+
+// Mapping between schema rules and JSON Schema draft 2020-12:
+//
+//   type            `type` (`datetime` is a string with `format = "date-time"`)
+//   required        `required` of the parent object
+//   min, max        `minimum`, `maximum`
+//   pattern         `pattern`
+//   enum            `enum`
+//   min-length      `minLength` for strings, `minItems` for arrays
+//   additional-keys `additionalProperties = false`
+//   `*`             `items` of arrays, `additionalProperties` of tables
+
+const JSON_SCHEMA_DRAFT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+// Deep enough for any real schema, stops recursive `$ref`s
+const MAX_DEPTH: usize = 64;
+
+fn format_schema_path(path: &[SchemaSegment]) -> String {
+    let keys: Vec<&str> = path
+        .iter()
+        .map(|segment| match segment {
+            SchemaSegment::Key(key) => key.as_str(),
+            SchemaSegment::Any => "*",
+        })
+        .collect();
+
+    return keys.join(".");
+}
+
+fn get_object_entry<'a>(node: &'a mut JSONValue, key: &str) -> &'a mut JSONValue {
+    let entries: &mut Vec<(String, JSONValue)> = match node {
+        JSONValue::Object(entries) => entries,
+        _ => panic!("[ERROR] JSON schema node is not an object."),
+    };
+
+    let index: usize = match entries.iter().position(|(k, _)| k == key) {
+        Some(index) => index,
+        None => {
+            entries.push((String::from(key), JSONValue::Object(vec![])));
+            entries.len() - 1
+        }
+    };
+
+    // `additionalProperties = false` becomes a schema once `*` has a rule
+    if !matches!(entries[index].1, JSONValue::Object(_)) {
+        entries[index].1 = JSONValue::Object(vec![]);
+    }
+
+    return &mut entries[index].1;
+}
+
+impl Schema {
+    fn get_rule_type(&self, path: &[SchemaSegment]) -> Option<&SchemaType> {
+        return self
+            .rules
+            .iter()
+            .find(|r| r.path == path && r.value_type.is_some())
+            .and_then(|r| r.value_type.as_ref());
+    }
+
+    fn get_json_node<'a>(
+        &self,
+        root: &'a mut JSONValue,
+        path: &[SchemaSegment],
+    ) -> &'a mut JSONValue {
+        let mut node: &mut JSONValue = root;
+
+        for (i, segment) in path.iter().enumerate() {
+            node = match segment {
+                SchemaSegment::Key(key) => {
+                    get_object_entry(get_object_entry(node, "properties"), key)
+                }
+                SchemaSegment::Any => {
+                    if self.get_rule_type(&path[0..i]) == Some(&SchemaType::Table) {
+                        get_object_entry(node, "additionalProperties")
+                    } else {
+                        get_object_entry(node, "items")
+                    }
+                }
+            };
+        }
+
+        return node;
+    }
+
+    pub fn to_json_schema(&self) -> JSONValue {
+        let mut root: JSONValue = JSONValue::Object(vec![
+            (
+                String::from("$schema"),
+                JSONValue::String(String::from(JSON_SCHEMA_DRAFT)),
+            ),
+            (
+                String::from("type"),
+                JSONValue::String(String::from("object")),
+            ),
+        ]);
+
+        // Parents first, so `additionalProperties = false` can be replaced by a `*` rule
+        let mut rules: Vec<&SchemaRule> = self.rules.iter().collect();
+        rules.sort_by_key(|r| r.path.len());
+
+        for rule in rules {
+            let node: &mut JSONValue = self.get_json_node(&mut root, &rule.path);
+
+            if let Some(value_type) = &rule.value_type {
+                let name: &str = match value_type {
+                    SchemaType::Bool => "boolean",
+                    SchemaType::Int => "integer",
+                    SchemaType::String | SchemaType::DateTime => "string",
+                    SchemaType::Array => "array",
+                    SchemaType::Table => "object",
+                };
+
+                node.insert("type", JSONValue::String(String::from(name)));
+
+                if *value_type == SchemaType::DateTime {
+                    node.insert("format", JSONValue::String(String::from("date-time")));
+                }
+            }
+
+            if let Some(min) = rule.min {
+                node.insert("minimum", JSONValue::Int(min));
+            }
+
+            if let Some(max) = rule.max {
+                node.insert("maximum", JSONValue::Int(max));
+            }
+
+            if let Some(pattern) = &rule.pattern {
+                node.insert("pattern", JSONValue::String(String::from(pattern.as_str())));
+            }
+
+            if let Some(values) = &rule.values {
+                let values: Vec<JSONValue> =
+                    values.iter().filter_map(convert_ast_to_json).collect();
+                node.insert("enum", JSONValue::Array(values));
+            }
+
+            // Without a type both keywords are needed, each only applies to its own type
+            let lengths: [(Option<usize>, &str, &str); 2] = [
+                (rule.min_length, "minLength", "minItems"),
+                (rule.max_length, "maxLength", "maxItems"),
+            ];
+
+            for (length, string_key, array_key) in lengths {
+                if let Some(length) = length {
+                    let length: JSONValue = JSONValue::Int(length as i64);
+
+                    match rule.value_type {
+                        Some(SchemaType::String) => node.insert(string_key, length),
+                        Some(SchemaType::Array) => node.insert(array_key, length),
+                        None => {
+                            node.insert(string_key, length.clone());
+                            node.insert(array_key, length);
+                        }
+                        _ => {}
+                    }
+                }
+            }
+
+            if !rule.additional_keys && node.get("additionalProperties").is_none() {
+                node.insert("additionalProperties", JSONValue::Bool(false));
+            }
+
+            if let (true, Some(SchemaSegment::Key(key))) = (rule.required, rule.path.last()) {
+                let parent: &mut JSONValue =
+                    self.get_json_node(&mut root, &rule.path[0..rule.path.len() - 1]);
+
+                let mut required: Vec<JSONValue> = match parent.get("required") {
+                    Some(JSONValue::Array(required)) => required.clone(),
+                    _ => vec![],
+                };
+                required.push(JSONValue::String(key.clone()));
+
+                parent.insert("required", JSONValue::Array(required));
+            }
+        }
+
+        return root;
+    }
+
+    pub fn from_json_schema(value: &JSONValue) -> Result<Self, String> {
+        let mut schema: Schema = Schema { rules: vec![] };
+
+        schema.import_json_node(value, value, &mut vec![], 0)?;
+
+        return Ok(schema);
+    }
+
+    fn get_rule_mut(&mut self, path: &[SchemaSegment]) -> &mut SchemaRule {
+        let index: usize = match self.rules.iter().position(|r| r.path == path) {
+            Some(index) => index,
+            None => {
+                self.rules.push(SchemaRule::new(path.to_vec()));
+                self.rules.len() - 1
+            }
+        };
+
+        return &mut self.rules[index];
+    }
+
+    fn import_json_node(
+        &mut self,
+        root: &JSONValue,
+        node: &JSONValue,
+        path: &mut Vec<SchemaSegment>,
+        depth: usize,
+    ) -> Result<(), String> {
+        let at: String = format_schema_path(path);
+
+        if depth > MAX_DEPTH {
+            return Err(format!(
+                "Schema at `{}` is nested too deeply, recursive `$ref`s are not supported.",
+                at
+            ));
+        }
+
+        // Make sure the key is known, even without constraints
+        self.get_rule_mut(path);
+
+        match node {
+            JSONValue::Bool(true) => return Ok(()),
+            JSONValue::Object(_) => {}
+            _ => return Err(format!("Expected a schema object at `{}`.", at)),
+        }
+
+        if let Some(reference) = node.get("$ref").and_then(|r| r.as_str()) {
+            let mut target: &JSONValue = root;
+
+            // Only references into this document, like `#/$defs/name`
+            if !reference.starts_with('#') {
+                return Err(format!(
+                    "Unsupported reference `{}` at `{}`.",
+                    reference, at
+                ));
+            }
+
+            for key in reference[1..].split('/').filter(|k| !k.is_empty()) {
+                let key: String = key.replace("~1", "/").replace("~0", "~");
+
+                target = match target.get(key.as_str()) {
+                    Some(target) => target,
+                    None => return Err(format!("Unknown reference `{}` at `{}`.", reference, at)),
+                };
+            }
+
+            self.import_json_node(root, target, path, depth + 1)?;
+        }
+
+        if let Some(JSONValue::Array(schemas)) = node.get("allOf") {
+            for schema in schemas {
+                self.import_json_node(root, schema, path, depth + 1)?;
+            }
+        }
+
+        // Nullable types are written as `["string", "null"]`
+        let types: Vec<&str> = match node.get("type") {
+            Some(JSONValue::String(t)) => vec![t.as_str()],
+            Some(JSONValue::Array(types)) => types
+                .iter()
+                .filter_map(|t| t.as_str())
+                .filter(|t| *t != "null")
+                .collect(),
+            _ => vec![],
+        };
+
+        if types.len() == 1 {
+            let format: Option<&str> = node.get("format").and_then(|f| f.as_str());
+
+            let value_type: SchemaType = match (types[0], format) {
+                ("string", Some("date-time" | "date" | "time")) => SchemaType::DateTime,
+                ("string", _) => SchemaType::String,
+                ("boolean", _) => SchemaType::Bool,
+                ("integer" | "number", _) => SchemaType::Int,
+                ("array", _) => SchemaType::Array,
+                ("object", _) => SchemaType::Table,
+                (t, _) => return Err(format!("Unsupported type `{}` at `{}`.", t, at)),
+            };
+
+            self.get_rule_mut(path).value_type = Some(value_type);
+        }
+
+        // Integers are 32 bit, bounds outside of that range always hold
+        let get_bound = |key: &str, offset: i64| {
+            return node
+                .get(key)
+                .and_then(|v| v.as_i64())
+                .map(|v| v + offset)
+                .filter(|v| *v >= i32::MIN as i64 && *v <= i32::MAX as i64);
+        };
+
+        if let Some(min) = get_bound("minimum", 0).or(get_bound("exclusiveMinimum", 1)) {
+            self.get_rule_mut(path).min = Some(min);
+        }
+
+        if let Some(max) = get_bound("maximum", 0).or(get_bound("exclusiveMaximum", -1)) {
+            self.get_rule_mut(path).max = Some(max);
+        }
+
+        if let Some(pattern) = node.get("pattern").and_then(|p| p.as_str()) {
+            match Regex::new(pattern) {
+                Ok(regex) => self.get_rule_mut(path).pattern = Some(regex),
+                Err(e) => return Err(format!("Invalid pattern `{}` at `{}`: {}.", pattern, at, e)),
+            }
+        }
+
+        let values: Option<Vec<JSONValue>> = match (node.get("enum"), node.get("const")) {
+            (Some(JSONValue::Array(values)), _) => Some(values.clone()),
+            (None, Some(value)) => Some(vec![value.clone()]),
+            _ => None,
+        };
+
+        if let Some(values) = values {
+            let mut asts: Vec<AST> = vec![];
+
+            for value in values.iter().filter(|v| **v != JSONValue::Null) {
+                match convert_json_to_ast(value) {
+                    Some(ast) => asts.push(ast),
+                    None => return Err(format!("Unsupported enum value at `{}`.", at)),
+                }
+            }
+
+            self.get_rule_mut(path).values = Some(asts);
+        }
+
+        let get_length = |keys: [&str; 2]| {
+            return keys
+                .iter()
+                .find_map(|k| node.get(k).and_then(|v| v.as_i64()))
+                .filter(|v| *v >= 0)
+                .map(|v| v as usize);
+        };
+
+        if let Some(length) = get_length(["minLength", "minItems"]) {
+            self.get_rule_mut(path).min_length = Some(length);
+        }
+
+        if let Some(length) = get_length(["maxLength", "maxItems"]) {
+            self.get_rule_mut(path).max_length = Some(length);
+        }
+
+        if let Some(JSONValue::Object(properties)) = node.get("properties") {
+            for (key, property) in properties {
+                // Dots would be read back as nested keys
+                if key.contains('.') || key == "*" {
+                    return Err(format!(
+                        "Key `{}` at `{}` can't be used in a schema.",
+                        key, at
+                    ));
+                }
+
+                path.push(SchemaSegment::Key(key.clone()));
+                self.import_json_node(root, property, path, depth + 1)?;
+                path.pop();
+            }
+        }
+
+        if let Some(JSONValue::Array(required)) = node.get("required") {
+            for key in required.iter().filter_map(|k| k.as_str()) {
+                path.push(SchemaSegment::Key(String::from(key)));
+                self.get_rule_mut(path).required = true;
+                path.pop();
+            }
+        }
+
+        match node.get("additionalProperties") {
+            Some(JSONValue::Bool(false)) => self.get_rule_mut(path).additional_keys = false,
+            Some(additional @ JSONValue::Object(_)) => {
+                path.push(SchemaSegment::Any);
+                self.import_json_node(root, additional, path, depth + 1)?;
+                path.pop();
+            }
+            _ => {}
+        }
+
+        if let Some(items @ JSONValue::Object(_)) = node.get("items") {
+            path.push(SchemaSegment::Any);
+            self.import_json_node(root, items, path, depth + 1)?;
+            path.pop();
+        }
+
+        return Ok(());
+    }
+
+    pub fn to_toml_string(&self) -> Result<String, String> {
+        let mut map: TOMLStringMap = TOMLStringMap::new();
+
+        for rule in &self.rules {
+            let name: String = if rule.path.is_empty() {
+                String::from(".")
+            } else {
+                format_schema_path(&rule.path)
+            };
+
+            // Tags have to be valid ids, `*` can't start one
+            if name != "." && !Token::is_valid_id(name.as_str()) {
+                return Err(format!("Rule `{}` can't be written as a tag.", name));
+            }
+
+            map.add_tag(name.as_str(), false);
+
+            let mut values: Vec<(&str, AST)> = vec![];
+
+            if let Some(value_type) = &rule.value_type {
+                values.push(("type", AST::ASTString(String::from(value_type.get_name()))));
+            }
+
+            if rule.required {
+                values.push(("required", AST::ASTBool(true)));
+            }
+
+            if !rule.additional_keys {
+                values.push(("additional-keys", AST::ASTBool(false)));
+            }
+
+            // Bounds and lengths were read from TOML or limited to 32 bit on import
+            if let Some(min) = rule.min {
+                values.push(("min", AST::ASTInt(min as i32)));
+            }
+
+            if let Some(max) = rule.max {
+                values.push(("max", AST::ASTInt(max as i32)));
+            }
+
+            if let Some(pattern) = &rule.pattern {
+                if pattern.as_str().contains('"') {
+                    return Err(format!(
+                        "Pattern of `{}` can't be written, it contains `\"`.",
+                        name
+                    ));
+                }
+
+                values.push(("pattern", AST::ASTString(String::from(pattern.as_str()))));
+            }
+
+            if let Some(v) = &rule.values {
+                values.push(("enum", AST::ASTArray(v.clone())));
+            }
+
+            if let Some(length) = rule.min_length {
+                values.push((
+                    "min-length",
+                    AST::ASTInt(length.min(i32::MAX as usize) as i32),
+                ));
+            }
+
+            if let Some(length) = rule.max_length {
+                values.push((
+                    "max-length",
+                    AST::ASTInt(length.min(i32::MAX as usize) as i32),
+                ));
+            }
+
+            for (key, value) in values {
+                // Enums from JSON may mix types, or hold strings TOML can't write
+                let value: String = match try_convert_ast_value_to_string(&value, true) {
+                    Ok(value) if !value.contains('"') => value,
+                    _ => return Err(format!("`{}` of `{}` can't be written as TOML.", key, name)),
+                };
+
+                map.add_value(name.as_str(), key, value.as_str());
+            }
+        }
+
+        return Ok(convert_string_map_to_toml(&map));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json::convert_json_to_string;
+
+    fn to_json_schema(schema: &str) -> String {
+        let schema: Schema = Schema::from_string(schema).unwrap();
+
+        return convert_json_to_string(&schema.to_json_schema(), false);
+    }
+
+    fn sorted_rules(schema: &Schema) -> Vec<SchemaRule> {
+        // The document itself is an object in JSON Schema
+        let mut rules: Vec<SchemaRule> = schema
+            .rules
+            .iter()
+            .filter(|r| !r.path.is_empty())
+            .cloned()
+            .map(|mut r| {
+                r.position = None;
+                r
+            })
+            .collect();
+        rules.sort_by_key(|r| format_schema_path(&r.path));
+
+        return rules;
+    }
+
+    #[test]
+    fn every_type() {
+        let schema: &str = "[a]\ntype = \"bool\"\n[b]\ntype = \"int\"\n[c]\ntype = \"string\"\n[d]\ntype = \"datetime\"\n[e]\ntype = \"array\"\n[f]\ntype = \"table\"\n";
+
+        assert_eq!(
+            to_json_schema(schema),
+            concat!(
+                "{\"$schema\":\"https://json-schema.org/draft/2020-12/schema\",\"type\":\"object\",",
+                "\"properties\":{\"a\":{\"type\":\"boolean\"},\"b\":{\"type\":\"integer\"},",
+                "\"c\":{\"type\":\"string\"},\"d\":{\"type\":\"string\",\"format\":\"date-time\"},",
+                "\"e\":{\"type\":\"array\"},\"f\":{\"type\":\"object\"}}}"
+            )
+        );
+    }
+
+    #[test]
+    fn constraints() {
+        let schema: &str = "[port]\ntype = \"int\"\nrequired = true\nmin = 1\nmax = 65535\n\n[name]\npattern = \"^[a-z]+$\"\nenum = [\"a\", \"b\"]\nmin-length = 1\n";
+
+        assert_eq!(
+            to_json_schema(schema),
+            concat!(
+                "{\"$schema\":\"https://json-schema.org/draft/2020-12/schema\",\"type\":\"object\",",
+                "\"properties\":{\"name\":{\"pattern\":\"^[a-z]+$\",\"enum\":[\"a\",\"b\"],",
+                "\"minLength\":1,\"minItems\":1},\"port\":{\"type\":\"integer\",\"minimum\":1,",
+                "\"maximum\":65535}},\"required\":[\"port\"]}"
+            )
+        );
+    }
+
+    #[test]
+    fn array_of_tables() {
+        let schema: &str = "[servers]\ntype = \"array\"\n\n[servers.*]\ntype = \"table\"\nadditional-keys = false\n\n[servers.*.port]\ntype = \"int\"\nrequired = true\n";
+
+        assert_eq!(
+            to_json_schema(schema),
+            concat!(
+                "{\"$schema\":\"https://json-schema.org/draft/2020-12/schema\",\"type\":\"object\",",
+                "\"properties\":{\"servers\":{\"type\":\"array\",\"items\":{\"type\":\"object\",",
+                "\"additionalProperties\":false,\"properties\":{\"port\":{\"type\":\"integer\"}},",
+                "\"required\":[\"port\"]}}}}"
+            )
+        );
+    }
+
+    #[test]
+    fn table_wildcard() {
+        let schema: &str =
+            "[env]\ntype = \"table\"\nadditional-keys = false\n\n[env.*]\ntype = \"string\"\n";
+
+        assert_eq!(
+            to_json_schema(schema),
+            concat!(
+                "{\"$schema\":\"https://json-schema.org/draft/2020-12/schema\",\"type\":\"object\",",
+                "\"properties\":{\"env\":{\"type\":\"object\",",
+                "\"additionalProperties\":{\"type\":\"string\"}}}}"
+            )
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let schema: Schema = Schema::from_string(
+            "[servers]\ntype = \"array\"\nmax-length = 3\n\n[servers.*]\ntype = \"table\"\n\n[servers.*.port]\ntype = \"int\"\nrequired = true\nmin = 1\n\n[servers.*.host]\ntype = \"string\"\npattern = \"^[a-z.]+$\"\n\n[started]\ntype = \"datetime\"\n",
+        )
+        .unwrap();
+
+        let imported: Schema = Schema::from_json_schema(&schema.to_json_schema()).unwrap();
+
+        assert_eq!(sorted_rules(&imported), sorted_rules(&schema));
+    }
+}
@@ -0,0 +1,245 @@
+pub mod infer;
+pub mod json;
+pub mod regex;
+pub mod validator;
+
+use crate::parser::ast::AST;
+use crate::parser::converter::{convert_string_to_ast, TOMLStringMap, TOMLStringTag};
+use crate::parser::error::ParseError;
+use crate::parser::token::Position;
+use crate::parser::tree::parse_string_map;
+use crate::schema::regex::Regex;
+use std::fs;
+
+// A schema is itself a TOML document. Every tag is a rule for the value at that path,
+// `*` matches any array element or table key:
+//
+//   [servers.*.port]
+//   type = "int"
+//   required = true
+//   min = 1
+//   max = 65535
+//
+// Global keys are the rule for the document itself.
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SchemaType {
+    Bool,
+    Int,
+    String,
+    DateTime,
+    Array,
+    Table,
+}
+
+impl SchemaType {
+    pub fn from_name(name: &str) -> Option<Self> {
+        return match name {
+            "bool" => Some(SchemaType::Bool),
+            "int" => Some(SchemaType::Int),
+            "string" => Some(SchemaType::String),
+            "datetime" => Some(SchemaType::DateTime),
+            "array" => Some(SchemaType::Array),
+            "table" => Some(SchemaType::Table),
+            _ => None,
+        };
+    }
+
+    pub fn get_name(&self) -> &str {
+        return match self {
+            SchemaType::Bool => "bool",
+            SchemaType::Int => "int",
+            SchemaType::String => "string",
+            SchemaType::DateTime => "datetime",
+            SchemaType::Array => "array",
+            SchemaType::Table => "table",
+        };
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SchemaSegment {
+    Key(String),
+    Any,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaRule {
+    pub path: Vec<SchemaSegment>,
+    pub value_type: Option<SchemaType>,
+    pub required: bool,
+    pub min: Option<i64>,
+    pub max: Option<i64>,
+    pub pattern: Option<Regex>,
+    pub values: Option<Vec<AST>>,
+    pub min_length: Option<usize>,
+    pub max_length: Option<usize>,
+    pub additional_keys: bool,
+    pub position: Option<Position>,
+}
+
+impl SchemaRule {
+    pub fn new(path: Vec<SchemaSegment>) -> Self {
+        return SchemaRule {
+            path,
+            value_type: None,
+            required: false,
+            min: None,
+            max: None,
+            pattern: None,
+            values: None,
+            min_length: None,
+            max_length: None,
+            additional_keys: true,
+            position: None,
+        };
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schema {
+    pub rules: Vec<SchemaRule>,
+}
+
+pub(crate) fn parse_schema_path(name: &str) -> Vec<SchemaSegment> {
+    if name == "." {
+        return vec![];
+    }
+
+    return name
+        .split('.')
+        .map(|key| {
+            if key == "*" {
+                SchemaSegment::Any
+            } else {
+                SchemaSegment::Key(String::from(key))
+            }
+        })
+        .collect();
+}
+
+fn parse_length(value: AST, key: &str, position: Position) -> Result<usize, ParseError> {
+    return match value {
+        AST::ASTInt(v) if v >= 0 => Ok(v as usize),
+        _ => Err(ParseError::new(
+            format!("`{}` must be a positive integer.", key).as_str(),
+            position,
+        )),
+    };
+}
+
+fn parse_rule(tag: &TOMLStringTag) -> Result<SchemaRule, ParseError> {
+    let tag_position: Position = tag.position.unwrap_or(Position::new());
+
+    // Arrays of tags are stored as `name[index]`
+    if tag.name.contains('[') {
+        return Err(ParseError::new(
+            format!(
+                "Arrays of tags can't be used in a schema, found `{}`.",
+                tag.name
+            )
+            .as_str(),
+            tag_position,
+        ));
+    }
+
+    let mut rule: SchemaRule = SchemaRule::new(parse_schema_path(tag.name.as_str()));
+    rule.position = tag.position;
+
+    let mut keys: Vec<&String> = tag.values.keys().collect();
+    keys.sort();
+
+    for key in keys {
+        let position: Position = tag.positions.get(key).copied().unwrap_or(tag_position);
+
+        let value: AST = match convert_string_to_ast(&tag.values[key]) {
+            Some(value) => value,
+            None => {
+                return Err(ParseError::new(
+                    format!("Failed to read `{}`.", key).as_str(),
+                    position,
+                ));
+            }
+        };
+
+        let wrong_type = |expected: &str| {
+            return ParseError::new(
+                format!("`{}` must be {}.", key, expected).as_str(),
+                position,
+            );
+        };
+
+        match (key.as_str(), value) {
+            ("type", AST::ASTString(v)) => {
+                rule.value_type = match SchemaType::from_name(v.as_str()) {
+                    Some(t) => Some(t),
+                    None => {
+                        return Err(ParseError::new(
+                            format!("Unknown type `{}`.", v).as_str(),
+                            position,
+                        ));
+                    }
+                };
+            }
+            ("required", AST::ASTBool(v)) => rule.required = v,
+            ("additional-keys", AST::ASTBool(v)) => rule.additional_keys = v,
+            ("min", AST::ASTInt(v)) => rule.min = Some(v as i64),
+            ("max", AST::ASTInt(v)) => rule.max = Some(v as i64),
+            ("pattern", AST::ASTString(v)) => {
+                rule.pattern = match Regex::new(v.as_str()) {
+                    Ok(regex) => Some(regex),
+                    Err(e) => {
+                        return Err(ParseError::new(
+                            format!("Invalid pattern `{}`: {}.", v, e).as_str(),
+                            position,
+                        ));
+                    }
+                };
+            }
+            ("enum", AST::ASTArray(v)) if !v.is_empty() => rule.values = Some(v),
+            ("min-length", v) => rule.min_length = Some(parse_length(v, key, position)?),
+            ("max-length", v) => rule.max_length = Some(parse_length(v, key, position)?),
+            ("type" | "pattern", _) => return Err(wrong_type("a string")),
+            ("required" | "additional-keys", _) => return Err(wrong_type("a bool")),
+            ("min" | "max", _) => return Err(wrong_type("an integer")),
+            ("enum", _) => return Err(wrong_type("a non empty array")),
+            _ => {
+                return Err(ParseError::new(
+                    format!("Unknown schema key `{}`.", key).as_str(),
+                    position,
+                ));
+            }
+        }
+    }
+
+    return Ok(rule);
+}
+
+impl Schema {
+    pub fn from_map(map: &TOMLStringMap) -> Result<Self, ParseError> {
+        let mut names: Vec<&String> = map.tags.keys().collect();
+        names.sort();
+
+        let mut rules: Vec<SchemaRule> = vec![];
+
+        for name in names {
+            rules.push(parse_rule(&map.tags[name])?);
+        }
+
+        return Ok(Schema { rules });
+    }
+
+    pub fn from_string(content: &str) -> Result<Self, ParseError> {
+        return Schema::from_map(&parse_string_map(content)?);
+    }
+
+    pub fn from_file(filepath: &str) -> Result<Self, ParseError> {
+        return match fs::read_to_string(filepath) {
+            Ok(content) => Schema::from_string(content.as_str()),
+            Err(e) => Err(ParseError::new(
+                format!("Failed to read schema `{}`: {}.", filepath, e).as_str(),
+                Position::new(),
+            )),
+        };
+    }
+}
@@ -0,0 +1,536 @@
+// A small regex engine for `pattern` rules. Patterns are compiled to an NFA and every
+// possible path is followed at once, matching is linear in the input and never backtracks.
+// Supports literals, `.`, `^`, `$`, classes (`[a-z]`, `[^0-9]`), `\d \w \s \D \W \S`,
+// groups with alternation (`(a|b)`) and the quantifiers `* + ? {n} {n,} {n,m}`.
+
+#[derive(Debug, Clone, PartialEq)]
+enum ClassItem {
+    Char(char),
+    Range(char, char),
+    Digit(bool),
+    Word(bool),
+    Space(bool),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Char(char),
+    Any,
+    Class(Vec<ClassItem>, bool),
+    Start,
+    End,
+    Group(Vec<Vec<Node>>),
+    Repeat(Box<Node>, usize, Option<usize>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Instruction {
+    Char(char),
+    Any,
+    Class(Vec<ClassItem>, bool),
+    Start,
+    End,
+    Split(usize, usize),
+    Jump(usize),
+    Match,
+}
+
+// Repetitions are expanded, this keeps `a{100000}` from using all the memory
+const MAX_PROGRAM_SIZE: usize = 10_000;
+const MAX_GROUP_DEPTH: usize = 100;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Regex {
+    source: String,
+    program: Vec<Instruction>,
+}
+
+struct RegexParser {
+    chars: Vec<char>,
+    index: usize,
+    depth: usize,
+}
+
+impl RegexParser {
+    fn peek(&self) -> Option<char> {
+        return self.chars.get(self.index).copied();
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c: Option<char> = self.peek();
+        self.index += 1;
+
+        return c;
+    }
+
+    fn error(&self, message: &str) -> String {
+        return format!("{} at position {}", message, self.index);
+    }
+
+    fn parse_alternatives(&mut self) -> Result<Vec<Vec<Node>>, String> {
+        let mut alternatives: Vec<Vec<Node>> = vec![self.parse_sequence()?];
+
+        while self.peek() == Some('|') {
+            self.next();
+            alternatives.push(self.parse_sequence()?);
+        }
+
+        return Ok(alternatives);
+    }
+
+    fn parse_sequence(&mut self) -> Result<Vec<Node>, String> {
+        let mut nodes: Vec<Node> = vec![];
+
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+
+            let atom: Node = self.parse_atom()?;
+            nodes.push(self.parse_quantifier(atom)?);
+        }
+
+        return Ok(nodes);
+    }
+
+    fn parse_atom(&mut self) -> Result<Node, String> {
+        return match self.next() {
+            Some('.') => Ok(Node::Any),
+            Some('^') => Ok(Node::Start),
+            Some('$') => Ok(Node::End),
+            Some('(') => {
+                // Groups are parsed and compiled recursively
+                if self.depth >= MAX_GROUP_DEPTH {
+                    return Err(self.error("Groups are nested too deeply"));
+                }
+
+                self.depth += 1;
+                let alternatives: Vec<Vec<Node>> = self.parse_alternatives()?;
+                self.depth -= 1;
+
+                if self.next() != Some(')') {
+                    return Err(self.error("Unclosed group"));
+                }
+
+                Ok(Node::Group(alternatives))
+            }
+            Some('[') => self.parse_class(),
+            Some('\\') => match self.parse_escape()? {
+                ClassItem::Char(c) => Ok(Node::Char(c)),
+                item => Ok(Node::Class(vec![item], false)),
+            },
+            Some(c @ ('*' | '+' | '?' | '{')) => {
+                Err(self.error(format!("Nothing to repeat with `{}`", c).as_str()))
+            }
+            Some(c) => Ok(Node::Char(c)),
+            None => Err(self.error("Unexpected end of pattern")),
+        };
+    }
+
+    fn parse_escape(&mut self) -> Result<ClassItem, String> {
+        return match self.next() {
+            Some('d') => Ok(ClassItem::Digit(true)),
+            Some('D') => Ok(ClassItem::Digit(false)),
+            Some('w') => Ok(ClassItem::Word(true)),
+            Some('W') => Ok(ClassItem::Word(false)),
+            Some('s') => Ok(ClassItem::Space(true)),
+            Some('S') => Ok(ClassItem::Space(false)),
+            Some('n') => Ok(ClassItem::Char('\n')),
+            Some('t') => Ok(ClassItem::Char('\t')),
+            Some(c) => Ok(ClassItem::Char(c)),
+            None => Err(self.error("Unfinished escape")),
+        };
+    }
+
+    fn parse_class(&mut self) -> Result<Node, String> {
+        let negated: bool = if self.peek() == Some('^') {
+            self.next();
+            true
+        } else {
+            false
+        };
+
+        let mut items: Vec<ClassItem> = vec![];
+
+        loop {
+            let item: ClassItem = match self.next() {
+                // A `]` right after the opening bracket is a literal
+                Some(']') if !items.is_empty() => break,
+                Some('\\') => self.parse_escape()?,
+                Some(c) => ClassItem::Char(c),
+                None => return Err(self.error("Unclosed character class")),
+            };
+
+            // Ranges like `a-z`, a trailing `-` is a literal
+            if let ClassItem::Char(start) = item {
+                if self.peek() == Some('-') && self.chars.get(self.index + 1) != Some(&']') {
+                    self.next();
+
+                    let end: char = match self.next() {
+                        Some('\\') => match self.parse_escape()? {
+                            ClassItem::Char(c) => c,
+                            _ => return Err(self.error("Invalid range")),
+                        },
+                        Some(c) => c,
+                        None => return Err(self.error("Unclosed character class")),
+                    };
+
+                    if end < start {
+                        return Err(self.error("Invalid range"));
+                    }
+
+                    items.push(ClassItem::Range(start, end));
+                    continue;
+                }
+            }
+
+            items.push(item);
+        }
+
+        return Ok(Node::Class(items, negated));
+    }
+
+    fn parse_number(&mut self) -> Option<usize> {
+        let start: usize = self.index;
+
+        while self.peek().map_or(false, |c| c.is_ascii_digit()) {
+            self.next();
+        }
+
+        return self.chars[start..self.index]
+            .iter()
+            .collect::<String>()
+            .parse::<usize>()
+            .ok();
+    }
+
+    fn parse_quantifier(&mut self, atom: Node) -> Result<Node, String> {
+        let (min, max) = match self.peek() {
+            Some('*') => (0, None),
+            Some('+') => (1, None),
+            Some('?') => (0, Some(1)),
+            Some('{') => {
+                self.next();
+
+                let min: usize = match self.parse_number() {
+                    Some(min) => min,
+                    None => return Err(self.error("Expected a number in `{}`")),
+                };
+
+                let max: Option<usize> = if self.peek() == Some(',') {
+                    self.next();
+                    self.parse_number()
+                } else {
+                    Some(min)
+                };
+
+                if self.peek() != Some('}') {
+                    return Err(self.error("Unclosed `{`"));
+                }
+
+                if max.map_or(false, |max| max < min) {
+                    return Err(self.error("Invalid repetition range"));
+                }
+
+                (min, max)
+            }
+            _ => return Ok(atom),
+        };
+
+        self.next();
+
+        if matches!(atom, Node::Start | Node::End) {
+            return Err(self.error("Anchors can't be repeated"));
+        }
+
+        return Ok(Node::Repeat(Box::new(atom), min, max));
+    }
+}
+
+fn match_class_item(item: &ClassItem, c: char) -> bool {
+    return match item {
+        ClassItem::Char(v) => *v == c,
+        ClassItem::Range(start, end) => *start <= c && c <= *end,
+        ClassItem::Digit(v) => c.is_ascii_digit() == *v,
+        ClassItem::Word(v) => (c.is_alphanumeric() || c == '_') == *v,
+        ClassItem::Space(v) => c.is_whitespace() == *v,
+    };
+}
+
+struct Compiler {
+    program: Vec<Instruction>,
+}
+
+impl Compiler {
+    fn emit(&mut self, instruction: Instruction) -> Result<usize, String> {
+        if self.program.len() >= MAX_PROGRAM_SIZE {
+            return Err(String::from("Pattern is too large"));
+        }
+
+        self.program.push(instruction);
+
+        return Ok(self.program.len() - 1);
+    }
+
+    fn compile_sequence(&mut self, nodes: &[Node]) -> Result<(), String> {
+        for node in nodes {
+            self.compile_node(node)?;
+        }
+
+        return Ok(());
+    }
+
+    fn compile_node(&mut self, node: &Node) -> Result<(), String> {
+        match node {
+            Node::Char(c) => {
+                self.emit(Instruction::Char(*c))?;
+            }
+            Node::Any => {
+                self.emit(Instruction::Any)?;
+            }
+            Node::Class(items, negated) => {
+                self.emit(Instruction::Class(items.clone(), *negated))?;
+            }
+            Node::Start => {
+                self.emit(Instruction::Start)?;
+            }
+            Node::End => {
+                self.emit(Instruction::End)?;
+            }
+            Node::Group(alternatives) => {
+                let mut jumps: Vec<usize> = vec![];
+
+                // Every alternative but the last one is tried through a split
+                for (i, alternative) in alternatives.iter().enumerate() {
+                    if i + 1 == alternatives.len() {
+                        self.compile_sequence(alternative)?;
+                        break;
+                    }
+
+                    let split: usize = self.emit(Instruction::Split(0, 0))?;
+                    self.compile_sequence(alternative)?;
+                    jumps.push(self.emit(Instruction::Jump(0))?);
+                    self.program[split] = Instruction::Split(split + 1, self.program.len());
+                }
+
+                for jump in jumps {
+                    self.program[jump] = Instruction::Jump(self.program.len());
+                }
+            }
+            Node::Repeat(node, min, max) => {
+                for _ in 0..*min {
+                    self.compile_node(node)?;
+                }
+
+                match max {
+                    None => {
+                        let split: usize = self.emit(Instruction::Split(0, 0))?;
+                        self.compile_node(node)?;
+                        self.emit(Instruction::Jump(split))?;
+                        self.program[split] = Instruction::Split(split + 1, self.program.len());
+                    }
+                    Some(max) => {
+                        let mut splits: Vec<usize> = vec![];
+
+                        for _ in *min..*max {
+                            splits.push(self.emit(Instruction::Split(0, 0))?);
+                            self.compile_node(node)?;
+                        }
+
+                        for split in splits {
+                            self.program[split] = Instruction::Split(split + 1, self.program.len());
+                        }
+                    }
+                }
+            }
+        }
+
+        return Ok(());
+    }
+}
+
+impl Regex {
+    pub fn new(source: &str) -> Result<Self, String> {
+        let mut parser: RegexParser = RegexParser {
+            chars: source.chars().collect(),
+            index: 0,
+            depth: 0,
+        };
+
+        let alternatives: Vec<Vec<Node>> = parser.parse_alternatives()?;
+
+        if parser.index < parser.chars.len() {
+            return Err(parser.error("Unmatched `)`"));
+        }
+
+        let mut compiler: Compiler = Compiler { program: vec![] };
+        compiler.compile_node(&Node::Group(alternatives))?;
+        compiler.emit(Instruction::Match)?;
+
+        return Ok(Regex {
+            source: String::from(source),
+            program: compiler.program,
+        });
+    }
+
+    pub fn as_str(&self) -> &str {
+        return self.source.as_str();
+    }
+
+    // Follows the jumps and splits from `pc`, the instructions reached are added to `threads`.
+    // An instruction is only added once per position, which also stops empty loops.
+    fn add_thread(
+        &self,
+        threads: &mut Vec<usize>,
+        seen: &mut [bool],
+        pc: usize,
+        pos: usize,
+        len: usize,
+    ) {
+        let mut stack: Vec<usize> = vec![pc];
+
+        while let Some(pc) = stack.pop() {
+            if seen[pc] {
+                continue;
+            }
+
+            seen[pc] = true;
+
+            match &self.program[pc] {
+                Instruction::Jump(to) => stack.push(*to),
+                Instruction::Split(a, b) => {
+                    stack.push(*b);
+                    stack.push(*a);
+                }
+                Instruction::Start if pos == 0 => stack.push(pc + 1),
+                Instruction::End if pos == len => stack.push(pc + 1),
+                Instruction::Start | Instruction::End => {}
+                _ => threads.push(pc),
+            }
+        }
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        let input: Vec<char> = text.chars().collect();
+        let mut threads: Vec<usize> = vec![];
+        let mut seen: Vec<bool> = vec![false; self.program.len()];
+
+        for pos in 0..=input.len() {
+            // Like most engines, the pattern can match anywhere unless anchored with `^` and `$`
+            self.add_thread(&mut threads, &mut seen, 0, pos, input.len());
+
+            if threads
+                .iter()
+                .any(|pc| self.program[*pc] == Instruction::Match)
+            {
+                return true;
+            }
+
+            if pos == input.len() {
+                break;
+            }
+
+            let mut next: Vec<usize> = vec![];
+            seen.fill(false);
+
+            for pc in &threads {
+                let matched: bool = match &self.program[*pc] {
+                    Instruction::Char(c) => input[pos] == *c,
+                    Instruction::Any => input[pos] != '\n',
+                    Instruction::Class(items, negated) => {
+                        items.iter().any(|i| match_class_item(i, input[pos])) != *negated
+                    }
+                    _ => false,
+                };
+
+                if matched {
+                    self.add_thread(&mut next, &mut seen, pc + 1, pos + 1, input.len());
+                }
+            }
+
+            threads = next;
+        }
+
+        return false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_match(pattern: &str, text: &str) -> bool {
+        return Regex::new(pattern).unwrap().is_match(text);
+    }
+
+    #[test]
+    fn literals_and_anchors() {
+        assert!(is_match("abc", "xabcx"));
+        assert!(!is_match("^abc", "xabc"));
+        assert!(!is_match("abc$", "abcx"));
+        assert!(is_match("^$", ""));
+        assert!(is_match("", "anything"));
+        assert!(!is_match("a.c", "a\nc"));
+    }
+
+    #[test]
+    fn classes() {
+        assert!(is_match("^[a-z_]+$", "snake_case"));
+        assert!(!is_match("^[a-z_]+$", "camelCase"));
+        assert!(is_match("^[^0-9]$", "x"));
+        assert!(!is_match("^[^0-9]$", "5"));
+        assert!(is_match("^[]a]$", "]"));
+        assert!(is_match("^[a-]$", "-"));
+        assert!(is_match(r"^\d+\s\w+$", "42 items"));
+        assert!(!is_match(r"^\D$", "4"));
+    }
+
+    #[test]
+    fn groups_and_quantifiers() {
+        assert!(is_match("^(foo|bar)+$", "foobarfoo"));
+        assert!(!is_match("^(foo|bar)+$", "foobaz"));
+        assert!(is_match("^ab?c$", "ac"));
+        assert!(is_match(r"^\d{3}$", "123"));
+        assert!(!is_match(r"^\d{3}$", "1234"));
+        assert!(is_match(r"^\d{2,}$", "12345"));
+        assert!(is_match(r"^\d{1,3}(\.\d{1,3}){3}$", "192.168.0.1"));
+        assert!(!is_match(r"^\d{1,3}(\.\d{1,3}){3}$", "192.168.0"));
+        assert!(is_match("^(a*)*b$", "aab"));
+        assert!(is_match("^(|a)+$", "aa"));
+    }
+
+    #[test]
+    fn long_input() {
+        let text: String = "a".repeat(50_000);
+
+        assert!(is_match("^[a-z]+$", text.as_str()));
+        assert!(!is_match("^[a-z]+$", format!("{}!", text).as_str()));
+    }
+
+    #[test]
+    fn nested_repetition() {
+        // Takes exponential time with backtracking
+        let text: String = format!("{}b", "a".repeat(10_000));
+
+        assert!(!is_match("^(a+)+$", text.as_str()));
+        assert!(!is_match("^(a|aa)*$", text.as_str()));
+    }
+
+    #[test]
+    fn invalid_patterns() {
+        let error = |pattern: &str| Regex::new(pattern).unwrap_err();
+
+        assert_eq!(error("(a"), "Unclosed group at position 3");
+        assert_eq!(error("a)"), "Unmatched `)` at position 1");
+        assert_eq!(error("[a"), "Unclosed character class at position 3");
+        assert_eq!(error("[z-a]"), "Invalid range at position 4");
+        assert_eq!(error("*a"), "Nothing to repeat with `*` at position 1");
+        assert_eq!(error("a{2,1}"), "Invalid repetition range at position 5");
+        assert_eq!(error("^*"), "Anchors can't be repeated at position 2");
+        assert_eq!(error("a{100000}"), "Pattern is too large");
+        assert_eq!(
+            error("(".repeat(100_000).as_str()),
+            "Groups are nested too deeply at position 101"
+        );
+    }
+}
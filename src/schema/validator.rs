@@ -0,0 +1,408 @@
+use crate::parser::ast::AST;
+use crate::parser::converter::TOMLStringMap;
+use crate::parser::path::{format_path, PathSegment};
+use crate::parser::token::Position;
+use crate::parser::tree::{TOMLTable, TOMLTree};
+use crate::parser::writer::convert_ast_value_to_toml;
+use crate::schema::{Schema, SchemaRule, SchemaSegment};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaError {
+    pub path: String,
+    pub message: String,
+    pub position: Option<Position>,
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.message)?;
+        } else {
+            write!(f, "`{}`: {}", self.path, self.message)?;
+        }
+
+        if let Some(position) = &self.position {
+            write!(f, " At line {}, column {}.", position.line, position.column)?;
+        }
+
+        return Ok(());
+    }
+}
+
+impl std::error::Error for SchemaError {}
+
+pub(crate) fn matches_path(rule_path: &[SchemaSegment], path: &[PathSegment]) -> bool {
+    return rule_path.len() == path.len()
+        && rule_path.iter().zip(path).all(|segments| match segments {
+            (SchemaSegment::Any, _) => true,
+            (SchemaSegment::Key(a), PathSegment::Key(b)) => a == b,
+            _ => false,
+        });
+}
+
+fn matches_key(rule: &SchemaRule, key: &str) -> bool {
+    return match rule.path.last() {
+        Some(SchemaSegment::Any) => true,
+        Some(SchemaSegment::Key(k)) => k == key,
+        None => false,
+    };
+}
+
+fn get_type_name(tree: &TOMLTree) -> &str {
+    return match tree {
+        TOMLTree::Value(AST::ASTBool(_), _) => "bool",
+        TOMLTree::Value(AST::ASTInt(_), _) => "int",
+        TOMLTree::Value(AST::ASTString(_), _) => "string",
+        TOMLTree::Value(AST::ASTDateTime(_), _) => "datetime",
+        TOMLTree::Value(AST::ASTArray(_), _) => "array",
+        TOMLTree::Value(_, _) => "value",
+        TOMLTree::Table(_) => "table",
+        TOMLTree::TableArray(_) => "array",
+    };
+}
+
+fn get_length(tree: &TOMLTree) -> Option<usize> {
+    return match tree {
+        TOMLTree::Value(AST::ASTArray(v), _) => Some(v.len()),
+        TOMLTree::Value(AST::ASTString(v), _) => Some(v.chars().count()),
+        TOMLTree::TableArray(v) => Some(v.len()),
+        _ => None,
+    };
+}
+
+struct Validator<'a> {
+    schema: &'a Schema,
+    errors: Vec<SchemaError>,
+}
+
+impl<'a> Validator<'a> {
+    fn error(&mut self, path: &[PathSegment], message: &str, position: Option<Position>) {
+        self.errors.push(SchemaError {
+            path: format_path(path),
+            message: String::from(message),
+            position,
+        });
+    }
+
+    fn check_rule(&mut self, rule: &SchemaRule, tree: &TOMLTree, path: &[PathSegment]) {
+        let position: Option<Position> = tree.get_position();
+
+        // The other checks only make sense for the right type
+        if let Some(value_type) = &rule.value_type {
+            if get_type_name(tree) != value_type.get_name() {
+                self.error(
+                    path,
+                    format!(
+                        "Expected type `{}`, found `{}`.",
+                        value_type.get_name(),
+                        get_type_name(tree)
+                    )
+                    .as_str(),
+                    position,
+                );
+                return;
+            }
+        }
+
+        if let TOMLTree::Value(AST::ASTInt(v), _) = tree {
+            let v: i64 = *v as i64;
+
+            if rule.min.map_or(false, |min| v < min) {
+                self.error(
+                    path,
+                    format!("{} is less than the minimum {}.", v, rule.min.unwrap()).as_str(),
+                    position,
+                );
+            }
+
+            if rule.max.map_or(false, |max| v > max) {
+                self.error(
+                    path,
+                    format!("{} is greater than the maximum {}.", v, rule.max.unwrap()).as_str(),
+                    position,
+                );
+            }
+        }
+
+        if let (TOMLTree::Value(AST::ASTString(v), _), Some(pattern)) = (tree, &rule.pattern) {
+            if !pattern.is_match(v.as_str()) {
+                self.error(
+                    path,
+                    format!(
+                        "\"{}\" doesn't match the pattern `{}`.",
+                        v,
+                        pattern.as_str()
+                    )
+                    .as_str(),
+                    position,
+                );
+            }
+        }
+
+        if let Some(values) = &rule.values {
+            let found: bool = match tree {
+                TOMLTree::Value(ast, _) => values.contains(ast),
+                _ => false,
+            };
+
+            if !found {
+                let names: Vec<String> = values.iter().map(convert_ast_value_to_toml).collect();
+
+                self.error(
+                    path,
+                    format!("Expected one of {}.", names.join(", ")).as_str(),
+                    position,
+                );
+            }
+        }
+
+        if let Some(len) = get_length(tree) {
+            if rule.min_length.map_or(false, |min| len < min) {
+                self.error(
+                    path,
+                    format!(
+                        "Length {} is less than the minimum length {}.",
+                        len,
+                        rule.min_length.unwrap()
+                    )
+                    .as_str(),
+                    position,
+                );
+            }
+
+            if rule.max_length.map_or(false, |max| len > max) {
+                self.error(
+                    path,
+                    format!(
+                        "Length {} is greater than the maximum length {}.",
+                        len,
+                        rule.max_length.unwrap()
+                    )
+                    .as_str(),
+                    position,
+                );
+            }
+        }
+    }
+
+    fn check_table(&mut self, table: &TOMLTable, path: &mut Vec<PathSegment>) {
+        let schema: &Schema = self.schema;
+
+        // Rules for the keys of this table
+        let children: Vec<&SchemaRule> = schema
+            .rules
+            .iter()
+            .filter(|r| r.path.len() == path.len() + 1)
+            .filter(|r| matches_path(&r.path[0..path.len()], path))
+            .collect();
+
+        for rule in &children {
+            if let (true, Some(SchemaSegment::Key(key))) = (rule.required, rule.path.last()) {
+                if table.get(key).is_none() {
+                    path.push(PathSegment::Key(key.clone()));
+                    self.error(path, "Missing required key.", table.position);
+                    path.pop();
+                }
+            }
+        }
+
+        let closed: bool = schema
+            .rules
+            .iter()
+            .any(|r| !r.additional_keys && matches_path(&r.path, path));
+
+        if closed {
+            for (key, tree) in &table.entries {
+                if !children.iter().any(|r| matches_key(r, key)) {
+                    path.push(PathSegment::Key(key.clone()));
+                    self.error(path, "Unexpected key.", tree.get_position());
+                    path.pop();
+                }
+            }
+        }
+    }
+
+    fn walk(&mut self, tree: &TOMLTree, path: &mut Vec<PathSegment>) {
+        let schema: &Schema = self.schema;
+
+        for rule in schema.rules.iter().filter(|r| matches_path(&r.path, path)) {
+            self.check_rule(rule, tree, path);
+        }
+
+        match tree {
+            TOMLTree::Table(table) => {
+                self.check_table(table, path);
+
+                for (key, child) in &table.entries {
+                    path.push(PathSegment::Key(key.clone()));
+                    self.walk(child, path);
+                    path.pop();
+                }
+            }
+            TOMLTree::TableArray(tables) => {
+                for (i, table) in tables.iter().enumerate() {
+                    path.push(PathSegment::Index(i));
+                    self.walk(&TOMLTree::Table(table.clone()), path);
+                    path.pop();
+                }
+            }
+            TOMLTree::Value(AST::ASTArray(values), position) => {
+                for (i, value) in values.iter().enumerate() {
+                    path.push(PathSegment::Index(i));
+                    self.walk(&TOMLTree::Value(value.clone(), *position), path);
+                    path.pop();
+                }
+            }
+            TOMLTree::Value(_, _) => {}
+        }
+    }
+}
+
+impl Schema {
+    // Returns every violation, an empty list means the document is valid
+    pub fn validate(&self, document: &TOMLTable) -> Vec<SchemaError> {
+        let mut validator: Validator = Validator {
+            schema: self,
+            errors: vec![],
+        };
+
+        validator.walk(&TOMLTree::Table(document.clone()), &mut vec![]);
+
+        // Report in source order
+        validator
+            .errors
+            .sort_by_key(|e| e.position.map_or(usize::MAX, |p| p.offset));
+
+        return validator.errors;
+    }
+
+    pub fn validate_map(&self, map: &TOMLStringMap) -> Vec<SchemaError> {
+        return match map.to_tree() {
+            Ok(document) => self.validate(&document),
+            Err(e) => vec![SchemaError {
+                path: String::new(),
+                message: e.message,
+                position: Some(e.position),
+            }],
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::tree::parse_string_map;
+
+    fn validate(schema: &str, document: &str) -> Vec<String> {
+        let schema: Schema = Schema::from_string(schema).unwrap();
+        let map: TOMLStringMap = parse_string_map(document).unwrap();
+
+        return schema
+            .validate_map(&map)
+            .iter()
+            .map(|e| e.to_string())
+            .collect();
+    }
+
+    #[test]
+    fn valid_document() {
+        let schema: &str = "[port]\ntype = \"int\"\nmin = 1\nmax = 65535\n";
+
+        assert!(validate(schema, "port = 80\n").is_empty());
+    }
+
+    #[test]
+    fn wrong_type_skips_other_checks() {
+        let schema: &str = "[port]\ntype = \"int\"\nmin = 1\nmin-length = 5\n";
+
+        assert_eq!(
+            validate(schema, "port = \"80\"\n"),
+            ["`port`: Expected type `int`, found `string`. At line 1, column 1."]
+        );
+    }
+
+    #[test]
+    fn ranges_and_lengths() {
+        let schema: &str = "[port]\nmin = 1\nmax = 10\n\n[name]\nmin-length = 2\nmax-length = 3\n\n[tags]\nmax-length = 1\n";
+        let document: &str = "port = 0\nname = \"a\"\ntags = [\"a\", \"b\"]\n";
+
+        assert_eq!(
+            validate(schema, document),
+            [
+                "`port`: 0 is less than the minimum 1. At line 1, column 1.",
+                "`name`: Length 1 is less than the minimum length 2. At line 2, column 1.",
+                "`tags`: Length 2 is greater than the maximum length 1. At line 3, column 1.",
+            ]
+        );
+    }
+
+    #[test]
+    fn pattern_and_enum() {
+        let schema: &str =
+            "[name]\npattern = \"^[a-z]+$\"\n\n[level]\nenum = [\"low\", \"high\"]\n";
+        let document: &str = "name = \"Name\"\nlevel = \"mid\"\n";
+
+        assert_eq!(
+            validate(schema, document),
+            [
+                "`name`: \"Name\" doesn't match the pattern `^[a-z]+$`. At line 1, column 1.",
+                "`level`: Expected one of \"low\", \"high\". At line 2, column 1.",
+            ]
+        );
+    }
+
+    #[test]
+    fn long_value_against_pattern() {
+        let schema: &str = "[name]\npattern = \"^(a+)+$\"\n";
+        let document: String = format!("name = \"{}b\"\n", "a".repeat(50_000));
+
+        assert_eq!(validate(schema, document.as_str()).len(), 1);
+    }
+
+    #[test]
+    fn required_and_closed_tables() {
+        let schema: &str = "[server]\nadditional-keys = false\n\n[server.host]\nrequired = true\n\n[server.port]\ntype = \"int\"\n";
+        let document: &str = "[server]\nport = 80\nname = \"a\"\n";
+
+        assert_eq!(
+            validate(schema, document),
+            [
+                "`server.host`: Missing required key. At line 1, column 1.",
+                "`server.name`: Unexpected key. At line 3, column 1.",
+            ]
+        );
+    }
+
+    #[test]
+    fn wildcards() {
+        let schema: &str = "[servers.*.port]\ntype = \"int\"\n";
+        let document: &str = "[[servers]]\nport = 1\n\n[[servers]]\nport = true\n";
+
+        assert_eq!(
+            validate(schema, document),
+            ["`servers[1].port`: Expected type `int`, found `bool`. At line 5, column 1."]
+        );
+    }
+
+    #[test]
+    fn invalid_schema() {
+        let error = |schema: &str| Schema::from_string(schema).unwrap_err().message;
+
+        assert_eq!(
+            error("[a]\npattern = \"(a\"\n"),
+            "Invalid pattern `(a`: Unclosed group at position 3."
+        );
+        assert_eq!(error("[a]\ntype = \"float\"\n"), "Unknown type `float`.");
+        assert_eq!(error("[a]\nmin = \"1\"\n"), "`min` must be an integer.");
+        assert_eq!(
+            error("[a]\nmin-length = -1\n"),
+            "`min-length` must be a positive integer."
+        );
+        assert_eq!(
+            error("[a]\nenum = []\n"),
+            "`enum` must be a non empty array."
+        );
+        assert_eq!(error("[a]\nsize = 1\n"), "Unknown schema key `size`.");
+    }
+}
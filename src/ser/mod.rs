@@ -0,0 +1,485 @@
+use crate::de::Error;
+use crate::parser::ast::AST;
+use crate::parser::converter::try_convert_ast_value_to_string;
+use crate::parser::path::PathSegment;
+use crate::parser::token::Token;
+use crate::parser::tree::{TOMLTable, TOMLTree};
+use crate::parser::writer::convert_tree_to_toml;
+use serde::ser::{self, Serialize};
+use std::fmt;
+
+impl ser::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        return Error::new(msg.to_string().as_str());
+    }
+}
+
+pub fn to_string<T: Serialize + ?Sized>(value: &T) -> Result<String, Error> {
+    return convert_value_to_toml(value, false);
+}
+
+pub fn to_string_pretty<T: Serialize + ?Sized>(value: &T) -> Result<String, Error> {
+    return convert_value_to_toml(value, true);
+}
+
+fn convert_value_to_toml<T: Serialize + ?Sized>(value: &T, pretty: bool) -> Result<String, Error> {
+    let table: TOMLTable = match value.serialize(Serializer::new(vec![]))? {
+        Some(TOMLTree::Table(table)) => table,
+        _ => return Err(Error::new("Only tables can be written as a document")),
+    };
+
+    return Ok(convert_tree_to_toml(&table, pretty));
+}
+
+fn check_key(key: &str, path: &[PathSegment]) -> Result<(), Error> {
+    // Dots would be read back as nested tables
+    if key.contains('.') || !Token::is_valid_id(key) {
+        return Err(
+            Error::new(format!("Key `{}` can't be written", key).as_str()).with_context(path, None),
+        );
+    }
+
+    return Ok(());
+}
+
+fn convert_items_to_tree(items: Vec<TOMLTree>, path: &[PathSegment]) -> Result<TOMLTree, Error> {
+    // Arrays of tables are written as `[[name]]`
+    if !items.is_empty() && items.iter().all(|t| matches!(t, TOMLTree::Table(_))) {
+        let tables: Vec<TOMLTable> = items
+            .into_iter()
+            .filter_map(|t| match t {
+                TOMLTree::Table(table) => Some(table),
+                _ => None,
+            })
+            .collect();
+
+        return Ok(TOMLTree::TableArray(tables));
+    }
+
+    let mut values: Vec<AST> = vec![];
+
+    for item in items {
+        if let TOMLTree::Value(ast, _) = item {
+            values.push(ast);
+        } else {
+            return Err(Error::new("Tables can only be written in arrays of tables")
+                .with_context(path, None));
+        }
+    }
+
+    let ast: AST = AST::ASTArray(values);
+
+    // Arrays have to hold a single type
+    if let Err(e) = try_convert_ast_value_to_string(&ast, true) {
+        return Err(Error::new(e.as_str()).with_context(path, None));
+    }
+
+    return Ok(TOMLTree::Value(ast, None));
+}
+
+pub struct Serializer {
+    path: Vec<PathSegment>,
+}
+
+impl Serializer {
+    fn new(path: Vec<PathSegment>) -> Self {
+        return Serializer { path };
+    }
+
+    fn error(&self, message: &str) -> Error {
+        return Error::new(message).with_context(&self.path, None);
+    }
+
+    fn value(&self, ast: AST) -> Result<Option<TOMLTree>, Error> {
+        return Ok(Some(TOMLTree::Value(ast, None)));
+    }
+
+    fn int<T: TryInto<i32> + fmt::Display + Copy>(&self, v: T) -> Result<Option<TOMLTree>, Error> {
+        return match v.try_into() {
+            Ok(v) => self.value(AST::ASTInt(v)),
+            Err(_) => Err(self.error(format!("Integer {} is out of range", v).as_str())),
+        };
+    }
+}
+
+impl ser::Serializer for Serializer {
+    // `None` values are left out of their table
+    type Ok = Option<TOMLTree>;
+    type Error = Error;
+
+    type SerializeSeq = SeqSerializer;
+    type SerializeTuple = SeqSerializer;
+    type SerializeTupleStruct = SeqSerializer;
+    type SerializeTupleVariant = VariantSerializer<SeqSerializer>;
+    type SerializeMap = TableSerializer;
+    type SerializeStruct = TableSerializer;
+    type SerializeStructVariant = VariantSerializer<TableSerializer>;
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok, Error> {
+        return self.value(AST::ASTBool(v));
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok, Error> {
+        return self.int(v);
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok, Error> {
+        return self.int(v);
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok, Error> {
+        return self.int(v);
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok, Error> {
+        return self.int(v);
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok, Error> {
+        return self.int(v);
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok, Error> {
+        return self.int(v);
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok, Error> {
+        return self.int(v);
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok, Error> {
+        return self.int(v);
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<Self::Ok, Error> {
+        return Err(self.error("Floats are not supported"));
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<Self::Ok, Error> {
+        return Err(self.error("Floats are not supported"));
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok, Error> {
+        return self.serialize_str(v.to_string().as_str());
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, Error> {
+        // Strings have no escapes
+        if v.contains('"') {
+            return Err(self.error("Strings can't contain `\"`"));
+        }
+
+        return self.value(AST::ASTString(String::from(v)));
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Error> {
+        let values: Vec<AST> = v.iter().map(|b| AST::ASTInt(*b as i32)).collect();
+
+        return self.value(AST::ASTArray(values));
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, Error> {
+        return Ok(None);
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Self::Ok, Error> {
+        return value.serialize(self);
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Error> {
+        return Err(self.error("Unit values are not supported"));
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Error> {
+        return self.serialize_unit();
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok, Error> {
+        return self.serialize_str(variant);
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Error> {
+        return value.serialize(self);
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Error> {
+        // Written as a table with a single key, like the deserializer expects
+        let mut table: TableSerializer = TableSerializer::new(self.path);
+        ser::SerializeStruct::serialize_field(&mut table, variant, value)?;
+
+        return ser::SerializeStruct::end(table);
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqSerializer, Error> {
+        return Ok(SeqSerializer::new(self.path, len.unwrap_or(0)));
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SeqSerializer, Error> {
+        return self.serialize_seq(Some(len));
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SeqSerializer, Error> {
+        return self.serialize_seq(Some(len));
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<VariantSerializer<SeqSerializer>, Error> {
+        let mut path: Vec<PathSegment> = self.path.clone();
+        path.push(PathSegment::Key(String::from(variant)));
+
+        return Ok(VariantSerializer {
+            variant,
+            path: self.path,
+            inner: SeqSerializer::new(path, len),
+        });
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<TableSerializer, Error> {
+        return Ok(TableSerializer::new(self.path));
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<TableSerializer, Error> {
+        return Ok(TableSerializer::new(self.path));
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<VariantSerializer<TableSerializer>, Error> {
+        let mut path: Vec<PathSegment> = self.path.clone();
+        path.push(PathSegment::Key(String::from(variant)));
+
+        return Ok(VariantSerializer {
+            variant,
+            path: self.path,
+            inner: TableSerializer::new(path),
+        });
+    }
+}
+
+pub struct SeqSerializer {
+    items: Vec<TOMLTree>,
+    path: Vec<PathSegment>,
+}
+
+impl SeqSerializer {
+    fn new(path: Vec<PathSegment>, len: usize) -> Self {
+        return SeqSerializer {
+            items: Vec::with_capacity(len),
+            path,
+        };
+    }
+
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let mut path: Vec<PathSegment> = self.path.clone();
+        path.push(PathSegment::Index(self.items.len()));
+
+        return match value.serialize(Serializer::new(path.clone()))? {
+            Some(tree) => {
+                self.items.push(tree);
+                Ok(())
+            }
+            None => {
+                Err(Error::new("`None` can't be written inside an array").with_context(&path, None))
+            }
+        };
+    }
+
+    fn finish(self) -> Result<Option<TOMLTree>, Error> {
+        return Ok(Some(convert_items_to_tree(self.items, &self.path)?));
+    }
+}
+
+impl ser::SerializeSeq for SeqSerializer {
+    type Ok = Option<TOMLTree>;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        return self.push(value);
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        return self.finish();
+    }
+}
+
+impl ser::SerializeTuple for SeqSerializer {
+    type Ok = Option<TOMLTree>;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        return self.push(value);
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        return self.finish();
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqSerializer {
+    type Ok = Option<TOMLTree>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        return self.push(value);
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        return self.finish();
+    }
+}
+
+pub struct TableSerializer {
+    table: TOMLTable,
+    key: Option<String>,
+    path: Vec<PathSegment>,
+}
+
+impl TableSerializer {
+    fn new(path: Vec<PathSegment>) -> Self {
+        return TableSerializer {
+            table: TOMLTable::new(),
+            key: None,
+            path,
+        };
+    }
+
+    fn insert<T: Serialize + ?Sized>(&mut self, key: &str, value: &T) -> Result<(), Error> {
+        check_key(key, &self.path)?;
+
+        let mut path: Vec<PathSegment> = self.path.clone();
+        path.push(PathSegment::Key(String::from(key)));
+
+        if let Some(tree) = value.serialize(Serializer::new(path))? {
+            self.table.insert(key, tree);
+        }
+
+        return Ok(());
+    }
+}
+
+impl ser::SerializeMap for TableSerializer {
+    type Ok = Option<TOMLTree>;
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        self.key = match key.serialize(Serializer::new(self.path.clone()))? {
+            Some(TOMLTree::Value(AST::ASTString(key), _)) => Some(key),
+            _ => {
+                return Err(Error::new("Map keys must be strings").with_context(&self.path, None));
+            }
+        };
+
+        return Ok(());
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let key: String = self
+            .key
+            .take()
+            .expect("[ERROR] Value written before its key.");
+
+        return self.insert(key.as_str(), value);
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        return Ok(Some(TOMLTree::Table(self.table)));
+    }
+}
+
+impl ser::SerializeStruct for TableSerializer {
+    type Ok = Option<TOMLTree>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        return self.insert(key, value);
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        return Ok(Some(TOMLTree::Table(self.table)));
+    }
+}
+
+pub struct VariantSerializer<S> {
+    variant: &'static str,
+    path: Vec<PathSegment>,
+    inner: S,
+}
+
+impl<S> VariantSerializer<S> {
+    fn wrap(
+        variant: &str,
+        path: &[PathSegment],
+        tree: TOMLTree,
+    ) -> Result<Option<TOMLTree>, Error> {
+        check_key(variant, path)?;
+
+        let mut table: TOMLTable = TOMLTable::new();
+        table.insert(variant, tree);
+
+        return Ok(Some(TOMLTree::Table(table)));
+    }
+}
+
+impl ser::SerializeTupleVariant for VariantSerializer<SeqSerializer> {
+    type Ok = Option<TOMLTree>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        return self.inner.push(value);
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        let tree: TOMLTree = convert_items_to_tree(self.inner.items, &self.inner.path)?;
+
+        return Self::wrap(self.variant, &self.path, tree);
+    }
+}
+
+impl ser::SerializeStructVariant for VariantSerializer<TableSerializer> {
+    type Ok = Option<TOMLTree>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        return self.inner.insert(key, value);
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        return Self::wrap(self.variant, &self.path, TOMLTree::Table(self.inner.table));
+    }
+}
@@ -0,0 +1,497 @@
+use crate::parser::ast::AST;
+use crate::parser::converter::{get_value_type, try_convert_ast_value_to_string};
+use crate::parser::error::ParseError;
+use crate::parser::parser::is_datetime;
+use crate::parser::token::{Position, Token};
+use crate::parser::tree::{TOMLTable, TOMLTree};
+use std::collections::HashMap;
+use yaml_rust2::parser::{Event, MarkedEventReceiver, Parser, Tag};
+use yaml_rust2::scanner::{Marker, TScalarStyle};
+
+// TOML documents map to YAML 1.2 block mappings in key order. Datetimes are written as
+// plain scalars, which YAML reads as timestamps, and plain scalars that look like a
+// datetime are read back as one. The parser doesn't keep TOML comments, so YAML comments
+// are dropped when reading and none are written.
+
+#[derive(Debug, Clone, PartialEq)]
+enum YAMLNode {
+    Scalar(String, TScalarStyle, Option<Tag>, Position),
+    Sequence(Vec<YAMLNode>, Position),
+    Mapping(Vec<(YAMLNode, YAMLNode)>, Position),
+}
+
+impl YAMLNode {
+    fn get_position(&self) -> Position {
+        return match self {
+            YAMLNode::Scalar(_, _, _, position)
+            | YAMLNode::Sequence(_, position)
+            | YAMLNode::Mapping(_, position) => *position,
+        };
+    }
+}
+
+fn convert_marker_to_position(mark: Marker) -> Position {
+    let mut position: Position = Position::new();
+    position.line = mark.line();
+    position.column = mark.col() + 1;
+    position.offset = mark.index();
+
+    return position;
+}
+
+// Open sequences and mappings, mappings keep the key waiting for its value
+enum Frame {
+    Sequence(Vec<YAMLNode>, Position),
+    Mapping(Vec<(YAMLNode, YAMLNode)>, Option<YAMLNode>, Position),
+}
+
+#[derive(Default)]
+struct YAMLBuilder {
+    stack: Vec<(Frame, usize)>,
+    anchors: HashMap<usize, YAMLNode>,
+    root: Option<YAMLNode>,
+    error: Option<ParseError>,
+}
+
+impl YAMLBuilder {
+    fn push_node(&mut self, node: YAMLNode, anchor: usize) {
+        if anchor > 0 {
+            self.anchors.insert(anchor, node.clone());
+        }
+
+        match self.stack.last_mut() {
+            Some((Frame::Sequence(values, _), _)) => values.push(node),
+            Some((Frame::Mapping(entries, key, _), _)) => match key.take() {
+                Some(key) => entries.push((key, node)),
+                None => *key = Some(node),
+            },
+            None => self.root = Some(node),
+        }
+    }
+}
+
+impl MarkedEventReceiver for YAMLBuilder {
+    fn on_event(&mut self, event: Event, mark: Marker) {
+        let position: Position = convert_marker_to_position(mark);
+
+        match event {
+            Event::Scalar(value, style, anchor, tag) => {
+                self.push_node(YAMLNode::Scalar(value, style, tag, position), anchor);
+            }
+            Event::SequenceStart(anchor, _) => {
+                self.stack.push((Frame::Sequence(vec![], position), anchor));
+            }
+            Event::MappingStart(anchor, _) => {
+                self.stack
+                    .push((Frame::Mapping(vec![], None, position), anchor));
+            }
+            Event::SequenceEnd | Event::MappingEnd => match self.stack.pop() {
+                Some((Frame::Sequence(values, position), anchor)) => {
+                    self.push_node(YAMLNode::Sequence(values, position), anchor);
+                }
+                Some((Frame::Mapping(entries, _, position), anchor)) => {
+                    self.push_node(YAMLNode::Mapping(entries, position), anchor);
+                }
+                None => {}
+            },
+            Event::Alias(anchor) => match self.anchors.get(&anchor) {
+                Some(node) => self.push_node(node.clone(), 0),
+                None => {
+                    self.error
+                        .get_or_insert(ParseError::new("Unknown alias.", position));
+                }
+            },
+            _ => {}
+        }
+    }
+}
+
+fn error(message: &str, node: &YAMLNode) -> ParseError {
+    return ParseError::new(message, node.get_position());
+}
+
+fn is_null(value: &str) -> bool {
+    return matches!(value, "" | "~" | "null" | "Null" | "NULL");
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    return match value {
+        "true" | "True" | "TRUE" => Some(true),
+        "false" | "False" | "FALSE" => Some(false),
+        _ => None,
+    };
+}
+
+fn parse_int(value: &str) -> Option<i64> {
+    let (sign, digits) = match value.strip_prefix('-') {
+        Some(digits) => (-1, digits),
+        None => (1, value.strip_prefix('+').unwrap_or(value)),
+    };
+
+    let parsed: Option<i64> = if let Some(hex) = digits.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16).ok()
+    } else if let Some(octal) = digits.strip_prefix("0o") {
+        i64::from_str_radix(octal, 8).ok()
+    } else if !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit()) {
+        digits.parse::<i64>().ok()
+    } else {
+        None
+    };
+
+    return parsed.map(|v| v * sign);
+}
+
+fn is_float(value: &str) -> bool {
+    let lower: String = value.to_lowercase();
+
+    return matches!(lower.trim_start_matches(['+', '-']), ".inf" | ".nan")
+        || (value.contains(['.', 'e', 'E'])
+            && value.parse::<f64>().is_ok()
+            && value.chars().any(|c| c.is_ascii_digit()));
+}
+
+fn convert_int(value: i64, node: &YAMLNode) -> Result<AST, ParseError> {
+    return match i32::try_from(value) {
+        Ok(v) => Ok(AST::ASTInt(v)),
+        Err(_) => Err(error(
+            format!("{} doesn't fit in a 32 bit integer.", value).as_str(),
+            node,
+        )),
+    };
+}
+
+fn convert_string(value: &str, node: &YAMLNode) -> Result<AST, ParseError> {
+    // Strings have no escapes
+    if value.contains(['"', '\0']) {
+        return Err(error(
+            "Strings containing `\"` can't be represented in TOML.",
+            node,
+        ));
+    }
+
+    return Ok(AST::ASTString(String::from(value)));
+}
+
+fn convert_tagged_scalar(value: &str, tag: &Tag, node: &YAMLNode) -> Result<AST, ParseError> {
+    if tag.handle != "tag:yaml.org,2002:" && tag.handle != "!!" {
+        return Err(error(
+            format!("Unsupported tag `{}{}`.", tag.handle, tag.suffix).as_str(),
+            node,
+        ));
+    }
+
+    return match tag.suffix.as_str() {
+        "str" => convert_string(value, node),
+        "bool" => match parse_bool(value) {
+            Some(v) => Ok(AST::ASTBool(v)),
+            None => Err(error(format!("\"{}\" isn't a bool.", value).as_str(), node)),
+        },
+        "int" => match parse_int(value) {
+            Some(v) => convert_int(v, node),
+            None => Err(error(
+                format!("\"{}\" isn't an integer.", value).as_str(),
+                node,
+            )),
+        },
+        "timestamp" if is_datetime(value) => Ok(AST::ASTDateTime(String::from(value))),
+        "timestamp" => Err(error(
+            format!("\"{}\" isn't a supported timestamp.", value).as_str(),
+            node,
+        )),
+        "null" => Err(error("Null can't be represented in TOML.", node)),
+        "float" => Err(error("Floats can't be represented in TOML.", node)),
+        _ => Err(error(
+            format!("Unsupported tag `!!{}`.", tag.suffix).as_str(),
+            node,
+        )),
+    };
+}
+
+// Plain scalars are resolved like the YAML 1.2 core schema, plus timestamps
+fn convert_scalar(node: &YAMLNode) -> Result<AST, ParseError> {
+    let (value, style, tag) = match node {
+        YAMLNode::Scalar(value, style, tag, _) => (value.as_str(), *style, tag),
+        _ => return Err(error("Expected a scalar.", node)),
+    };
+
+    if let Some(tag) = tag {
+        return convert_tagged_scalar(value, tag, node);
+    }
+
+    if style != TScalarStyle::Plain {
+        return convert_string(value, node);
+    }
+
+    if is_null(value) {
+        return Err(error("Null can't be represented in TOML.", node));
+    }
+
+    if let Some(v) = parse_bool(value) {
+        return Ok(AST::ASTBool(v));
+    }
+
+    if let Some(v) = parse_int(value) {
+        return convert_int(v, node);
+    }
+
+    if is_float(value) {
+        return Err(error("Floats can't be represented in TOML.", node));
+    }
+
+    if is_datetime(value) {
+        return Ok(AST::ASTDateTime(String::from(value)));
+    }
+
+    return convert_string(value, node);
+}
+
+fn convert_node_to_ast(node: &YAMLNode) -> Result<AST, ParseError> {
+    return match node {
+        YAMLNode::Scalar(_, _, _, _) => convert_scalar(node),
+        YAMLNode::Sequence(values, _) => {
+            let array: Vec<AST> = values
+                .iter()
+                .map(convert_node_to_ast)
+                .collect::<Result<Vec<AST>, ParseError>>()?;
+
+            // Arrays hold a single type
+            let types: Vec<String> = array
+                .iter()
+                .map(|v| match try_convert_ast_value_to_string(v, true) {
+                    Ok(s) => String::from(get_value_type(s.as_str()).unwrap_or("array")),
+                    Err(_) => String::from("array"),
+                })
+                .collect();
+
+            if let Some(other) = types.iter().find(|t| *t != &types[0]) {
+                return Err(error(
+                    format!(
+                        "Arrays can't mix types, found `{}` and `{}`.",
+                        types[0], other
+                    )
+                    .as_str(),
+                    node,
+                ));
+            }
+
+            Ok(AST::ASTArray(array))
+        }
+        YAMLNode::Mapping(_, _) => Err(error("Arrays can't hold tables and values.", node)),
+    };
+}
+
+fn convert_node_to_tree(node: &YAMLNode) -> Result<TOMLTree, ParseError> {
+    return match node {
+        YAMLNode::Mapping(_, _) => Ok(TOMLTree::Table(convert_node_to_table(node)?)),
+        // Sequences of mappings are arrays of tables
+        YAMLNode::Sequence(values, _)
+            if !values.is_empty()
+                && values.iter().all(|v| matches!(v, YAMLNode::Mapping(_, _))) =>
+        {
+            Ok(TOMLTree::TableArray(
+                values
+                    .iter()
+                    .map(convert_node_to_table)
+                    .collect::<Result<Vec<TOMLTable>, ParseError>>()?,
+            ))
+        }
+        _ => Ok(TOMLTree::Value(
+            convert_node_to_ast(node)?,
+            Some(node.get_position()),
+        )),
+    };
+}
+
+fn convert_node_to_table(node: &YAMLNode) -> Result<TOMLTable, ParseError> {
+    let entries: &Vec<(YAMLNode, YAMLNode)> = match node {
+        YAMLNode::Mapping(entries, _) => entries,
+        _ => return Err(error("Expected a mapping.", node)),
+    };
+
+    let mut table: TOMLTable = TOMLTable::new();
+    table.position = Some(node.get_position());
+
+    let mut merged: Vec<&YAMLNode> = vec![];
+
+    for (key_node, value) in entries {
+        let key: &str = match key_node {
+            YAMLNode::Scalar(key, TScalarStyle::Plain, None, _) if key == "<<" => {
+                match value {
+                    YAMLNode::Sequence(values, _) => merged.extend(values),
+                    _ => merged.push(value),
+                }
+                continue;
+            }
+            YAMLNode::Scalar(key, _, _, _) => key.as_str(),
+            _ => return Err(error("Keys must be scalars.", key_node)),
+        };
+
+        // Dots would be read back as nested tables
+        if key.contains('.') || !Token::is_valid_id(key) {
+            return Err(error(
+                format!("Key `{}` can't be represented in TOML.", key).as_str(),
+                key_node,
+            ));
+        }
+
+        if table.get(key).is_some() {
+            return Err(error(
+                format!("Key `{}` is defined twice.", key).as_str(),
+                key_node,
+            ));
+        }
+
+        table.insert(key, convert_node_to_tree(value)?);
+    }
+
+    // Merge keys (`<<: *defaults`) add what the mapping doesn't set itself
+    for source in merged {
+        for (key, tree) in convert_node_to_table(source)?.entries {
+            if table.get(key.as_str()).is_none() {
+                table.insert(key.as_str(), tree);
+            }
+        }
+    }
+
+    return Ok(table);
+}
+
+// Reads the first document, it has to be a mapping
+pub fn convert_yaml_to_tree(content: &str) -> Result<TOMLTable, ParseError> {
+    let mut builder: YAMLBuilder = YAMLBuilder::default();
+
+    if let Err(e) = Parser::new_from_str(content).load(&mut builder, false) {
+        return Err(ParseError::new(
+            format!("{}.", e.info()).as_str(),
+            convert_marker_to_position(*e.marker()),
+        ));
+    }
+
+    if let Some(e) = builder.error {
+        return Err(e);
+    }
+
+    return match &builder.root {
+        Some(node @ YAMLNode::Mapping(_, _)) => convert_node_to_table(node),
+        Some(node) => Err(error(
+            "The top level of the document must be a mapping.",
+            node,
+        )),
+        None => Ok(TOMLTable::new()),
+    };
+}
+
+// Plain scalars that YAML would read as another type, or that hold indicators, get quoted
+fn needs_quotes(value: &str) -> bool {
+    return value.is_empty()
+        || value.starts_with([
+            '-', '?', ':', ',', '[', ']', '{', '}', '#', '&', '*', '!', '|', '>', '\'', '"', '%',
+            '@', '`', ' ',
+        ])
+        || value.ends_with([' ', ':'])
+        || value.contains(": ")
+        || value.contains(" #")
+        || value.contains([',', '[', ']', '{', '}'])
+        || value.contains(|c: char| c.is_control())
+        || is_null(value)
+        || parse_bool(value).is_some()
+        || matches!(
+            value.to_lowercase().as_str(),
+            "y" | "n" | "yes" | "no" | "on" | "off"
+        )
+        || parse_int(value).is_some()
+        || is_float(value)
+        || value.parse::<f64>().is_ok()
+        || is_datetime(value);
+}
+
+fn convert_str_to_yaml(value: &str) -> String {
+    if !needs_quotes(value) {
+        return String::from(value);
+    }
+
+    let mut string: String = String::from("\"");
+
+    for c in value.chars() {
+        match c {
+            '"' => string += "\\\"",
+            '\\' => string += "\\\\",
+            '\n' => string += "\\n",
+            '\t' => string += "\\t",
+            c if c.is_control() => string += format!("\\u{:04x}", c as u32).as_str(),
+            c => string.push(c),
+        }
+    }
+
+    string.push('"');
+
+    return string;
+}
+
+fn convert_ast_to_yaml(value: &AST) -> String {
+    return match value {
+        AST::ASTBool(v) => v.to_string(),
+        AST::ASTInt(v) => v.to_string(),
+        AST::ASTDateTime(v) => v.clone(),
+        AST::ASTString(v) => convert_str_to_yaml(v),
+        // Arrays of values are written in flow style
+        AST::ASTArray(values) => {
+            let elements: Vec<String> = values.iter().map(convert_ast_to_yaml).collect();
+            format!("[{}]", elements.join(", "))
+        }
+        _ => String::from("null"),
+    };
+}
+
+fn convert_table_to_yaml(table: &TOMLTable, indent: usize, in_item: bool, string: &mut String) {
+    let pad: String = " ".repeat(indent);
+
+    for (i, (key, tree)) in table.entries.iter().enumerate() {
+        // The first key of a sequence item follows its `- `
+        if i > 0 || !in_item {
+            *string += pad.as_str();
+        }
+
+        *string += convert_str_to_yaml(key).as_str();
+        *string += ":";
+
+        match tree {
+            TOMLTree::Value(ast, _) => {
+                *string += " ";
+                *string += convert_ast_to_yaml(ast).as_str();
+                *string += "\n";
+            }
+            TOMLTree::Table(sub_table) if sub_table.entries.is_empty() => *string += " {}\n",
+            TOMLTree::Table(sub_table) => {
+                *string += "\n";
+                convert_table_to_yaml(sub_table, indent + 2, false, string);
+            }
+            TOMLTree::TableArray(tables) => {
+                *string += "\n";
+
+                for sub_table in tables {
+                    *string += pad.as_str();
+                    *string += "  - ";
+
+                    if sub_table.entries.is_empty() {
+                        *string += "{}\n";
+                    } else {
+                        convert_table_to_yaml(sub_table, indent + 4, true, string);
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub fn convert_tree_to_yaml(table: &TOMLTable) -> String {
+    if table.entries.is_empty() {
+        return String::from("{}\n");
+    }
+
+    let mut string: String = String::new();
+
+    convert_table_to_yaml(table, 0, false, &mut string);
+
+    return string;
+}
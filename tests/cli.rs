@@ -0,0 +1,280 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+// Each test writes its files to its own directory
+fn write_file(test: &str, name: &str, content: &str) -> PathBuf {
+    let dir: PathBuf = std::env::temp_dir().join(format!("toml-cli-{}", test));
+    fs::create_dir_all(&dir).unwrap();
+
+    let path: PathBuf = dir.join(name);
+    fs::write(&path, content).unwrap();
+
+    return path;
+}
+
+fn toml(args: &[&str], env: &[(&str, &str)]) -> (i32, String, String) {
+    let output: Output = Command::new(env!("CARGO_BIN_EXE_toml"))
+        .args(args)
+        .envs(env.iter().copied())
+        .output()
+        .unwrap();
+
+    return (
+        output.status.code().unwrap(),
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    );
+}
+
+#[test]
+fn overlay_env_default_separator() {
+    let path: PathBuf = write_file(
+        "overlay-env",
+        "config.toml",
+        "[server]\nport = 80\nmax_connections = 10\n",
+    );
+    let path: &str = path.to_str().unwrap();
+
+    let (code, stdout, stderr) = toml(
+        &["overlay-env", "--prefix", "APP", path],
+        &[
+            ("APP__SERVER__PORT", "8080"),
+            ("APP__SERVER__MAX_CONNECTIONS", "20"),
+        ],
+    );
+
+    assert_eq!(code, 0, "{}", stderr);
+    assert!(stdout.contains("port = 8080"), "{}", stdout);
+    assert!(stdout.contains("max_connections = 20"), "{}", stdout);
+}
+
+#[test]
+fn overlay_env_separator_flag() {
+    let path: PathBuf = write_file("overlay-env-flag", "config.toml", "[server]\nport = 80\n");
+    let path: &str = path.to_str().unwrap();
+
+    let (code, stdout, stderr) = toml(
+        &["overlay-env", "--prefix", "APP", "--separator", "_", path],
+        &[("APP_SERVER_PORT", "8080")],
+    );
+
+    assert_eq!(code, 0, "{}", stderr);
+    assert!(stdout.contains("port = 8080"), "{}", stdout);
+}
+
+#[test]
+fn patch_dry_run() {
+    let document: PathBuf = write_file("patch-dry-run", "doc.toml", "[server]\nport = 80\n");
+    let patch: PathBuf = write_file(
+        "patch-dry-run",
+        "p.json",
+        r#"[{"op": "replace", "path": "/server/port", "value": 8080}]"#,
+    );
+
+    let (code, stdout, stderr) = toml(
+        &[
+            "patch",
+            "--dry-run",
+            patch.to_str().unwrap(),
+            document.to_str().unwrap(),
+        ],
+        &[],
+    );
+
+    assert_eq!(code, 0, "{}", stderr);
+    assert!(stdout.contains("~ server.port = 80 -> 8080"), "{}", stdout);
+    assert_eq!(
+        fs::read_to_string(&document).unwrap(),
+        "[server]\nport = 80\n"
+    );
+}
+
+#[test]
+fn patch_malformed() {
+    let document: PathBuf = write_file("patch-malformed", "doc.toml", "[server]\nport = 80\n");
+    let patch: PathBuf = write_file("patch-malformed", "p.json", r#"{"op": "remove"}"#);
+
+    let (code, _, stderr) = toml(
+        &["patch", patch.to_str().unwrap(), document.to_str().unwrap()],
+        &[],
+    );
+
+    assert_eq!(code, 2);
+    assert!(
+        stderr.contains("Invalid patch, expected an array of operations."),
+        "{}",
+        stderr
+    );
+    assert_eq!(
+        fs::read_to_string(&document).unwrap(),
+        "[server]\nport = 80\n"
+    );
+}
+
+#[test]
+fn patch_prints_without_in_place() {
+    let document: PathBuf =
+        write_file("patch-print", "doc.toml", "[server]\nport = 80 # default\n");
+    let patch: PathBuf = write_file(
+        "patch-print",
+        "p.toml",
+        "[[operations]]\nop = \"replace\"\npath = \"server.port\"\nvalue = 8080\n",
+    );
+    let files: [&str; 2] = [patch.to_str().unwrap(), document.to_str().unwrap()];
+
+    let (code, stdout, stderr) = toml(&[&["patch"], &files[..]].concat(), &[]);
+
+    assert_eq!(code, 0, "{}", stderr);
+    assert_eq!(stdout, "[server]\nport = 8080 # default\n");
+    assert_eq!(
+        fs::read_to_string(&document).unwrap(),
+        "[server]\nport = 80 # default\n"
+    );
+
+    let (code, stdout, stderr) = toml(&[&["patch", "--in-place"], &files[..]].concat(), &[]);
+
+    assert_eq!(code, 0, "{}", stderr);
+    assert_eq!(stdout, "");
+    assert_eq!(
+        fs::read_to_string(&document).unwrap(),
+        "[server]\nport = 8080 # default\n"
+    );
+}
+
+#[test]
+fn patch_failed_operation() {
+    let document: PathBuf = write_file("patch-failed", "doc.toml", "a = 1\n");
+    let patch: PathBuf = write_file(
+        "patch-failed",
+        "p.json",
+        r#"[{"op": "remove", "path": "/a"}, {"op": "remove", "path": "/b"}]"#,
+    );
+
+    let (code, _, stderr) = toml(
+        &[
+            "patch",
+            "--in-place",
+            patch.to_str().unwrap(),
+            document.to_str().unwrap(),
+        ],
+        &[],
+    );
+
+    assert_eq!(code, 1);
+    assert!(
+        stderr.contains("Operation 2: `b` doesn't exist."),
+        "{}",
+        stderr
+    );
+    assert_eq!(fs::read_to_string(&document).unwrap(), "a = 1\n");
+}
+
+#[test]
+fn get_values() {
+    let path: PathBuf = write_file(
+        "get",
+        "doc.toml",
+        "name = \"app\"\n\n[server]\nports = [80, 443]\n",
+    );
+    let path: &str = path.to_str().unwrap();
+
+    assert_eq!(toml(&["get", "name", path], &[]).1, "app\n");
+    assert_eq!(toml(&["get", "server.ports[1]", path], &[]).1, "443\n");
+    assert_eq!(
+        toml(&["get", "server", path], &[]).1,
+        "ports = [\n    80,\n    443\n]\n"
+    );
+    assert_eq!(
+        toml(&["--output", "json", "get", "server.ports", path], &[]).1,
+        "[\n  80,\n  443\n]\n"
+    );
+
+    let (code, _, stderr) = toml(&["get", "server.host", path], &[]);
+
+    assert_eq!(code, 1);
+    assert!(
+        stderr.contains("`server.host` isn't in the document."),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn set_replaces_existing_values() {
+    let path: PathBuf = write_file("set-replace", "doc.toml", "[s]\nc = [1, 2] # list\nd = 1\n");
+    let path: &str = path.to_str().unwrap();
+
+    let (code, stdout, stderr) = toml(&["set", "s.c[0]", "9", path], &[]);
+    assert_eq!(code, 0, "{}", stderr);
+    assert_eq!(stdout, "[s]\nc = [9, 2] # list\nd = 1\n");
+
+    let (code, stdout, stderr) = toml(&["set", "--string", "s.d", "2", path], &[]);
+    assert_eq!(code, 0, "{}", stderr);
+    assert_eq!(stdout, "[s]\nc = [1, 2] # list\nd = \"2\"\n");
+}
+
+#[test]
+fn set_creates_missing_tables() {
+    let path: PathBuf = write_file("set-add", "doc.toml", "[s]\nd = 1\n");
+    let path: &str = path.to_str().unwrap();
+
+    let (code, _, stderr) = toml(&["set", "--in-place", "s.e.f", "3", path], &[]);
+
+    assert_eq!(code, 0, "{}", stderr);
+    assert_eq!(
+        fs::read_to_string(path).unwrap(),
+        "[s]\nd = 1\n\n[s.e]\nf = 3\n"
+    );
+
+    let (code, _, stderr) = toml(&["set", "s.c[0]", "9", path], &[]);
+
+    assert_eq!(code, 1);
+    assert!(stderr.contains("isn't in the document."), "{}", stderr);
+}
+
+#[test]
+fn fmt_check_and_in_place() {
+    let path: PathBuf = write_file("fmt", "doc.toml", "a=1\n[t]\nb   =  2\n");
+    let path: &str = path.to_str().unwrap();
+
+    let (code, _, _) = toml(&["fmt", "--check", path], &[]);
+    assert_eq!(code, 1);
+
+    let (code, stdout, stderr) = toml(&["fmt", path], &[]);
+    assert_eq!(code, 0, "{}", stderr);
+    assert_eq!(fs::read_to_string(path).unwrap(), "a=1\n[t]\nb   =  2\n");
+
+    let (code, _, stderr) = toml(&["fmt", "--in-place", path], &[]);
+    assert_eq!(code, 0, "{}", stderr);
+    assert_eq!(fs::read_to_string(path).unwrap(), stdout);
+
+    let (code, _, _) = toml(&["fmt", "--check", path], &[]);
+    assert_eq!(code, 0);
+}
+
+#[test]
+fn lint_fix() {
+    let path: PathBuf = write_file("lint-fix", "doc.toml", "\"a\" = 1\nmaxSize = \"2\"\n");
+    let path: &str = path.to_str().unwrap();
+
+    // The warning is left
+    let (code, stdout, _) = toml(&["lint", "--fix", path], &[]);
+
+    assert_eq!(code, 1);
+    assert!(
+        stdout.contains("suggestion: Write `2` without quotes."),
+        "{}",
+        stdout
+    );
+    assert_eq!(
+        fs::read_to_string(path).unwrap(),
+        "a = 1\nmaxSize = \"2\"\n"
+    );
+
+    let (code, stdout, stderr) = toml(&["lint", "--unsafe-fixes", path], &[]);
+
+    assert_eq!(code, 0, "{}", stderr);
+    assert_eq!(stdout, "");
+    assert_eq!(fs::read_to_string(path).unwrap(), "a = 1\nmax_size = 2\n");
+}